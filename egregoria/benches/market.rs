@@ -0,0 +1,74 @@
+//! Compares the spatial market matcher against the naive O(n²) one.
+//! Run with `cargo bench -p egregoria --bench market`
+
+use egregoria::economy::{CommodityKind, Market, Money};
+use egregoria::SoulID;
+use geom::{vec2, Vec2};
+use legion::World;
+use std::time::{Duration, Instant};
+
+/// Small xorshift so that every run uses the same orders
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % 1_000_000) as f32 / 1_000_000.0
+    }
+
+    fn pos(&mut self, size: f32) -> Vec2 {
+        vec2(self.next() * size, self.next() * size)
+    }
+}
+
+/// n sellers and n buyers spread over a Paris sized map
+fn setup(n: usize) -> Market {
+    let mut world = World::default();
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    let mut m = Market::default();
+
+    for _ in 0..n {
+        let seller = SoulID(world.push(()));
+        m.produce(seller, CommodityKind::Bread, 10);
+        let price = Money::cents(80 + (rng.next() * 40.0) as i64);
+        m.sell(seller, rng.pos(10000.0), CommodityKind::Bread, 10, price);
+    }
+
+    for _ in 0..n {
+        let buyer = SoulID(world.push(()));
+        let price = Money::cents(80 + (rng.next() * 40.0) as i64);
+        m.buy(buyer, rng.pos(10000.0), CommodityKind::Bread, 1, price);
+    }
+
+    m
+}
+
+fn bench(n: usize, f: impl Fn(&mut Market) -> usize) -> (Duration, usize) {
+    const RUNS: u32 = 5;
+    let mut total = Duration::default();
+    let mut n_trades = 0;
+    for _ in 0..RUNS {
+        let mut m = setup(n);
+        let start = Instant::now();
+        n_trades = f(&mut m);
+        total += start.elapsed();
+    }
+    (total / RUNS, n_trades)
+}
+
+fn main() {
+    println!(
+        "{:>6} | {:>12} {:>7} | {:>12} {:>7}",
+        "orders", "naive", "trades", "spatial", "trades"
+    );
+    for &n in &[100, 500, 1000, 3000] {
+        let (naive, naive_trades) = bench(n, |m| m.make_trades_naive().count());
+        let (spatial, spatial_trades) = bench(n, |m| m.make_trades().count());
+        println!(
+            "{:>6} | {:>12?} {:>7} | {:>12?} {:>7}",
+            n, naive, naive_trades, spatial, spatial_trades
+        );
+    }
+}
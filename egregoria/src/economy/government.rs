@@ -1,7 +1,15 @@
 use crate::economy::Money;
 use crate::engine_interaction::WorldCommand;
+use crate::map_dynamic::{BuildingInfos, MapHistory};
+use crate::souls::goods_company::GoodsCompanyRegistry;
+use crate::transit::{n_buses, Transit};
+use crate::utils::time::GameTime;
 use crate::Egregoria;
+use geom::{PolyLine3, Vec2, Vec3};
+use legion::system;
+use map_model::{BuildingKind, LaneKind, LanePattern, Map, Road, RoadID};
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
 register_resource!(Government, "government");
 #[derive(Serialize, Deserialize)]
@@ -17,8 +25,265 @@ impl Default for Government {
     }
 }
 
+register_resource!(Taxes, "taxes");
+/// Kept apart from the government so that saves from before taxes existed still load their money
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Taxes {
+    /// Levied on every unit of commodity traded, job openings excluded
+    pub trade_tax_per_unit: Money,
+    /// Levied every day on each adult living in a house
+    pub resident_tax_per_day: Money,
+}
+
+impl Default for Taxes {
+    fn default() -> Self {
+        Self {
+            trade_tax_per_unit: Money::cents(10),
+            resident_tax_per_day: Money::base(2),
+        }
+    }
+}
+
+/// Income and expenses of a single day
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct DayLedger {
+    pub trade_taxes: Money,
+    pub resident_taxes: Money,
+    pub expenses: Money,
+    pub refunds: Money,
+    pub parking_fees: Money,
+}
+
+impl DayLedger {
+    pub fn balance(&self) -> Money {
+        self.trade_taxes + self.resident_taxes + self.parking_fees + self.refunds - self.expenses
+    }
+}
+
+/// Number of days kept in the ledger
+const LEDGER_DAYS: usize = 30;
+
+register_resource!(Ledger, "ledger");
+#[derive(Default, Serialize, Deserialize)]
+pub struct Ledger {
+    days: VecDeque<(i32, DayLedger)>,
+}
+
+impl Ledger {
+    pub fn day_mut(&mut self, day: i32) -> &mut DayLedger {
+        if self.days.back().map(|x| x.0) != Some(day) {
+            if self.days.len() == LEDGER_DAYS {
+                self.days.pop_front();
+            }
+            self.days.push_back((day, DayLedger::default()));
+        }
+        // unwrap ok: just pushed
+        &mut self.days.back_mut().unwrap().1
+    }
+
+    /// Recorded days, oldest first
+    pub fn days(&self) -> impl DoubleEndedIterator<Item = &(i32, DayLedger)> {
+        self.days.iter()
+    }
+}
+
+/// Cost per meter of a lane, in cents
+fn lane_cost_per_meter(kind: LaneKind) -> i64 {
+    match kind {
+        LaneKind::Driving | LaneKind::Bus => 100,
+        LaneKind::Biking | LaneKind::Parking => 50,
+        LaneKind::Walking => 30,
+    }
+}
+
+/// Cost of a single pylon holding an elevated road
+const PYLON_COST: Money = Money::base(50);
+/// Fraction of the construction cost given back when demolishing, in percent
+const REFUND_PERCENT: i64 = 50;
+const LIGHTS_COST: Money = Money::base(20);
+const BUS_STOP_COST: Money = Money::base(30);
+const BUS_COST: Money = Money::base(300);
+const PARKING_LOT_COST: Money = Money::base(200);
+const PARKING_GARAGE_COST: Money = Money::base(800);
+/// Cost per meter of a power line, in cents
+const POWER_LINE_COST_PER_METER: i64 = 50;
+/// Cost per square meter of terrain sculpted, in cents
+const TERRAFORM_COST_PER_SQ_METER: i64 = 1;
+
 impl Government {
-    pub fn action_cost(_action: &WorldCommand, _goria: &Egregoria) -> Money {
-        Money(100)
+    /// Cost of applying the command, negative when it is a refund
+    pub fn action_cost(action: &WorldCommand, goria: &Egregoria) -> Money {
+        let map = goria.map();
+        match *action {
+            WorldCommand::MapMakeConnection(from, to, interpoint, ref pat) => {
+                Self::connection_cost(&map, from.pos, to.pos, interpoint, pat)
+            }
+            WorldCommand::MapRemoveRoad(id) => -Self::refund(Self::road_cost(&map, id)),
+            WorldCommand::MapRemoveIntersection(id) => {
+                let inter = unwrap_or!(map.intersections().get(id), return Money::ZERO);
+                let roads_cost = inter
+                    .roads
+                    .iter()
+                    .map(|&r| Self::road_cost(&map, r))
+                    .fold(Money::ZERO, |a, b| a + b);
+                -Self::refund(roads_cost)
+            }
+            WorldCommand::MapBuildHouse(_) => Self::building_cost(BuildingKind::House, goria),
+            WorldCommand::MapBuildSpecialBuilding(_, _, kind, _) => {
+                Self::building_cost(kind, goria)
+            }
+            WorldCommand::MapRemoveBuilding(id) => {
+                let b = unwrap_or!(map.buildings().get(id), return Money::ZERO);
+                -Self::refund(Self::building_cost(b.kind, goria))
+            }
+            WorldCommand::MapUpdateIntersectionPolicy(id, _, ref lp) => {
+                let inter = unwrap_or!(map.intersections().get(id), return Money::ZERO);
+                if lp.has_lights() && !inter.light_policy.has_lights() {
+                    LIGHTS_COST
+                } else {
+                    Money::ZERO
+                }
+            }
+            WorldCommand::MapSetGreenWave(ref corridor) => {
+                let n_new_lights = corridor
+                    .iter()
+                    .filter_map(|&id| map.intersections().get(id))
+                    .filter(|inter| !inter.light_policy.has_lights())
+                    .count();
+                LIGHTS_COST * n_new_lights as i64
+            }
+            WorldCommand::MapAddPowerLine(src, dst) => {
+                let length = unwrap_or!(map.power_line_length(src, dst), return Money::ZERO);
+                Money::cents(POWER_LINE_COST_PER_METER * length as i64)
+            }
+            WorldCommand::MapRemovePowerLine(id) => {
+                let line = unwrap_or!(map.power_lines().get(id), return Money::ZERO);
+                let length = unwrap_or!(line.length(map.buildings()), return Money::ZERO);
+                -Self::refund(Money::cents(POWER_LINE_COST_PER_METER * length as i64))
+            }
+            WorldCommand::MapTerraform(center, radius, kind) => {
+                if !map.can_terraform(center, radius, kind) {
+                    return Money::ZERO;
+                }
+                let area = std::f32::consts::PI * radius * radius;
+                Money::cents(TERRAFORM_COST_PER_SQ_METER * area as i64)
+            }
+            WorldCommand::AddBusStop(pos) => {
+                if Transit::place_stop(&map, pos).is_some() {
+                    BUS_STOP_COST
+                } else {
+                    Money::ZERO
+                }
+            }
+            WorldCommand::RemoveBusStop(id) => {
+                if goria.read::<Transit>().stops().contains_key(id) {
+                    -Self::refund(BUS_STOP_COST)
+                } else {
+                    Money::ZERO
+                }
+            }
+            WorldCommand::AddBusLine(ref stops) => {
+                if goria.read::<Transit>().valid_line(stops) {
+                    BUS_COST * n_buses(stops.len()) as i64
+                } else {
+                    Money::ZERO
+                }
+            }
+            WorldCommand::RemoveBusLine(id) => {
+                let transit = goria.read::<Transit>();
+                let line = unwrap_or!(transit.lines().get(id), return Money::ZERO);
+                -Self::refund(BUS_COST * line.buses.len() as i64)
+            }
+            WorldCommand::MapLoadParis
+            | WorldCommand::MapLoadTestField(..)
+            | WorldCommand::ResetSave
+            | WorldCommand::SetGameTime(_)
+            | WorldCommand::UpdateTransform(..)
+            | WorldCommand::SetTaxes(_)
+            | WorldCommand::SetParkingPrices(_)
+            | WorldCommand::MapSetLotKind(..)
+            | WorldCommand::MapCloseRoad(..)
+            | WorldCommand::MapReopenRoad(_) => Money::ZERO,
+            WorldCommand::Undo => -goria.read::<MapHistory>().undo_cost(),
+            WorldCommand::Redo => goria.read::<MapHistory>().redo_cost(),
+        }
+    }
+
+    pub fn building_cost(kind: BuildingKind, goria: &Egregoria) -> Money {
+        match kind {
+            BuildingKind::House => Money::base(20),
+            BuildingKind::ParkingLot => PARKING_LOT_COST,
+            BuildingKind::ParkingGarage => PARKING_GARAGE_COST,
+            BuildingKind::GoodsCompany(_) => {
+                let registry = goria.read::<GoodsCompanyRegistry>();
+                let descr = unwrap_or!(registry.descriptions.get(&kind), return Money::ZERO);
+                Money::base(500 + 100 * descr.n_workers as i64 + (descr.size * descr.size) as i64)
+            }
+        }
+    }
+
+    fn pattern_cost(pat: &LanePattern, length: f32, n_pylons: usize) -> Money {
+        let per_meter: i64 = pat
+            .lanes_forward
+            .iter()
+            .chain(pat.lanes_backward.iter())
+            .map(|&(kind, _)| lane_cost_per_meter(kind))
+            .sum();
+        Money::cents(per_meter * length as i64) + PYLON_COST * n_pylons as i64
+    }
+
+    fn connection_cost(
+        map: &Map,
+        from: Vec3,
+        to: Vec3,
+        interpoint: Option<Vec2>,
+        pat: &LanePattern,
+    ) -> Money {
+        let mut points = vec![from];
+        if let Some(p) = interpoint {
+            points.push(p.z((from.z + to.z) * 0.5));
+        }
+        points.push(to);
+        let poly = PolyLine3::new(points);
+
+        let n_pylons = Road::pylons_positions(&poly, &map.terrain).count();
+        Self::pattern_cost(pat, poly.length(), n_pylons)
+    }
+
+    fn road_cost(map: &Map, id: RoadID) -> Money {
+        let road = unwrap_or!(map.roads().get(id), return Money::ZERO);
+        let n_pylons = Road::pylons_positions(road.interfaced_points(), &map.terrain).count();
+        Self::pattern_cost(&road.pattern(map.lanes()), road.length(), n_pylons)
+    }
+
+    fn refund(cost: Money) -> Money {
+        Money::cents(cost.0 * REFUND_PERCENT / 100)
     }
 }
+
+register_system!(resident_taxes);
+#[system]
+pub fn resident_taxes(
+    #[resource] gov: &mut Government,
+    #[resource] taxes: &Taxes,
+    #[resource] ledger: &mut Ledger,
+    #[resource] binfos: &BuildingInfos,
+    #[resource] map: &Map,
+    #[resource] time: &GameTime,
+) {
+    if !time.tick(GameTime::DAY as u32) {
+        return;
+    }
+
+    let buildings = map.buildings();
+    let n_residents: usize = binfos
+        .buildings()
+        .filter(|&b| matches!(buildings.get(b).map(|b| b.kind), Some(BuildingKind::House)))
+        .filter_map(|b| binfos.get(b))
+        .map(|info| info.household.adults.len())
+        .sum();
+
+    let income = taxes.resident_tax_per_day * n_residents as i64;
+    gov.money += income;
+    ledger.day_mut(time.daytime.day).resident_taxes += income;
+}
@@ -1,18 +1,57 @@
-use crate::economy::CommodityKind;
+use crate::economy::{CommodityKind, Money};
 use crate::SoulID;
+use flat_spatial::SparseGrid;
 use geom::Vec2;
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, VecDeque};
 
-#[derive(Default, Serialize, Deserialize)]
+/// Cost of transporting one unit over one meter, in cents
+const TRANSPORT_COST: f32 = 0.1;
+/// How much the reference price moves at each update when offer and demand are unbalanced
+const PRICE_STEP_PERCENT: i64 = 5;
+/// How much above (resp. below) the reference price buyers bid (resp. sellers ask)
+const ORDER_MARGIN_PERCENT: i64 = 10;
+/// Number of price updates kept in the price history
+const PRICE_HISTORY_SIZE: usize = 48;
+/// Cell size of the grid used to find the buyers close to a seller
+const MATCH_CELL_SIZE: i32 = 200;
+/// Initial radius of the search for buyers, doubled until enough candidates are found
+const MATCH_RADIUS: f32 = 200.0;
+/// Number of buyers each seller considers
+const MATCH_CANDIDATES: usize = 8;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Order {
+    pub pos: Vec2,
+    pub qty: i32,
+    /// Per unit, the maximum paid for buy orders and the minimum received for sell orders
+    pub price: Money,
+    /// Retail orders are between stores and consumers, they never match wholesale orders
+    pub retail: bool,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct SingleMarket {
     capital: BTreeMap<SoulID, i32>,
-    buy_orders: BTreeMap<SoulID, (Vec2, i32)>,
-    sell_orders: BTreeMap<SoulID, (Vec2, i32)>,
+    buy_orders: BTreeMap<SoulID, Order>,
+    sell_orders: BTreeMap<SoulID, Order>,
+    price: Money,
+    price_history: VecDeque<Money>,
 }
 
 impl SingleMarket {
+    fn new(price: Money) -> Self {
+        Self {
+            capital: Default::default(),
+            buy_orders: Default::default(),
+            sell_orders: Default::default(),
+            price,
+            price_history: Default::default(),
+        }
+    }
+
     pub fn capital(&self, soul: SoulID) -> Option<i32> {
         self.capital.get(&soul).copied()
     }
@@ -20,12 +59,288 @@ impl SingleMarket {
     pub fn capital_map(&self) -> &BTreeMap<SoulID, i32> {
         &self.capital
     }
-    pub fn buy_orders(&self) -> &BTreeMap<SoulID, (Vec2, i32)> {
+    pub fn buy_orders(&self) -> &BTreeMap<SoulID, Order> {
         &self.buy_orders
     }
-    pub fn sell_orders(&self) -> &BTreeMap<SoulID, (Vec2, i32)> {
+    pub fn sell_orders(&self) -> &BTreeMap<SoulID, Order> {
         &self.sell_orders
     }
+
+    /// The reference price of one unit, follows the trades and the balance of offer and demand
+    pub fn price(&self) -> Money {
+        self.price
+    }
+
+    /// Reference prices at each price update, oldest first
+    pub fn price_history(&self) -> &VecDeque<Money> {
+        &self.price_history
+    }
+
+    fn update_price(&mut self) {
+        let old = self.price;
+        if old > Money::ZERO {
+            let offer = self.sell_orders.values().map(|o| o.qty).sum::<i32>();
+            let demand = self.buy_orders.values().map(|o| o.qty).sum::<i32>();
+            let new = match demand.cmp(&offer) {
+                Ordering::Greater => {
+                    Money((old.0 * (100 + PRICE_STEP_PERCENT) / 100).max(old.0 + 1))
+                }
+                Ordering::Less => Money((old.0 * (100 - PRICE_STEP_PERCENT) / 100).max(1)),
+                Ordering::Equal => old,
+            };
+
+            // pending orders follow the reference price, otherwise sellers that
+            // are not producing anymore would never lower their price
+            if new != old {
+                for o in self
+                    .buy_orders
+                    .values_mut()
+                    .chain(self.sell_orders.values_mut())
+                {
+                    o.price = Money(o.price.0 * new.0 / old.0);
+                }
+                self.price = new;
+            }
+        }
+
+        if self.price_history.len() == PRICE_HISTORY_SIZE {
+            self.price_history.pop_front();
+        }
+        self.price_history.push_back(self.price);
+    }
+}
+
+/// (score, trade, whether the trade completes the sell order), lower scores are better
+type PotentialTrade = (f32, Trade, bool);
+/// Lowest score first, then by souls so the order doesn't depend on how the candidates were
+/// found, and the index of the trade in the potential trades
+type HeapEntry = Reverse<(OrderedFloat<f32>, SoulID, SoulID, usize)>;
+
+fn push_candidates(
+    candidates: &mut Vec<PotentialTrade>,
+    potential: &mut Vec<PotentialTrade>,
+    heap: &mut BinaryHeap<HeapEntry>,
+) {
+    for c in candidates.drain(..) {
+        heap.push(Reverse((
+            OrderedFloat(c.0),
+            c.1.seller,
+            c.1.buyer,
+            potential.len(),
+        )));
+        potential.push(c);
+    }
+}
+
+impl SingleMarket {
+    fn potential_trade(
+        kind: CommodityKind,
+        seller: SoulID,
+        sell: &Order,
+        buyer: SoulID,
+        buy: &Order,
+    ) -> Option<PotentialTrade> {
+        if buy.retail != sell.retail {
+            return None;
+        }
+        if seller == buyer {
+            log::warn!(
+                "{:?} is both selling and buying same commodity: {:?}",
+                seller,
+                kind
+            );
+            return None;
+        }
+        if buy.qty > sell.qty || buy.price < sell.price {
+            return None;
+        }
+        let score = sell.price.0 as f32 + sell.pos.distance(buy.pos) * TRANSPORT_COST;
+        Some((
+            score,
+            Trade {
+                buyer,
+                seller,
+                qty: buy.qty,
+                price: Money((buy.price.0 + sell.price.0) / 2),
+                sell_pos: sell.pos,
+                buy_pos: buy.pos,
+                kind,
+            },
+            buy.qty == sell.qty,
+        ))
+    }
+
+    /// The sellers that have enough capital to sell
+    fn sellers(&self) -> impl Iterator<Item = (SoulID, &Order)> + '_ {
+        self.sell_orders
+            .iter()
+            .filter(move |&(seller, sell)| {
+                matches!(self.capital(*seller), Some(capital) if sell.qty <= capital)
+            })
+            .map(|(&seller, sell)| (seller, sell))
+    }
+
+    /// The `MATCH_CANDIDATES` best buyers of a seller among the ones left in the grid
+    fn candidates(
+        &self,
+        kind: CommodityKind,
+        grid: &SparseGrid<SoulID>,
+        seller: SoulID,
+        sell: &Order,
+        out: &mut Vec<PotentialTrade>,
+    ) {
+        // widen the search until enough buyers are found, so that far away buyers
+        // still get matched when nobody is around
+        let mut radius = MATCH_RADIUS;
+        loop {
+            out.clear();
+
+            let side = 2.0 * radius / MATCH_CELL_SIZE as f32;
+            if side * side > grid.len() as f32 {
+                // the query would go through more cells than there are buyers
+                for (_, &buyer) in grid.objects() {
+                    let buy = unwrap_cont!(self.buy_orders.get(&buyer));
+                    out.extend(Self::potential_trade(kind, seller, sell, buyer, buy));
+                }
+                break;
+            }
+
+            for (h, _) in grid.query_around(sell.pos, radius) {
+                let buyer = *unwrap_cont!(grid.get(h)).1;
+                let buy = unwrap_cont!(self.buy_orders.get(&buyer));
+                out.extend(Self::potential_trade(kind, seller, sell, buyer, buy));
+            }
+            if out.len() >= MATCH_CANDIDATES {
+                break;
+            }
+            radius *= 2.0;
+        }
+
+        if out.len() > MATCH_CANDIDATES {
+            out.sort_unstable_by_key(|(x, t, _)| (OrderedFloat(*x), t.buyer));
+            out.truncate(MATCH_CANDIDATES);
+        }
+    }
+
+    /// Makes the same trades as `make_trades_naive`, cheapest first, but each seller only
+    /// considers its `MATCH_CANDIDATES` best buyers and looks further only once they all
+    /// traded with someone else.
+    /// The buyers are put in a grid once, retail and wholesale apart since they never match,
+    /// and leave it as soon as they trade.
+    fn make_trades_spatial(&mut self, kind: CommodityKind, out: &mut Vec<Trade>) {
+        let mut grids = [
+            SparseGrid::new(MATCH_CELL_SIZE),
+            SparseGrid::new(MATCH_CELL_SIZE),
+        ];
+        let mut handles = BTreeMap::new();
+        for (&buyer, buy) in &self.buy_orders {
+            let g = buy.retail as usize;
+            handles.insert(buyer, (g, grids[g].insert(buy.pos, buyer)));
+        }
+
+        let mut traded = BTreeSet::default();
+        let mut potential = vec![];
+        let mut heap = BinaryHeap::new();
+        // number of candidates of each seller still in the heap
+        let mut remaining = BTreeMap::new();
+        let mut candidates = vec![];
+
+        for (seller, sell) in self.sellers() {
+            self.candidates(
+                kind,
+                &grids[sell.retail as usize],
+                seller,
+                sell,
+                &mut candidates,
+            );
+            remaining.insert(seller, candidates.len());
+            push_candidates(&mut candidates, &mut potential, &mut heap);
+        }
+
+        while let Some(Reverse((_, seller, _, i))) = heap.pop() {
+            let (_, trade, complete) = potential[i];
+            if self.apply_trade(&trade, complete, &mut traded) {
+                if let Some(&(g, h)) = handles.get(&trade.buyer) {
+                    grids[g].remove_maintain(h);
+                }
+                out.push(trade);
+                continue;
+            }
+            if traded.contains(&seller) {
+                continue;
+            }
+
+            let left = unwrap_cont!(remaining.get_mut(&seller));
+            *left -= 1;
+            if *left > 0 {
+                continue;
+            }
+            // all the candidates traded with someone else, look again among the buyers left
+            let sell = unwrap_cont!(self.sell_orders.get(&seller));
+            self.candidates(
+                kind,
+                &grids[sell.retail as usize],
+                seller,
+                sell,
+                &mut candidates,
+            );
+            remaining.insert(seller, candidates.len());
+            push_candidates(&mut candidates, &mut potential, &mut heap);
+        }
+    }
+
+    /// Makes the trades in order of score, every seller considering every buyer
+    fn make_trades_naive(&mut self, kind: CommodityKind, out: &mut Vec<Trade>) {
+        let mut potential = vec![];
+        for (seller, sell) in self.sellers() {
+            for (&buyer, buy) in &self.buy_orders {
+                potential.extend(Self::potential_trade(kind, seller, sell, buyer, buy));
+            }
+        }
+        potential.sort_unstable_by_key(|(x, t, _)| (OrderedFloat(*x), t.seller, t.buyer));
+
+        let mut traded = BTreeSet::default();
+        for (_, trade, complete) in potential {
+            if self.apply_trade(&trade, complete, &mut traded) {
+                out.push(trade);
+            }
+        }
+    }
+
+    /// Applies the trade and returns true, unless one of its souls already traded
+    fn apply_trade(
+        &mut self,
+        trade: &Trade,
+        complete: bool,
+        traded: &mut BTreeSet<SoulID>,
+    ) -> bool {
+        // checked together so a skipped trade doesn't take the buyer away from the others
+        if traded.contains(&trade.buyer) || traded.contains(&trade.seller) {
+            return false;
+        }
+        traded.insert(trade.buyer);
+        traded.insert(trade.seller);
+        self.buy_orders.remove(&trade.buyer);
+        if complete {
+            self.sell_orders.remove(&trade.seller);
+        } else if let Some(o) = self.sell_orders.get_mut(&trade.seller) {
+            o.qty -= trade.qty
+        }
+
+        self.price = Money((self.price.0 * 3 + trade.price.0) / 4);
+
+        *self.capital.entry(trade.buyer).or_default() += trade.qty;
+        *self.capital.entry(trade.seller).or_default() -= trade.qty;
+
+        true
+    }
+}
+
+fn initial_price(kind: CommodityKind) -> Money {
+    match kind {
+        CommodityKind::JobOpening => Money::ZERO,
+        _ => Money::base(1),
+    }
 }
 
 register_resource!(Market, "market");
@@ -39,7 +354,7 @@ impl Default for Market {
         Self {
             markets: CommodityKind::values()
                 .iter()
-                .map(|&v| (v, SingleMarket::default()))
+                .map(|&v| (v, SingleMarket::new(initial_price(v))))
                 .collect(),
         }
     }
@@ -50,6 +365,8 @@ pub struct Trade {
     pub buyer: SoulID,
     pub seller: SoulID,
     pub qty: i32,
+    /// Per unit
+    pub price: Money,
     pub sell_pos: Vec2,
     pub buy_pos: Vec2,
     pub kind: CommodityKind,
@@ -63,25 +380,79 @@ impl Market {
     /// Called when an agent tells the world it wants to sell something
     /// If an order is already placed, it will be updated.
     /// Beware that you need capital to sell anything, using produce.
-    pub fn sell(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, qty: i32) {
-        log::info!("{:?} sell {:?} {:?} near {:?}", soul, qty, kind, near);
-        self.m(kind).sell_orders.insert(soul, (near, qty));
+    pub fn sell(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, qty: i32, price: Money) {
+        self.sell_order(
+            soul,
+            kind,
+            Order {
+                pos: near,
+                qty,
+                price,
+                retail: false,
+            },
+        );
     }
 
     pub fn sell_all(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind) {
+        self.sell_all_inner(soul, near, kind, false);
+    }
+
+    /// Same as `sell_all`, but only consumers coming to the store can buy
+    pub fn sell_all_retail(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind) {
+        self.sell_all_inner(soul, near, kind, true);
+    }
+
+    fn sell_all_inner(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, retail: bool) {
         let c = self.capital(soul, kind);
         if c == 0 {
             return;
         }
-        self.sell(soul, near, kind, c);
+        let price = self.ask_price(kind);
+        self.sell_order(
+            soul,
+            kind,
+            Order {
+                pos: near,
+                qty: c,
+                price,
+                retail,
+            },
+        );
+    }
+
+    fn sell_order(&mut self, soul: SoulID, kind: CommodityKind, order: Order) {
+        log::info!("{:?} sell {:?} {:?}", soul, kind, order);
+        self.m(kind).sell_orders.insert(soul, order);
     }
 
     /// Called when an agent tells the world it wants to buy something
     /// If an order is already placed, it will be updated.
-    pub fn buy(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, qty: i32) {
-        log::info!("{:?} buy {:?} {:?} near {:?}", soul, qty, kind, near);
+    pub fn buy(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, qty: i32, price: Money) {
+        self.buy_order(
+            soul,
+            kind,
+            Order {
+                pos: near,
+                qty,
+                price,
+                retail: false,
+            },
+        );
+    }
 
-        self.m(kind).buy_orders.insert(soul, (near, qty));
+    /// Called when a consumer wants to buy something from a store at the usual price
+    pub fn buy_retail(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, qty: i32) {
+        let price = self.bid_price(kind);
+        self.buy_order(
+            soul,
+            kind,
+            Order {
+                pos: near,
+                qty,
+                price,
+                retail: true,
+            },
+        );
     }
 
     pub fn buy_until(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, qty: i32) {
@@ -89,7 +460,38 @@ impl Market {
         if c >= qty {
             return;
         }
-        self.buy(soul, near, kind, qty - c);
+        let price = self.bid_price(kind);
+        self.buy(soul, near, kind, qty - c, price);
+    }
+
+    fn buy_order(&mut self, soul: SoulID, kind: CommodityKind, order: Order) {
+        log::info!("{:?} buy {:?} {:?}", soul, kind, order);
+        self.m(kind).buy_orders.insert(soul, order);
+    }
+
+    /// The reference price of one unit
+    pub fn price(&self, kind: CommodityKind) -> Money {
+        self.markets.get(&kind).unwrap().price
+    }
+
+    /// The price at which buyers are usually willing to buy one unit
+    pub fn bid_price(&self, kind: CommodityKind) -> Money {
+        let p = self.price(kind);
+        Money(p.0 * (100 + ORDER_MARGIN_PERCENT) / 100)
+    }
+
+    /// The price at which sellers are usually willing to sell one unit
+    pub fn ask_price(&self, kind: CommodityKind) -> Money {
+        let p = self.price(kind);
+        Money(p.0 * (100 - ORDER_MARGIN_PERCENT) / 100)
+    }
+
+    /// Moves the reference prices according to the unmatched offer and demand
+    /// and records them in the price histories. Pending orders are requoted accordingly.
+    pub fn update_prices(&mut self) {
+        for market in self.markets.values_mut() {
+            market.update_price();
+        }
     }
 
     /// Get the capital that this agent owns
@@ -97,6 +499,23 @@ impl Market {
         self.markets.get(&kind).unwrap().capital(soul).unwrap_or(0)
     }
 
+    /// How many units are offered on top of what is asked for, negative when demand is higher
+    pub fn unmet_offer(&self, kind: CommodityKind) -> i32 {
+        let m = self.markets.get(&kind).unwrap();
+        let offer: i32 = m.sell_orders.values().map(|o| o.qty).sum();
+        let demand: i32 = m.buy_orders.values().map(|o| o.qty).sum();
+        offer - demand
+    }
+
+    /// Removes the orders and the capital of the soul, for example when it leaves the city
+    pub fn remove(&mut self, soul: SoulID) {
+        for market in self.markets.values_mut() {
+            market.capital.remove(&soul);
+            market.buy_orders.remove(&soul);
+            market.sell_orders.remove(&soul);
+        }
+    }
+
     /// Registers a soul to the market, not obligatory
     pub fn register(&mut self, soul: SoulID, kind: CommodityKind) {
         self.m(kind).capital.entry(soul).or_default();
@@ -113,78 +532,28 @@ impl Market {
     }
 
     /// Returns a list of buy and sell orders matched together.
+    /// Orders only match when the buyer bids at least the asking price, the cheapest offers
+    /// (price plus transport) are matched first and the trade is made halfway between the two prices.
+    /// Sellers only look at the few closest buyers they can trade with, and further away
+    /// once those all traded with someone else.
     /// A trade updates the buy and sell orders from the market, and the capital of the buyers and sellers.
     /// A trade can only be completed if the seller has enough capital.
     pub fn make_trades(&mut self) -> impl Iterator<Item = Trade> + '_ {
         let mut all_trades = vec![];
-        let mut potential = vec![];
-
         for (&kind, market) in &mut self.markets {
-            // Naive O(n²) alg
-            for (&seller, &(sell_pos, qty_sell)) in &market.sell_orders {
-                let capital_sell = unwrap_or!(market.capital(seller), continue);
-                if qty_sell > capital_sell {
-                    continue;
-                }
-                for (&buyer, &(buy_pos, qty_buy)) in &market.buy_orders {
-                    if seller == buyer {
-                        log::warn!(
-                            "{:?} is both selling and buying same commodity: {:?}",
-                            seller,
-                            kind
-                        );
-                        continue;
-                    }
-                    if qty_buy <= qty_sell {
-                        let score = sell_pos.distance2(buy_pos);
-                        potential.push((
-                            score,
-                            Trade {
-                                buyer,
-                                seller,
-                                qty: qty_buy,
-                                sell_pos,
-                                buy_pos,
-                                kind,
-                            },
-                            qty_buy == qty_sell,
-                        ))
-                    }
-                }
-            }
-            potential.sort_unstable_by_key(|(x, _, _)| OrderedFloat(*x));
-            let mut already_sold = BTreeSet::default();
-            let SingleMarket {
-                buy_orders,
-                sell_orders,
-                capital,
-            } = market;
-
-            all_trades.extend(
-                potential
-                    .drain(..)
-                    .filter(move |(_, trade, complete)| {
-                        let ok =
-                            already_sold.insert(trade.buyer) && already_sold.insert(trade.seller);
-                        if !ok {
-                            return false;
-                        }
-                        buy_orders.remove(&trade.buyer);
-                        if *complete {
-                            sell_orders.remove(&trade.seller);
-                        } else if let Some((_, qty)) = sell_orders.get_mut(&trade.seller) {
-                            *qty -= trade.qty
-                        }
-
-                        *capital.entry(trade.buyer).or_default() += trade.qty;
-                        *capital.entry(trade.seller).or_default() -= trade.qty;
-
-                        true
-                    })
-                    .map(|(_, x, _)| x),
-            )
+            market.make_trades_spatial(kind, &mut all_trades);
         }
+        all_trades.into_iter()
+    }
 
+    /// Same as `make_trades` but every seller considers every buyer, in O(n²).
+    /// Only kept to benchmark and test the spatial matcher against.
+    #[doc(hidden)]
+    pub fn make_trades_naive(&mut self) -> impl Iterator<Item = Trade> + '_ {
+        let mut all_trades = vec![];
+        for (&kind, market) in &mut self.markets {
+            market.make_trades_naive(kind, &mut all_trades);
+        }
         all_trades.into_iter()
     }
 
@@ -195,11 +564,12 @@ impl Market {
 
 #[cfg(test)]
 mod tests {
-    use super::Market;
-    use crate::economy::CommodityKind;
+    use super::{Market, Trade};
+    use crate::economy::{CommodityKind, Money};
     use crate::SoulID;
     use geom::{vec2, Vec2};
     use legion::Entity;
+    use std::collections::BTreeSet;
 
     fn mk_ent(id: u64) -> Entity {
         unsafe { std::mem::transmute(id) }
@@ -216,9 +586,15 @@ mod tests {
         m.produce(seller, CommodityKind::Cereal, 3);
         m.produce(seller_far, CommodityKind::Cereal, 3);
 
-        m.buy(buyer, Vec2::ZERO, CommodityKind::Cereal, 2);
-        m.sell(seller, Vec2::X, CommodityKind::Cereal, 3);
-        m.sell(seller_far, vec2(10.0, 10.0), CommodityKind::Cereal, 3);
+        m.buy(buyer, Vec2::ZERO, CommodityKind::Cereal, 2, Money::base(1));
+        m.sell(seller, Vec2::X, CommodityKind::Cereal, 3, Money::base(1));
+        m.sell(
+            seller_far,
+            vec2(10.0, 10.0),
+            CommodityKind::Cereal,
+            3,
+            Money::base(1),
+        );
 
         let trades = m.make_trades().collect::<Vec<_>>();
 
@@ -227,5 +603,137 @@ mod tests {
         assert_eq!(t0.seller, seller);
         assert_eq!(t0.buyer, buyer);
         assert_eq!(t0.qty, 2);
+        assert_eq!(t0.price, Money::base(1));
+    }
+
+    #[test]
+    fn test_match_price() {
+        let seller_expensive = SoulID(mk_ent(1));
+        let seller_cheap_far = SoulID(mk_ent(2));
+        let buyer = SoulID(mk_ent(3));
+        let buyer_poor = SoulID(mk_ent(4));
+
+        let mut m = Market::default();
+
+        m.produce(seller_expensive, CommodityKind::Cereal, 1);
+        m.produce(seller_cheap_far, CommodityKind::Cereal, 1);
+
+        m.buy(buyer, Vec2::ZERO, CommodityKind::Cereal, 1, Money::base(10));
+        m.buy(
+            buyer_poor,
+            Vec2::ZERO,
+            CommodityKind::Cereal,
+            1,
+            Money::cents(1),
+        );
+        m.sell(
+            seller_expensive,
+            Vec2::X,
+            CommodityKind::Cereal,
+            1,
+            Money::base(8),
+        );
+        m.sell(
+            seller_cheap_far,
+            vec2(100.0, 0.0),
+            CommodityKind::Cereal,
+            1,
+            Money::base(4),
+        );
+
+        let trades = m.make_trades().collect::<Vec<_>>();
+
+        // 4 + 100m of transport is cheaper than 8 next door, and nobody sells at 1¢
+        assert_eq!(trades.len(), 1);
+        let t0 = trades[0];
+        assert_eq!(t0.seller, seller_cheap_far);
+        assert_eq!(t0.buyer, buyer);
+        assert_eq!(t0.price, Money::base(7));
+
+        m.update_prices();
+        let cereal = &m.inner()[&CommodityKind::Cereal];
+        assert_eq!(cereal.price_history().len(), 1);
+        assert_eq!(cereal.price(), cereal.price_history()[0]);
+    }
+
+    #[test]
+    fn test_spatial_matches_naive() {
+        let setup = || {
+            let mut m = Market::default();
+            // crowded sellers share the same closest buyers
+            for i in 1..=30u64 {
+                let seller = SoulID(mk_ent(i));
+                m.produce(seller, CommodityKind::Cereal, 1);
+                let pos = vec2((i % 3) as f32, (i / 3) as f32);
+                m.sell(seller, pos, CommodityKind::Cereal, 1, Money::base(1));
+            }
+            for i in 1..=40u64 {
+                let pos = vec2((i * 97 % 1000) as f32, (i * 31 % 600) as f32);
+                let buyer = SoulID(mk_ent(100 + i));
+                m.buy(buyer, pos, CommodityKind::Cereal, 1, Money::base(100));
+            }
+            m
+        };
+
+        let pairs = |trades: Vec<Trade>| {
+            trades
+                .iter()
+                .map(|t| (t.seller, t.buyer))
+                .collect::<BTreeSet<_>>()
+        };
+        let spatial = pairs(setup().make_trades().collect());
+        let naive = pairs(setup().make_trades_naive().collect());
+        assert_eq!(spatial.len(), 30);
+        assert_eq!(spatial, naive);
+    }
+
+    #[test]
+    fn test_match_far_away() {
+        let seller = SoulID(mk_ent(1));
+        let buyer = SoulID(mk_ent(2));
+
+        let mut m = Market::default();
+
+        m.produce(seller, CommodityKind::Cereal, 1);
+
+        m.buy(buyer, Vec2::ZERO, CommodityKind::Cereal, 1, Money::base(1));
+        m.sell(
+            seller,
+            vec2(20000.0, 0.0),
+            CommodityKind::Cereal,
+            1,
+            Money::base(1),
+        );
+
+        let trades = m.make_trades().collect::<Vec<_>>();
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].buyer, buyer);
+    }
+
+    #[test]
+    fn test_match_retail() {
+        let factory = SoulID(mk_ent(1));
+        let store = SoulID(mk_ent(2));
+        let consumer = SoulID(mk_ent(3));
+
+        let mut m = Market::default();
+
+        m.produce(factory, CommodityKind::Meat, 1);
+        m.sell_all(factory, Vec2::ZERO, CommodityKind::Meat);
+        m.buy_retail(consumer, Vec2::ZERO, CommodityKind::Meat, 1);
+        m.buy_until(store, vec2(100.0, 0.0), CommodityKind::Meat, 1);
+
+        // the consumer is closer but cannot buy from the factory
+        let trades = m.make_trades().collect::<Vec<_>>();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].buyer, store);
+
+        m.sell_all_retail(store, vec2(100.0, 0.0), CommodityKind::Meat);
+
+        let trades = m.make_trades().collect::<Vec<_>>();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].seller, store);
+        assert_eq!(trades[0].buyer, consumer);
     }
 }
@@ -1,21 +1,28 @@
+use crate::utils::time::GameTime;
 use crate::SoulID;
 use common::FastMap;
 use legion::world::SubWorld;
 use legion::{system, EntityStore};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
 
 mod government;
 mod market;
+mod zoning;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 /// Money in cents, can be negative when in debt.
 pub struct Money(pub i64);
 
 impl Display for Money {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        (self.0 / 100).fmt(f)?;
-        let cent = self.0 % 100;
+        if self.0 < 0 {
+            f.write_str("-")?;
+        }
+        let abs = self.0.abs();
+        (abs / 100).fmt(f)?;
+        let cent = abs % 100;
         if cent > 0 {
             f.write_str(".")?;
             if cent < 10 {
@@ -28,17 +35,60 @@ impl Display for Money {
 }
 
 impl Money {
-    pub fn cents(cents: i64) -> Self {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn cents(cents: i64) -> Self {
         Self(cents)
     }
 
-    pub fn base(base: i64) -> Self {
+    pub const fn base(base: i64) -> Self {
         Self(base * 100)
     }
 }
 
+impl Add for Money {
+    type Output = Money;
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 -= rhs.0
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+    fn neg(self) -> Self::Output {
+        Self(-self.0)
+    }
+}
+
+impl Mul<i64> for Money {
+    type Output = Money;
+    fn mul(self, rhs: i64) -> Self::Output {
+        Self(self.0 * rhs)
+    }
+}
+
 pub use government::*;
 pub use market::*;
+pub use zoning::*;
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct Sold(pub Vec<Trade>);
@@ -62,6 +112,14 @@ macro_rules! commodity {
                 use CommodityKind::*;
                 &[$($member),*]
             }
+
+            /// Parses the name of the variant, as it is serialized
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $(stringify!($member) => Some(Self::$member),)*
+                    _ => None,
+                }
+            }
         }
         impl Display for CommodityKind {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -103,14 +161,34 @@ commodity! {
 }
 
 register_system!(market_update);
+/// Hands the matched trades to the sellers and buyers.
+/// Souls don't hold money: the price only decides who trades with whom, the trade tax is
+/// the only money that changes hands.
 #[system]
 #[write_component(Sold)]
 #[write_component(Bought)]
 #[write_component(Workers)]
-pub fn market_update(#[resource] m: &mut Market, subworld: &mut SubWorld<'_>) {
+pub fn market_update(
+    #[resource] m: &mut Market,
+    #[resource] gov: &mut Government,
+    #[resource] taxes: &Taxes,
+    #[resource] ledger: &mut Ledger,
+    #[resource] time: &GameTime,
+    subworld: &mut SubWorld<'_>,
+) {
+    if time.tick(GameTime::HOUR as u32) {
+        m.update_prices();
+    }
+
     for trade in m.make_trades() {
         log::info!("A trade was made! {:?}", trade);
 
+        if trade.kind != CommodityKind::JobOpening {
+            let tax = taxes.trade_tax_per_unit * trade.qty as i64;
+            gov.money += tax;
+            ledger.day_mut(time.daytime.day).trade_taxes += tax;
+        }
+
         let mut ent = unwrap_orr!(subworld.entry_mut(trade.seller.0), continue);
 
         match trade.kind {
@@ -0,0 +1,156 @@
+use crate::economy::{CommodityKind, Market};
+use crate::map_dynamic::BuildingInfos;
+use crate::souls::goods_company::{GoodsCompanyDescription, GoodsCompanyRegistry};
+use crate::utils::rand_provider::RandProvider;
+use crate::utils::time::GameTime;
+use legion::system;
+use map_model::{BuildingID, BuildingKind, LotKind, Map};
+use serde::{Deserialize, Serialize};
+
+/// How often a company may grow in each zone, in seconds
+const ZONING_PERIOD: u32 = GameTime::HOUR as u32;
+/// Companies stop growing when this many job openings are unfilled, nobody would work there
+const MAX_VACANCIES: i32 = 20;
+
+/// Goods that are asked for but that nobody offers
+#[derive(Default, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct GoodsDemand {
+    pub total: i32,
+    pub most_wanted: Option<CommodityKind>,
+}
+
+register_resource!(ZoneDemand, "zone_demand");
+/// What the city lacks according to the market, companies grow on the zones to fill it
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct ZoneDemand {
+    /// People looking for a job plus empty houses, minus the unfilled job openings
+    pub jobs: i32,
+    pub vacancies: i32,
+    /// Asked for by consumers, to be sold by stores
+    pub commercial: GoodsDemand,
+    /// Asked for by companies, to be made by factories
+    pub industrial: GoodsDemand,
+}
+
+impl ZoneDemand {
+    pub fn compute(market: &Market, free_homes: i32) -> Self {
+        let vacancies = market.unmet_offer(CommodityKind::JobOpening);
+        let mut d = Self {
+            jobs: free_homes - vacancies,
+            vacancies,
+            ..Self::default()
+        };
+        let mut most_wanted = [0, 0];
+
+        for (&kind, m) in market.inner() {
+            if matches!(kind, CommodityKind::JobOpening | CommodityKind::Electricity) {
+                continue;
+            }
+            for (i, &retail) in [true, false].iter().enumerate() {
+                let demand: i32 = m
+                    .buy_orders()
+                    .values()
+                    .filter(|o| o.retail == retail)
+                    .map(|o| o.qty)
+                    .sum();
+                let offer: i32 = m
+                    .sell_orders()
+                    .values()
+                    .filter(|o| o.retail == retail)
+                    .map(|o| o.qty)
+                    .sum();
+                let unmet = demand - offer;
+                if unmet <= 0 {
+                    continue;
+                }
+
+                let goods = if retail {
+                    &mut d.commercial
+                } else {
+                    &mut d.industrial
+                };
+                goods.total += unmet;
+                if unmet > most_wanted[i] {
+                    most_wanted[i] = unmet;
+                    goods.most_wanted = Some(kind);
+                }
+            }
+        }
+        d
+    }
+
+    pub fn goods(&self, zone: LotKind) -> Option<GoodsDemand> {
+        match zone {
+            LotKind::Commercial => Some(self.commercial),
+            LotKind::Industrial => Some(self.industrial),
+            LotKind::Unassigned | LotKind::Residential => None,
+        }
+    }
+}
+
+register_system!(zoning);
+#[system]
+pub fn zoning(
+    #[resource] time: &GameTime,
+    #[resource] map: &mut Map,
+    #[resource] binfos: &mut BuildingInfos,
+    #[resource] market: &Market,
+    #[resource] registry: &GoodsCompanyRegistry,
+    #[resource] rand: &mut RandProvider,
+    #[resource] demand: &mut ZoneDemand,
+) {
+    if !time.tick(ZONING_PERIOD) {
+        return;
+    }
+
+    let free_homes = map
+        .buildings()
+        .iter()
+        .filter(|(id, b)| {
+            b.kind == BuildingKind::House
+                && binfos.get(*id).map(|i| i.owner.is_none()).unwrap_or(false)
+        })
+        .count();
+    *demand = ZoneDemand::compute(market, free_homes as i32);
+    if demand.vacancies > MAX_VACANCIES {
+        return;
+    }
+
+    for &zone in &[LotKind::Commercial, LotKind::Industrial] {
+        let goods = unwrap_cont!(demand.goods(zone));
+        if goods.total <= 0 && demand.jobs <= 0 {
+            continue;
+        }
+
+        // companies making what is most wanted, or any of them when only jobs are needed
+        let candidates: Vec<&GoodsCompanyDescription> = registry
+            .descriptions
+            .values()
+            .filter(|d| d.zone() == Some(zone))
+            .filter(|d| goods.most_wanted.map(|k| d.provides(k)).unwrap_or(true))
+            .collect();
+        if candidates.is_empty() {
+            continue;
+        }
+
+        let start = rand.random::<u32>() as usize;
+        for i in 0..candidates.len() {
+            let des = candidates[(start + i) % candidates.len()];
+            if let Some(b) = grow_company(map, des, zone) {
+                binfos.insert(b);
+                log::info!("{} grew on the {:?} zone", des.name, zone);
+                break;
+            }
+        }
+    }
+}
+
+/// Builds the company on the first lot of the zone where it fits
+fn grow_company(map: &mut Map, des: &GoodsCompanyDescription, zone: LotKind) -> Option<BuildingID> {
+    let (road, obb) = map
+        .lots()
+        .values()
+        .filter(|lot| lot.kind == zone)
+        .find_map(|lot| Some((lot.parent, map.zoned_building_obb(lot.id, des.size)?)))?;
+    map.build_special_building(road, &obb, des.bkind, des.bgen)
+}
@@ -1,7 +1,7 @@
 use crate::{ent_from_id, ent_id, Egregoria};
 use map_model::{
-    BuildingGen, BuildingID, BuildingKind, IntersectionID, LanePattern, LightPolicy, LotID, Map,
-    MapProject, RoadID, TurnPolicy,
+    BuildingGen, BuildingID, BuildingKind, Closure, ClosureKind, IntersectionID, LanePattern,
+    LightPolicy, LotID, LotKind, Map, MapProject, PowerLineID, RoadID, Terraform, TurnPolicy,
 };
 use serde::{Deserialize, Serialize};
 
@@ -22,6 +22,21 @@ impl Default for Selectable {
     }
 }
 
+register_resource_noserialize!(RejectedCommands);
+/// Commands that couldn't be applied, kept so that the UI can tell the player
+#[derive(Default)]
+pub struct RejectedCommands {
+    pub rejected: VecDeque<RejectedCommand>,
+}
+
+pub struct RejectedCommand {
+    pub tick: u32,
+    pub command: WorldCommand,
+    pub reason: String,
+}
+
+const MAX_REJECTED: usize = 10;
+
 #[derive(Clone, Default)]
 pub struct WorldCommands {
     pub(crate) commands: Vec<WorldCommand>,
@@ -35,21 +50,42 @@ pub enum WorldCommand {
     MapRemoveRoad(RoadID),
     MapRemoveBuilding(BuildingID),
     MapBuildHouse(LotID),
+    MapSetLotKind(LotID, LotKind),
     MapMakeConnection(MapProject, MapProject, Option<Vec2>, LanePattern),
     MapUpdateIntersectionPolicy(IntersectionID, TurnPolicy, LightPolicy),
+    MapSetGreenWave(Vec<IntersectionID>),
+    /// Closes a road to vehicles, for the given number of seconds if any
+    MapCloseRoad(RoadID, ClosureKind, Option<u32>),
+    MapReopenRoad(RoadID),
+    /// Sculpts the terrain within the radius around the point
+    MapTerraform(Vec2, f32, Terraform),
     MapBuildSpecialBuilding(RoadID, OBB, BuildingKind, BuildingGen),
+    MapAddPowerLine(BuildingID, BuildingID),
+    MapRemovePowerLine(PowerLineID),
     MapLoadParis,
     MapLoadTestField(Vec2, u32, f32),
     ResetSave,
     SetGameTime(GameTime),
     UpdateTransform(u64, Transform),
+    SetTaxes(Taxes),
+    SetParkingPrices(ParkingPrices),
+    AddBusStop(Vec3),
+    RemoveBusStop(BusStopID),
+    AddBusLine(Vec<BusStopID>),
+    RemoveBusLine(BusLineID),
+    Undo,
+    Redo,
 }
 
-use crate::economy::Government;
-use crate::map_dynamic::BuildingInfos;
+use crate::economy::{Government, Ledger, Money, Taxes};
+use crate::map_dynamic::{BuildingInfos, MapHistory, ParkingManagement, ParkingPrices};
+use crate::transit::{
+    remove_bus_line, remove_bus_stop, spawn_bus_line, BusLineID, BusStopID, Transit,
+};
 use crate::utils::time::GameTime;
-use geom::{Transform, Vec2, OBB};
+use geom::{Transform, Vec2, Vec3, OBB};
 use legion::Entity;
+use std::collections::VecDeque;
 use WorldCommand::*;
 
 impl WorldCommands {
@@ -65,6 +101,38 @@ impl WorldCommands {
         self.commands.is_empty()
     }
 
+    pub fn set_taxes(&mut self, taxes: Taxes) {
+        self.commands.push(SetTaxes(taxes))
+    }
+
+    pub fn set_parking_prices(&mut self, prices: ParkingPrices) {
+        self.commands.push(SetParkingPrices(prices))
+    }
+
+    pub fn add_bus_stop(&mut self, pos: Vec3) {
+        self.commands.push(AddBusStop(pos))
+    }
+
+    pub fn remove_bus_stop(&mut self, id: BusStopID) {
+        self.commands.push(RemoveBusStop(id))
+    }
+
+    pub fn add_bus_line(&mut self, stops: Vec<BusStopID>) {
+        self.commands.push(AddBusLine(stops))
+    }
+
+    pub fn remove_bus_line(&mut self, id: BusLineID) {
+        self.commands.push(RemoveBusLine(id))
+    }
+
+    pub fn undo(&mut self) {
+        self.commands.push(Undo)
+    }
+
+    pub fn redo(&mut self) {
+        self.commands.push(Redo)
+    }
+
     pub fn map_load_paris(&mut self) {
         self.commands.push(MapLoadParis)
     }
@@ -112,6 +180,22 @@ impl WorldCommands {
         self.commands.push(MapBuildHouse(id))
     }
 
+    pub fn map_set_lot_kind(&mut self, id: LotID, kind: LotKind) {
+        self.commands.push(MapSetLotKind(id, kind))
+    }
+
+    pub fn map_terraform(&mut self, center: Vec2, radius: f32, kind: Terraform) {
+        self.commands.push(MapTerraform(center, radius, kind))
+    }
+
+    pub fn map_add_power_line(&mut self, src: BuildingID, dst: BuildingID) {
+        self.commands.push(MapAddPowerLine(src, dst))
+    }
+
+    pub fn map_remove_power_line(&mut self, id: PowerLineID) {
+        self.commands.push(MapRemovePowerLine(id))
+    }
+
     pub fn map_make_connection(
         &mut self,
         from: MapProject,
@@ -131,55 +215,189 @@ impl WorldCommands {
     ) {
         self.commands.push(MapUpdateIntersectionPolicy(id, tp, lp))
     }
+
+    pub fn map_set_green_wave(&mut self, corridor: Vec<IntersectionID>) {
+        self.commands.push(MapSetGreenWave(corridor))
+    }
+
+    pub fn map_close_road(&mut self, id: RoadID, kind: ClosureKind, duration: Option<u32>) {
+        self.commands.push(MapCloseRoad(id, kind, duration))
+    }
+
+    pub fn map_reopen_road(&mut self, id: RoadID) {
+        self.commands.push(MapReopenRoad(id))
+    }
 }
 
 impl WorldCommand {
-    pub(crate) fn apply(&self, goria: &mut Egregoria) {
+    /// Whether this command edits the map and can be undone
+    pub fn is_map_editing(&self) -> bool {
+        matches!(
+            self,
+            MapRemoveIntersection(_)
+                | MapRemoveRoad(_)
+                | MapRemoveBuilding(_)
+                | MapBuildHouse(_)
+                | MapSetLotKind(..)
+                | MapTerraform(..)
+                | MapMakeConnection(..)
+                | MapUpdateIntersectionPolicy(..)
+                | MapSetGreenWave(_)
+                | MapBuildSpecialBuilding(..)
+                | MapAddPowerLine(..)
+                | MapRemovePowerLine(_)
+                | MapLoadParis
+                | MapLoadTestField(..)
+        )
+    }
+
+    /// Applies the command and returns what it cost, nothing if it was rejected or didn't do anything
+    pub(crate) fn apply(&self, goria: &mut Egregoria) -> Money {
         let cost = Government::action_cost(self, goria);
-        goria.write::<Government>().money.0 -= cost.0;
-        match *self {
-            MapRemoveIntersection(id) => goria.map_mut().remove_intersection(id),
-            MapRemoveRoad(id) => drop(goria.map_mut().remove_road(id)),
-            MapRemoveBuilding(id) => drop(goria.map_mut().remove_building(id)),
+        {
+            let gov = goria.read::<Government>();
+            if cost > Money::ZERO && gov.money < cost {
+                let reason = format!(
+                    "not enough money: costs {} but only {} left",
+                    cost, gov.money
+                );
+                log::info!("rejected command {:?}: {}", self, reason);
+                let mut rejected = goria.write::<RejectedCommands>();
+                if rejected.rejected.len() == MAX_REJECTED {
+                    rejected.rejected.pop_front();
+                }
+                rejected.rejected.push_back(RejectedCommand {
+                    tick: goria.get_tick(),
+                    command: self.clone(),
+                    reason,
+                });
+                return Money::ZERO;
+            }
+        }
+
+        // whether the command did what it was paid for
+        let applied = match *self {
+            MapRemoveIntersection(id) => {
+                goria.map_mut().remove_intersection(id);
+                true
+            }
+            MapRemoveRoad(id) => goria.map_mut().remove_road(id).is_some(),
+            MapRemoveBuilding(id) => goria.map_mut().remove_building(id).is_some(),
             MapBuildHouse(id) => {
-                if let Some(build) = goria.map_mut().build_house(id) {
-                    let mut infos = goria.write::<BuildingInfos>();
-                    infos.insert(build);
+                let build = goria.map_mut().build_house(id);
+                if let Some(build) = build {
+                    goria.write::<BuildingInfos>().insert(build);
                 }
+                build.is_some()
             }
-            MapMakeConnection(from, to, interpoint, ref pat) => {
-                goria
-                    .write::<Map>()
-                    .make_connection(from, to, interpoint, pat);
+            MapSetLotKind(id, kind) => {
+                goria.map_mut().set_lot_kind(id, kind);
+                true
             }
-            MapUpdateIntersectionPolicy(id, tp, lp) => {
+            MapTerraform(center, radius, kind) => goria.map_mut().terraform(center, radius, kind),
+            MapMakeConnection(from, to, interpoint, ref pat) => goria
+                .write::<Map>()
+                .make_connection(from, to, interpoint, pat)
+                .is_some(),
+            MapUpdateIntersectionPolicy(id, tp, ref lp) => {
                 goria.map_mut().update_intersection(id, move |i| {
-                    i.light_policy = lp;
+                    i.light_policy = lp.clone();
                     i.turn_policy = tp;
-                })
+                });
+                true
+            }
+            MapSetGreenWave(ref corridor) => goria.map_mut().set_green_wave(corridor),
+            MapCloseRoad(id, kind, duration) => {
+                let now = goria.read::<GameTime>().seconds;
+                let closure = Closure {
+                    kind,
+                    until: duration.map(|d| now + d),
+                };
+                goria.map_mut().set_road_closure(id, Some(closure));
+                true
+            }
+            MapReopenRoad(id) => {
+                goria.map_mut().set_road_closure(id, None);
+                true
             }
             MapBuildSpecialBuilding(id, obb, kind, gen) => {
-                if let Some(id) = goria
+                let build = goria
                     .write::<Map>()
-                    .build_special_building(id, &obb, kind, gen)
-                {
-                    goria.write::<BuildingInfos>().insert(id);
+                    .build_special_building(id, &obb, kind, gen);
+                if let Some(build) = build {
+                    goria.write::<BuildingInfos>().insert(build);
                 }
+                build.is_some()
+            }
+            MapAddPowerLine(src, dst) => goria.map_mut().add_power_line(src, dst).is_some(),
+            MapRemovePowerLine(id) => goria.map_mut().remove_power_line(id).is_some(),
+            SetGameTime(gt) => {
+                *goria.write::<GameTime>() = gt;
+                true
+            }
+            MapLoadParis => {
+                map_model::procgen::load_parismap(&mut *goria.map_mut());
+                true
             }
-            SetGameTime(gt) => *goria.write::<GameTime>() = gt,
-            MapLoadParis => map_model::procgen::load_parismap(&mut *goria.map_mut()),
             MapLoadTestField(pos, size, spacing) => {
-                map_model::procgen::load_testfield(&mut *goria.map_mut(), pos, size, spacing)
+                map_model::procgen::load_testfield(&mut *goria.map_mut(), pos, size, spacing);
+                true
             }
             ResetSave => {
                 *goria = Egregoria::new(10);
+                true
             }
             UpdateTransform(e, t) => {
                 if let Some(x) = goria.comp_mut(ent_from_id(e)) {
                     *x = t
                 }
+                true
+            }
+            SetTaxes(taxes) => {
+                *goria.write::<Taxes>() = taxes;
+                true
+            }
+            SetParkingPrices(prices) => {
+                goria.write::<ParkingManagement>().prices = prices;
+                true
+            }
+            AddBusStop(pos) => {
+                let map = goria.map();
+                let stop = goria.write::<Transit>().add_stop(&map, pos);
+                stop.is_some()
             }
+            RemoveBusStop(id) => {
+                remove_bus_stop(goria, id);
+                true
+            }
+            AddBusLine(ref stops) => spawn_bus_line(goria, stops.clone()).is_some(),
+            RemoveBusLine(id) => {
+                remove_bus_line(goria, id);
+                true
+            }
+            Undo => {
+                MapHistory::undo(goria);
+                true
+            }
+            Redo => {
+                MapHistory::redo(goria);
+                true
+            }
+        };
+
+        if !applied || cost == Money::ZERO {
+            return Money::ZERO;
+        }
+
+        goria.write::<Government>().money -= cost;
+        let mut ledger = goria.write::<Ledger>();
+        let day = ledger.day_mut(goria.read::<GameTime>().daytime.day);
+        if cost > Money::ZERO {
+            day.expenses += cost;
+        } else {
+            day.refunds -= cost;
         }
+        cost
     }
 }
 
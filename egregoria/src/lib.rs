@@ -24,14 +24,16 @@
 
 use crate::economy::{Bought, Sold, Workers};
 use crate::engine_interaction::{Selectable, WorldCommands};
-use crate::map_dynamic::{Itinerary, Router};
+use crate::map_dynamic::{Itinerary, MapHistory, Router};
 use crate::pedestrians::Pedestrian;
 use crate::physics::CollisionWorld;
 use crate::physics::{Collider, Kinematics};
 use crate::souls::add_souls_to_empty_buildings;
-use crate::souls::desire::{BuyFood, Home, Work};
-use crate::souls::goods_company::GoodsCompany;
+use crate::souls::desire::{BuyFood, Home, Shop, Work};
+use crate::souls::goods_company::{GoodsCompany, GoodsCompanyRegistry};
 use crate::souls::human::HumanDecision;
+use crate::souls::population::Happiness;
+use crate::transit::Bus;
 use crate::vehicles::Vehicle;
 use atomic_refcell::{AtomicRef, AtomicRefMut};
 use common::saveload::Encoder;
@@ -89,8 +91,9 @@ macro_rules! register_resource {
                 }),
                 load: Box::new(|goria, v| {
                     if let Some(v) = v {
-                        if let Ok(res) = <common::saveload::Bincode as common::saveload::Encoder>::decode::<$t>(&v) {
-                            goria.insert(res);
+                        match <common::saveload::Bincode as common::saveload::Encoder>::decode::<$t>(&v) {
+                            Ok(res) => goria.insert(res),
+                            Err(e) => log::error!("couldn't load {}, starting from scratch: {}", $name, e),
                         }
                     }
                 })
@@ -109,8 +112,9 @@ macro_rules! register_resource {
                 }),
                 load: Box::new(|goria, v| {
                     if let Some(v) = v {
-                        if let Ok(res) = <common::saveload::Bincode as common::saveload::Encoder>::decode::<$t>(&v) {
-                            goria.insert(res);
+                        match <common::saveload::Bincode as common::saveload::Encoder>::decode::<$t>(&v) {
+                            Ok(res) => goria.insert(res),
+                            Err(e) => log::error!("couldn't load {}, starting from scratch: {}", $name, e),
                         }
                     }
                 })
@@ -154,6 +158,7 @@ pub mod pedestrians;
 pub mod physics;
 pub mod souls;
 mod tests;
+pub mod transit;
 pub mod utils;
 pub mod vehicles;
 
@@ -266,7 +271,13 @@ impl Egregoria {
         }
 
         for command in &commands.commands {
-            command.apply(self);
+            if command.is_map_editing() {
+                MapHistory::before_edit(self);
+                let cost = command.apply(self);
+                MapHistory::after_edit(self, cost);
+            } else {
+                command.apply(self);
+            }
         }
 
         game_schedule.execute(self);
@@ -296,6 +307,14 @@ impl Egregoria {
             hashes.insert(name, hash(&*v));
         }
 
+        // not saved as it is loaded from the assets, but players with different ones desync
+        let registry = self.read::<GoodsCompanyRegistry>();
+        let companies =
+            common::saveload::Bincode::encode(&registry.descriptions.values().collect::<Vec<_>>());
+        if let Ok(companies) = companies {
+            hashes.insert("companies".to_string(), hash(&companies));
+        }
+
         hashes
     }
 
@@ -309,7 +328,9 @@ impl Egregoria {
             );
             return None;
         }
-        Self::try_from(ser).ok()
+        Self::try_from(ser)
+            .map_err(|e| log::error!("couldn't load save {}: {}", save_name, e))
+            .ok()
     }
 
     pub fn save_to_disk(&self, save_name: &'static str) {
@@ -485,9 +506,11 @@ fn registry() -> Registry<u64> {
     let mut registry = Registry::default();
     register!(registry;
         Bought,
+        Bus,
         BuyFood,
         Collider,
         GoodsCompany,
+        Happiness,
         Home,
         HumanDecision,
         Itinerary,
@@ -496,6 +519,7 @@ fn registry() -> Registry<u64> {
         Pedestrian,
         Router,
         Selectable,
+        Shop,
         Sold,
         Transform,
         Vehicle,
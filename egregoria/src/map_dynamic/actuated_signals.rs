@@ -0,0 +1,175 @@
+use crate::map_dynamic::Itinerary;
+use crate::utils::time::GameTime;
+use crate::vehicles::Vehicle;
+use geom::Transform;
+use legion::world::SubWorld;
+use legion::{system, IntoQuery};
+use map_model::{
+    IntersectionID, LaneID, LightPolicy, Map, TrafficBehavior, TrafficControl, TraverseKind,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A green phase lasts at least this long, in seconds
+pub const MIN_GREEN: u32 = 5;
+/// A green phase isn't extended past this if vehicles are waiting elsewhere, in seconds
+pub const MAX_GREEN: u32 = 40;
+pub const ORANGE_LENGTH: u32 = 4;
+/// Vehicles closer than this to the stop line count as waiting, in meters
+pub const DETECTOR_LENGTH: f32 = 30.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignalStage {
+    Green,
+    Orange,
+}
+
+/// State of the controller of an intersection with `LightPolicy::Actuated`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActuatedSignal {
+    /// Index of the phase having the green light, see `LightPolicy::phases`
+    pub phase: usize,
+    pub stage: SignalStage,
+    /// Game seconds at which the stage started
+    pub since: u32,
+}
+
+impl ActuatedSignal {
+    pub fn new(now: u32) -> Self {
+        Self {
+            phase: 0,
+            stage: SignalStage::Green,
+            since: now,
+        }
+    }
+
+    /// `demand` holds the number of vehicles waiting for each phase.
+    /// The green is extended while vehicles keep coming and phases nobody waits at are skipped.
+    pub fn update(&mut self, now: u32, demand: &[u32]) {
+        let n = demand.len();
+        if n == 0 {
+            return;
+        }
+        if self.phase >= n {
+            *self = Self::new(now);
+        }
+        let elapsed = now.saturating_sub(self.since);
+
+        match self.stage {
+            SignalStage::Green => {
+                let others_waiting = demand
+                    .iter()
+                    .enumerate()
+                    .any(|(i, &d)| i != self.phase && d > 0);
+                let mine = demand.get(self.phase).copied().unwrap_or(0);
+
+                if others_waiting && elapsed >= MIN_GREEN && (mine == 0 || elapsed >= MAX_GREEN) {
+                    self.stage = SignalStage::Orange;
+                    self.since = now;
+                }
+            }
+            SignalStage::Orange => {
+                if elapsed >= ORANGE_LENGTH {
+                    let phase = self.phase;
+                    self.phase = (1..=n)
+                        .map(|k| (phase + k) % n)
+                        .find(|&i| demand.get(i).copied().unwrap_or(0) > 0)
+                        .unwrap_or((phase + 1) % n);
+                    self.stage = SignalStage::Green;
+                    self.since = now;
+                }
+            }
+        }
+    }
+
+    pub fn behavior(&self, phase: usize) -> TrafficBehavior {
+        if phase != self.phase {
+            return TrafficBehavior::RED;
+        }
+        match self.stage {
+            SignalStage::Green => TrafficBehavior::GREEN,
+            SignalStage::Orange => TrafficBehavior::ORANGE,
+        }
+    }
+}
+
+register_resource!(ActuatedSignals, "actuated_signals");
+#[derive(Default, Serialize, Deserialize)]
+pub struct ActuatedSignals {
+    signals: BTreeMap<IntersectionID, ActuatedSignal>,
+    /// Vehicles waiting on each lane controlled by an actuated light, as of the last tick
+    queues: BTreeMap<LaneID, u32>,
+}
+
+impl ActuatedSignals {
+    pub fn get(&self, inter: IntersectionID) -> Option<&ActuatedSignal> {
+        self.signals.get(&inter)
+    }
+
+    pub fn queue(&self, lane: LaneID) -> u32 {
+        self.queues.get(&lane).copied().unwrap_or(0)
+    }
+}
+
+register_system!(actuated_signals);
+/// Counts the vehicles waiting at actuated lights and switches them accordingly
+#[system]
+#[read_component(Vehicle)]
+#[read_component(Itinerary)]
+#[read_component(Transform)]
+pub fn actuated_signals(
+    #[resource] map: &mut Map,
+    #[resource] time: &GameTime,
+    #[resource] signals: &mut ActuatedSignals,
+    sw: &SubWorld<'_>,
+) {
+    let actuated: Vec<(IntersectionID, Vec<Vec<LaneID>>)> = map
+        .intersections()
+        .values()
+        .filter(|inter| matches!(inter.light_policy, LightPolicy::Actuated))
+        .map(|inter| (inter.id, LightPolicy::phases(inter, map.roads())))
+        .collect();
+
+    signals
+        .signals
+        .retain(|id, _| actuated.iter().any(|(x, _)| x == id));
+    signals.queues.clear();
+    if actuated.is_empty() {
+        return;
+    }
+
+    for (_, it, trans) in <(&Vehicle, &Itinerary, &Transform)>::query().iter(sw) {
+        let lane_id = match it.get_travers().map(|t| t.kind) {
+            Some(TraverseKind::Lane(id)) => id,
+            _ => continue,
+        };
+        let lane = unwrap_cont!(map.lanes().get(lane_id));
+        if !matches!(lane.control, TrafficControl::Actuated(_)) {
+            continue;
+        }
+        if lane.points.last().is_close(trans.position, DETECTOR_LENGTH) {
+            *signals.queues.entry(lane_id).or_default() += 1;
+        }
+    }
+
+    let now = time.seconds;
+    for (id, phases) in actuated {
+        let demand: Vec<u32> = phases
+            .iter()
+            .map(|lanes| lanes.iter().map(|&l| signals.queue(l)).sum())
+            .collect();
+
+        let signal = signals
+            .signals
+            .entry(id)
+            .or_insert_with(|| ActuatedSignal::new(now));
+        signal.update(now, &demand);
+
+        for (i, lanes) in phases.into_iter().enumerate() {
+            let behavior = signal.behavior(i);
+            for lane in lanes {
+                map.set_actuated_light(lane, behavior);
+            }
+        }
+    }
+}
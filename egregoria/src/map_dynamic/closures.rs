@@ -0,0 +1,37 @@
+use crate::map_dynamic::Itinerary;
+use crate::utils::time::GameTime;
+use legion::world::SubWorld;
+use legion::{system, IntoQuery};
+use map_model::Map;
+use serde::{Deserialize, Serialize};
+
+register_resource!(RoadClosures, "road_closures");
+#[derive(Default, Serialize, Deserialize)]
+pub struct RoadClosures {
+    /// Map dirt id at which the routes were last checked against the closures
+    dirt_id: u32,
+}
+
+register_system!(road_closures);
+/// Lifts the closures that expired, and reroutes the vehicles on or planning to go through a closure
+#[system]
+#[write_component(Itinerary)]
+pub fn road_closures(
+    #[resource] map: &mut Map,
+    #[resource] time: &GameTime,
+    #[resource] closures: &mut RoadClosures,
+    sw: &mut SubWorld<'_>,
+) {
+    map.lift_expired_closures(time.seconds);
+    if closures.dirt_id == map.dirt_id.0 {
+        return;
+    }
+    closures.dirt_id = map.dirt_id.0;
+
+    let lanes = map.lanes();
+    for it in <&mut Itinerary>::query().iter_mut(sw) {
+        if it.goes_through(|id| matches!(lanes.get(id), Some(lane) if lane.closure.is_some())) {
+            it.reroute();
+        }
+    }
+}
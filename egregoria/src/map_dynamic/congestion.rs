@@ -0,0 +1,45 @@
+use crate::map_dynamic::Itinerary;
+use crate::physics::Kinematics;
+use crate::utils::time::GameTime;
+use crate::vehicles::{Vehicle, VehicleState};
+use legion::world::SubWorld;
+use legion::{system, IntoQuery};
+use map_model::{LaneID, Map, TraverseKind};
+use std::collections::BTreeMap;
+
+/// How often lane speeds are sampled, in seconds
+pub const SPEED_SAMPLE_PERIOD: u32 = 5;
+
+register_system!(lane_speeds);
+/// Samples the speed of the vehicles on each lane so that the pathfinder can avoid congestion.
+/// The samples are sorted by lane so every client of a lockstep game blends them the same way.
+#[system]
+#[read_component(Vehicle)]
+#[read_component(Itinerary)]
+#[read_component(Kinematics)]
+pub fn lane_speeds(#[resource] map: &mut Map, #[resource] time: &GameTime, sw: &SubWorld<'_>) {
+    if !time.tick(SPEED_SAMPLE_PERIOD) {
+        return;
+    }
+
+    let mut sums: BTreeMap<LaneID, (f32, u32)> = BTreeMap::new();
+    for (vehicle, it, kin) in <(&Vehicle, &Itinerary, &Kinematics)>::query().iter(sw) {
+        if !matches!(
+            vehicle.state,
+            VehicleState::Driving | VehicleState::Panicking(_)
+        ) {
+            continue;
+        }
+        if let Some(TraverseKind::Lane(id)) = it.get_travers().map(|t| t.kind) {
+            let (sum, n) = sums.entry(id).or_default();
+            *sum += kin.velocity.magnitude();
+            *n += 1;
+        }
+    }
+
+    let samples = sums
+        .into_iter()
+        .map(|(id, (sum, n))| (id, sum / n as f32))
+        .collect();
+    map.observe_lane_speeds(&samples);
+}
@@ -0,0 +1,96 @@
+use crate::souls::goods_company::GoodsCompany;
+use legion::world::SubWorld;
+use legion::{system, IntoQuery};
+use map_model::{BuildingID, Map, PowerLine};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Buildings linked by power lines, electricity flows freely inside a grid
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Grid {
+    pub buildings: Vec<BuildingID>,
+    /// Power sent to the grid by the plants this tick, in kW
+    pub production: i32,
+    /// Power needed by the consumers this tick, in kW
+    pub consumption: i32,
+}
+
+impl Grid {
+    /// Proportion of the consumption that is met, below 1 means a brownout
+    pub fn satisfaction(&self) -> f32 {
+        if self.consumption <= 0 {
+            return 1.0;
+        }
+        (self.production as f32 / self.consumption as f32).min(1.0)
+    }
+}
+
+register_resource!(ElectricityFlow, "electricity_flow");
+#[derive(Default, Serialize, Deserialize)]
+pub struct ElectricityFlow {
+    grids: Vec<Grid>,
+    grid_of: BTreeMap<BuildingID, usize>,
+    /// Map dirt id at which the grids were last computed
+    dirt_id: u32,
+}
+
+impl ElectricityFlow {
+    pub fn grids(&self) -> &[Grid] {
+        &self.grids
+    }
+
+    pub fn grid(&self, b: BuildingID) -> Option<&Grid> {
+        self.grids.get(*self.grid_of.get(&b)?)
+    }
+
+    /// Proportion of the building's needs that is met.
+    /// Buildings only depend on the power plants once they are connected to a grid,
+    /// before that they make do on their own.
+    pub fn satisfaction(&self, b: BuildingID) -> f32 {
+        self.grid(b).map(Grid::satisfaction).unwrap_or(1.0)
+    }
+
+    fn update_grids(&mut self, map: &Map) {
+        self.grid_of.clear();
+        self.grids = PowerLine::grids(map.power_lines())
+            .into_iter()
+            .enumerate()
+            .map(|(i, buildings)| {
+                for &b in &buildings {
+                    self.grid_of.insert(b, i);
+                }
+                Grid {
+                    buildings,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        self.dirt_id = map.dirt_id.0;
+    }
+}
+
+register_system!(electricity_flow);
+/// Balances what power plants produce against what buildings consume on each grid
+#[system]
+#[read_component(GoodsCompany)]
+pub fn electricity_flow(
+    #[resource] map: &Map,
+    #[resource] flow: &mut ElectricityFlow,
+    sw: &SubWorld<'_>,
+) {
+    if flow.dirt_id != map.dirt_id.0 {
+        flow.update_grids(map);
+    }
+
+    for grid in &mut flow.grids {
+        grid.production = 0;
+        grid.consumption = 0;
+    }
+
+    for company in <&GoodsCompany>::query().iter(sw) {
+        let i = *unwrap_cont!(flow.grid_of.get(&company.building));
+        let grid = &mut flow.grids[i];
+        grid.production += company.power_output;
+        grid.consumption += company.power_consumption;
+    }
+}
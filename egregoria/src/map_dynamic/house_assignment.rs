@@ -1,13 +1,49 @@
+use crate::vehicles::VehicleID;
 use crate::SoulID;
-use map_model::BuildingID;
+use map_model::{Building, BuildingID};
 use serde::{Deserialize, Serialize};
 use slotmap::SecondaryMap;
 use std::collections::BTreeMap;
 
+/// Lot area needed per resident, in square meters
+const AREA_PER_RESIDENT: f32 = 200.0;
+pub const MAX_HOUSEHOLD_SIZE: u32 = 8;
+
+/// How many people can live in a house, depending on the size of its lot
+pub fn house_capacity(house: &Building) -> u32 {
+    let [w, h] = house.obb.axis();
+    ((w.magnitude() * h.magnitude() / AREA_PER_RESIDENT) as u32).clamp(1, MAX_HOUSEHOLD_SIZE)
+}
+
+/// The people living in a house, sharing its car
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Household {
+    pub adults: Vec<SoulID>,
+    pub children: Vec<SoulID>,
+    pub car: Option<VehicleID>,
+    /// The adult currently using the car, the others have to find another way
+    pub car_user: Option<SoulID>,
+}
+
+impl Household {
+    pub fn len(&self) -> usize {
+        self.adults.len() + self.children.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = SoulID> + '_ {
+        self.adults.iter().chain(self.children.iter()).copied()
+    }
+}
+
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct BuildingInfo {
     pub owner: Option<SoulID>,
     pub inside: Vec<SoulID>,
+    pub household: Household,
 }
 
 register_resource!(BuildingInfos, "binfos");
@@ -15,6 +51,7 @@ register_resource!(BuildingInfos, "binfos");
 pub struct BuildingInfos {
     assignment: SecondaryMap<BuildingID, BuildingInfo>,
     owners: BTreeMap<SoulID, BuildingID>,
+    homes: BTreeMap<SoulID, BuildingID>,
 }
 
 impl BuildingInfos {
@@ -22,6 +59,18 @@ impl BuildingInfos {
         self.assignment.insert(building, BuildingInfo::default());
     }
 
+    /// Forgets the building, its residents have to leave beforehand
+    pub fn remove(&mut self, building: BuildingID) -> Option<BuildingInfo> {
+        let info = self.assignment.remove(building)?;
+        self.owners.retain(|_, &mut b| b != building);
+        self.homes.retain(|_, &mut b| b != building);
+        Some(info)
+    }
+
+    pub fn buildings(&self) -> impl Iterator<Item = BuildingID> + '_ {
+        self.assignment.keys()
+    }
+
     pub fn get(&self, building: BuildingID) -> Option<&BuildingInfo> {
         self.assignment.get(building)
     }
@@ -30,6 +79,10 @@ impl BuildingInfos {
         self.assignment.get_mut(building)
     }
 
+    pub fn owners(&self) -> impl Iterator<Item = (SoulID, BuildingID)> + '_ {
+        self.owners.iter().map(|(&soul, &b)| (soul, b))
+    }
+
     pub fn building_owned_by(&self, soul: SoulID) -> Option<BuildingID> {
         self.owners.get(&soul).copied()
     }
@@ -41,6 +94,61 @@ impl BuildingInfos {
         self.owners.insert(soul, building);
     }
 
+    pub fn home_of(&self, soul: SoulID) -> Option<BuildingID> {
+        self.homes.get(&soul).copied()
+    }
+
+    /// Adds the soul to the household of the house, the first adult becomes its owner
+    pub fn add_resident(&mut self, house: BuildingID, soul: SoulID, adult: bool) {
+        let b = unwrap_ret!(self.get_mut(house));
+        if adult {
+            b.household.adults.push(soul);
+        } else {
+            b.household.children.push(soul);
+        }
+        if adult && b.owner.is_none() {
+            self.set_owner(house, soul);
+        }
+        self.homes.insert(soul, house);
+    }
+
+    /// Removes the soul from its household, another adult becomes the owner if there is one
+    pub fn remove_resident(&mut self, soul: SoulID) {
+        let house = unwrap_ret!(self.homes.remove(&soul));
+        let b = unwrap_ret!(self.get_mut(house));
+        b.household.adults.retain(|&x| x != soul);
+        b.household.children.retain(|&x| x != soul);
+        if b.household.car_user == Some(soul) {
+            b.household.car_user = None;
+        }
+        if b.owner != Some(soul) {
+            return;
+        }
+        b.owner = None;
+        let next_owner = b.household.adults.first().copied();
+        self.owners.remove(&soul);
+        if let Some(next_owner) = next_owner {
+            self.set_owner(house, next_owner);
+        }
+    }
+
+    /// Returns whether the soul can use the car of the household
+    pub fn claim_car(&mut self, house: BuildingID, soul: SoulID) -> bool {
+        let h = unwrap_ret!(self.get_mut(house).map(|b| &mut b.household), false);
+        if h.car_user.is_some() && h.car_user != Some(soul) {
+            return false;
+        }
+        h.car_user = Some(soul);
+        true
+    }
+
+    pub fn release_car(&mut self, house: BuildingID, soul: SoulID) {
+        let h = unwrap_ret!(self.get_mut(house).map(|b| &mut b.household));
+        if h.car_user == Some(soul) {
+            h.car_user = None;
+        }
+    }
+
     pub fn get_in(&mut self, building: BuildingID, e: SoulID) {
         let b = unwrap_ret!(self.get_mut(building));
         if cfg!(debug_assertions) && b.inside.contains(&e) {
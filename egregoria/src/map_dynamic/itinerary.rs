@@ -1,11 +1,11 @@
 use crate::utils::time::GameTime;
-use geom::{Transform, Vec3};
+use geom::{PolyLine3, Transform, Vec3};
 use imgui::Ui;
 use imgui_inspect::{InspectArgsDefault, InspectRenderDefault};
 use imgui_inspect_derive::Inspect;
 use legion::world::SubWorld;
 use legion::{system, Query};
-use map_model::{Map, PathKind, Pathfinder, Traversable, TraverseDirection, TraverseKind};
+use map_model::{LaneID, Map, PathKind, Pathfinder, Traversable, TraverseDirection, TraverseKind};
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug, Serialize, Deserialize, Inspect)]
@@ -36,7 +36,25 @@ pub struct Route {
     pub cur: Traversable,
 }
 
+impl Route {
+    /// Points to follow along the current traversable, stopping at the end position on the last one
+    fn route_points(&self, points: PolyLine3) -> Vec<Vec3> {
+        if !self.reversed_route.is_empty() {
+            return points.into_vec();
+        }
+        let (proj_pos, id) = points.project_segment(self.end_pos);
+        let mut v = Vec::with_capacity(id + 2);
+        #[allow(clippy::indexing_slicing)]
+        v.extend(&points.as_slice()[..id]);
+        v.push(proj_pos);
+        v.push(self.end_pos);
+        v
+    }
+}
+
 pub const OBJECTIVE_OK_DIST: f32 = 3.0;
+/// Distance driven while moving over to the next lane, in meters
+pub const LANE_CHANGE_LENGTH: f32 = 10.0;
 
 impl Itinerary {
     pub fn none() -> Self {
@@ -97,7 +115,12 @@ impl Itinerary {
             .rev()
             .collect();
 
-        reversed_route.pop(); // Remove start
+        // Remove start, unless the route starts by changing lanes
+        if let Some(first) = reversed_route.pop() {
+            if let TraverseKind::LaneChange { .. } = first.kind {
+                cur = first;
+            }
+        }
 
         if let Some(&Traversable {
             kind: TraverseKind::Lane(id),
@@ -141,6 +164,14 @@ impl Itinerary {
         };
 
         if self.local_path.is_empty() {
+            if self.is_changing_lane() {
+                // no gap was found along the lane, merge at its end anyway
+                if let Some(pos) = v {
+                    self.change_lane(map, pos);
+                }
+                return v;
+            }
+
             if let ItineraryKind::Route(ref mut r, pathkind) = self.kind {
                 r.cur = r.reversed_route.pop()?;
 
@@ -152,20 +183,60 @@ impl Itinerary {
                     }
                 };
 
-                if r.reversed_route.is_empty() {
-                    let (proj_pos, id) = points.project_segment(r.end_pos);
-                    #[allow(clippy::indexing_slicing)]
-                    self.local_path.extend(&points.as_slice()[..id]);
-                    self.local_path.push(proj_pos);
-                    self.local_path.push(r.end_pos);
-                } else {
-                    self.local_path = points.into_vec();
-                }
+                self.local_path = r.route_points(points);
             }
         }
         v
     }
 
+    /// Whether the rest of the route, the current traversable included, goes through one of the lanes
+    pub fn goes_through(&self, f: impl Fn(LaneID) -> bool) -> bool {
+        match &self.kind {
+            ItineraryKind::Route(r, _) => std::iter::once(&r.cur)
+                .chain(&r.reversed_route)
+                .any(|t| f(t.destination_lane())),
+            _ => false,
+        }
+    }
+
+    /// Drops the route so that a new one is searched from where the vehicle is
+    pub fn reroute(&mut self) {
+        if let ItineraryKind::Route(ref r, kind) = self.kind {
+            *self = Self::wait_for_reroute(kind, r.end_pos);
+        }
+    }
+
+    pub fn is_changing_lane(&self) -> bool {
+        matches!(
+            self.get_travers(),
+            Some(Traversable {
+                kind: TraverseKind::LaneChange { .. },
+                ..
+            })
+        )
+    }
+
+    /// Moves over to the lane the current lane change goes to, merging a bit ahead of `position`
+    pub fn change_lane(&mut self, map: &Map, position: Vec3) {
+        if !self.is_changing_lane() {
+            return;
+        }
+        if let ItineraryKind::Route(ref mut r, pathkind) = self.kind {
+            r.cur = unwrap_ret!(r.reversed_route.pop());
+
+            let points = match r.cur.points(map) {
+                Some(x) => x,
+                None => {
+                    *self = Self::wait_for_reroute(pathkind, r.end_pos);
+                    return;
+                }
+            };
+
+            let along = points.length_at_proj(points.project(position));
+            self.local_path = r.route_points(points.cut_start(along + LANE_CHANGE_LENGTH));
+        }
+    }
+
     #[allow(clippy::collapsible_else_if)]
     pub fn update(&mut self, position: Vec3, time: u32, map: &Map) {
         if let Some(p) = self.get_point() {
@@ -355,3 +426,9 @@ pub fn itinerary_update(
         it.update(trans.position, time.seconds, map)
     });
 }
+
+register_system!(routing_index_update);
+#[system]
+pub fn routing_index_update(#[resource] map: &mut Map, #[resource] time: &GameTime) {
+    map.update_routing_index(time.seconds);
+}
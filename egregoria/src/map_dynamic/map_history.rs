@@ -0,0 +1,155 @@
+use crate::economy::Money;
+use crate::map_dynamic::{BuildingInfos, Itinerary, ParkingManagement, Router};
+use crate::souls::goods_company::{close_company, GoodsCompany};
+use crate::souls::population::evict;
+use crate::vehicles::{Vehicle, VehicleID, VehicleState};
+use crate::{Egregoria, SoulID};
+use legion::{Entity, IntoQuery};
+use map_model::{BuildingID, Map, MapDiff};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Maximum number of edits kept for undo
+const HISTORY_SIZE: usize = 10;
+
+register_resource!(MapHistory, "map_history");
+/// The previous state of what the last map edits changed, to undo and redo them.
+/// Only the changes made by the editing commands themselves are kept, not what the
+/// simulation changes afterwards (like houses growing along a new road).
+/// Undoing an edit gives back what it cost (or takes back its refund), redoing it costs it again.
+/// Saved with the world so that players joining a game undo the same edits.
+#[derive(Default, Serialize, Deserialize)]
+pub struct MapHistory {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Edit {
+    diff: MapDiff,
+    /// What the edit cost, negative if it was a refund
+    cost: Money,
+}
+
+impl MapHistory {
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// What the edit to undo cost
+    pub fn undo_cost(&self) -> Money {
+        self.undo.back().map_or(Money::ZERO, |edit| edit.cost)
+    }
+
+    /// What the edit to redo cost
+    pub fn redo_cost(&self) -> Money {
+        self.redo.last().map_or(Money::ZERO, |edit| edit.cost)
+    }
+
+    /// Called before applying a map editing command, starts recording its changes
+    pub(crate) fn before_edit(goria: &mut Egregoria) {
+        goria.map_mut().start_recording();
+    }
+
+    /// Called after applying a map editing command. Only commands that changed the map
+    /// (not rejected ones) get an entry and invalidate the redo stack.
+    pub(crate) fn after_edit(goria: &mut Egregoria, cost: Money) {
+        let diff = goria.map_mut().stop_recording();
+        if diff.is_empty() {
+            return;
+        }
+        let mut history = goria.write::<MapHistory>();
+        let mut dropped: Vec<Edit> = history.redo.drain(..).collect();
+        if history.undo.len() == HISTORY_SIZE {
+            dropped.extend(history.undo.pop_front());
+        }
+        history.undo.push_back(Edit { diff, cost });
+
+        let mut map = goria.map_mut();
+        let kept: Vec<&MapDiff> = history.undo.iter().map(|edit| &edit.diff).collect();
+        for edit in &dropped {
+            map.release(&edit.diff, &kept);
+        }
+    }
+
+    pub(crate) fn undo(goria: &mut Egregoria) {
+        let mut edit = unwrap_ret!(goria.write::<MapHistory>().undo.pop_back());
+        goria.map_mut().apply_diff(&mut edit.diff);
+        goria.write::<MapHistory>().redo.push(edit);
+        fix_dependents(goria);
+    }
+
+    pub(crate) fn redo(goria: &mut Egregoria) {
+        let mut edit = unwrap_ret!(goria.write::<MapHistory>().redo.pop());
+        goria.map_mut().apply_diff(&mut edit.diff);
+        goria.write::<MapHistory>().undo.push_back(edit);
+        fix_dependents(goria);
+    }
+}
+
+/// Brings what depends on the map in line with it after undoing or redoing an edit,
+/// as buildings, lanes and parking spots may have appeared or disappeared
+fn fix_dependents(goria: &mut Egregoria) {
+    let map = goria.map();
+
+    let closed: Vec<SoulID> = <(Entity, &GoodsCompany)>::query()
+        .iter(&goria.world)
+        .filter(|(_, company)| !map.buildings().contains_key(company.building))
+        .map(|(&e, _)| SoulID(e))
+        .collect();
+
+    let gone: Vec<BuildingID> = goria
+        .read::<BuildingInfos>()
+        .buildings()
+        .filter(|&b| !map.buildings().contains_key(b))
+        .collect();
+
+    let stale_vehicles: Vec<VehicleID> = <(Entity, &Vehicle)>::query()
+        .iter(&goria.world)
+        .filter(|(_, v)| match v.state {
+            VehicleState::Parked(ref spot) | VehicleState::RoadToPark(_, _, ref spot) => {
+                !spot.exists(&map.parking)
+            }
+            _ => false,
+        })
+        .map(|(&e, _)| VehicleID(e))
+        .collect();
+
+    {
+        let mut binfos = goria.write::<BuildingInfos>();
+        for id in map.buildings().keys() {
+            if binfos.get(id).is_none() {
+                binfos.insert(id);
+            }
+        }
+    }
+    drop(map);
+
+    for company in closed {
+        close_company(goria, company);
+    }
+    for house in gone {
+        evict(goria, house);
+        goria.write::<BuildingInfos>().remove(house);
+    }
+    for vehicle in stale_vehicles {
+        crate::vehicles::repark(goria, vehicle);
+    }
+
+    let map = goria.resources.get::<Map>().unwrap();
+    let mut pm = goria.resources.get_mut::<ParkingManagement>().unwrap();
+    for router in <&mut Router>::query().iter_mut(&mut goria.world) {
+        router.forget_missing_spots(&mut pm, &map.parking);
+    }
+    for it in <&mut Itinerary>::query().iter_mut(&mut goria.world) {
+        if it.goes_through(|id| !map.lanes().contains_key(id))
+            || matches!(it.get_travers(), Some(t) if t.points(&map).is_none())
+        {
+            it.reroute();
+        }
+    }
+}
@@ -1,9 +1,19 @@
+mod actuated_signals;
+mod closures;
+mod congestion;
+mod electricity;
 mod house_assignment;
 mod itinerary;
+mod map_history;
 mod parking;
 mod router;
 
+pub use actuated_signals::*;
+pub use closures::*;
+pub use congestion::*;
+pub use electricity::*;
 pub use house_assignment::*;
 pub use itinerary::*;
+pub use map_history::*;
 pub use parking::*;
 pub use router::*;
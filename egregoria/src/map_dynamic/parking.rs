@@ -1,17 +1,61 @@
+use crate::economy::Money;
+use crate::utils::time::GameTime;
 use common::PtrCmp;
-use geom::Vec3;
-use map_model::{LaneKind, Map, ParkingSpot, ParkingSpotID, ParkingSpots};
+use geom::{vec2, Vec2, Vec3, AABB};
+use legion::system;
+use map_model::{
+    BuildingGen, BuildingKind, LaneKind, Map, ParkingSpot, ParkingSpotID, ParkingSpots,
+    ProjectFilter, ProjectKind,
+};
+use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// How far from the destination parking lots and garages are considered, in meters
+const OFFSTREET_RADIUS: f32 = 150.0;
+/// How many meters a driver is willing to walk to save a cent
+const METERS_PER_CENT: f32 = 0.5;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[repr(transparent)]
 pub struct SpotReservation(ParkingSpotID);
 
+/// Price paid each time a vehicle parks, zero means free parking
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct ParkingPrices {
+    pub street: Money,
+    pub lot: Money,
+    pub garage: Money,
+}
+
+/// Parking lots and garages the player can build
+pub struct ParkingFacility {
+    pub name: &'static str,
+    pub bkind: BuildingKind,
+    pub bgen: BuildingGen,
+    pub size: f32,
+}
+
+pub const PARKING_FACILITIES: [ParkingFacility; 2] = [
+    ParkingFacility {
+        name: "Parking lot",
+        bkind: BuildingKind::ParkingLot,
+        bgen: BuildingGen::Parking { levels: 1 },
+        size: 40.0,
+    },
+    ParkingFacility {
+        name: "Parking garage",
+        bkind: BuildingKind::ParkingGarage,
+        bgen: BuildingGen::Parking { levels: 4 },
+        size: 40.0,
+    },
+];
+
 register_resource!(ParkingManagement, "pmanagement");
 #[derive(Default, Serialize, Deserialize)]
 pub struct ParkingManagement {
     reserved_spots: BTreeSet<ParkingSpotID>,
+    pub prices: ParkingPrices,
 }
 
 impl ParkingManagement {
@@ -27,10 +71,79 @@ impl ParkingManagement {
     }
 
     pub fn is_spot_free(&self, spot: ParkingSpotID) -> bool {
-        self.reserved_spots.contains(&spot)
+        !self.reserved_spots.contains(&spot)
     }
 
+    pub fn price(&self, map: &Map, spot: ParkingSpotID) -> Money {
+        let building = map.parking.get(spot).and_then(|s| s.building);
+        match building
+            .and_then(|b| map.buildings().get(b))
+            .map(|b| b.kind)
+        {
+            Some(BuildingKind::ParkingGarage) => self.prices.garage,
+            Some(BuildingKind::ParkingLot) => self.prices.lot,
+            _ => self.prices.street,
+        }
+    }
+
+    /// Reserves the free spot near `near` with the best tradeoff between walking distance
+    /// and price, be it on the street or in a parking lot or garage
     pub fn reserve_near(&mut self, near: Vec3, map: &Map) -> Option<SpotReservation> {
+        let cost = |spot: ParkingSpotID| {
+            let walk_from = map
+                .parking
+                .get(spot)
+                .and_then(|s| match s.building {
+                    Some(b) => Some(map.buildings().get(b)?.door_pos),
+                    None => Some(s.trans.position),
+                })
+                .unwrap_or(near);
+            let price = self.price(map, spot).0 as f32;
+            OrderedFloat(walk_from.distance(near) + price * METERS_PER_CENT)
+        };
+
+        let spot = self
+            .street_spot_near(near, map)
+            .into_iter()
+            .chain(self.offstreet_spots_near(near, map))
+            .min_by_key(|&spot| cost(spot))?;
+
+        self.reserved_spots.insert(spot);
+        Some(SpotReservation(spot))
+    }
+
+    /// Reserves the free spot closest to `near` however far it is, whatever its price
+    pub fn reserve_closest(&mut self, near: Vec3, map: &Map) -> Option<SpotReservation> {
+        let (spot, _) = map
+            .parking
+            .all_spots()
+            .filter(|(spot, _)| !self.reserved_spots.contains(spot))
+            .min_by_key(|(_, s)| OrderedFloat(s.trans.position.distance2(near)))?;
+
+        self.reserved_spots.insert(spot);
+        Some(SpotReservation(spot))
+    }
+
+    /// A free spot in each parking lot or garage around `near`
+    fn offstreet_spots_near<'a>(
+        &'a self,
+        near: Vec3,
+        map: &'a Map,
+    ) -> impl Iterator<Item = ParkingSpotID> + 'a {
+        map.spatial_map()
+            .query_around(near.xy(), OFFSTREET_RADIUS, ProjectFilter::BUILDING)
+            .filter_map(move |kind| match kind {
+                ProjectKind::Building(id) => map
+                    .parking
+                    .building_spots(id)
+                    .iter()
+                    .copied()
+                    .find(|spot| !self.reserved_spots.contains(spot)),
+                _ => None,
+            })
+    }
+
+    fn street_spot_near(&self, near: Vec3, map: &Map) -> Option<ParkingSpotID> {
         let lane = map.nearest_lane(near, LaneKind::Driving)?;
         let lane = map.lanes().get(lane)?;
 
@@ -50,8 +163,8 @@ impl ParkingManagement {
 
                 if let Some(p_iter) = map.parking.closest_spots(plane, near) {
                     for spot in p_iter {
-                        if self.reserved_spots.insert(spot) {
-                            return Some(SpotReservation(spot));
+                        if !self.reserved_spots.contains(&spot) {
+                            return Some(spot);
                         }
                     }
                 }
@@ -89,4 +202,101 @@ impl SpotReservation {
     pub fn park_pos(&self, map: &Map) -> Option<Vec3> {
         map.parking_to_drive_pos(self.0)
     }
+
+    pub fn fee(&self, map: &Map, pm: &ParkingManagement) -> Money {
+        pm.price(map, self.0)
+    }
+}
+
+/// Side of the square areas over which parking occupancy is aggregated, in meters
+pub const OCCUPANCY_AREA_SIZE: f32 = 500.0;
+/// How often the occupancy statistics are refreshed, in seconds
+const OCCUPANCY_PERIOD: u32 = 10;
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Occupancy {
+    pub street_used: u32,
+    pub street_total: u32,
+    pub offstreet_used: u32,
+    pub offstreet_total: u32,
+}
+
+impl Occupancy {
+    pub fn ratio(&self) -> f32 {
+        let total = self.street_total + self.offstreet_total;
+        if total == 0 {
+            return 0.0;
+        }
+        (self.street_used + self.offstreet_used) as f32 / total as f32
+    }
+
+    fn add(&mut self, other: &Occupancy) {
+        self.street_used += other.street_used;
+        self.street_total += other.street_total;
+        self.offstreet_used += other.offstreet_used;
+        self.offstreet_total += other.offstreet_total;
+    }
+}
+
+register_resource_noserialize!(ParkingOccupancy);
+/// Parking occupancy aggregated over a grid of square areas
+#[derive(Default)]
+pub struct ParkingOccupancy {
+    areas: BTreeMap<(i32, i32), Occupancy>,
+}
+
+impl ParkingOccupancy {
+    pub fn area_of(pos: Vec2) -> (i32, i32) {
+        (
+            (pos.x / OCCUPANCY_AREA_SIZE).floor() as i32,
+            (pos.y / OCCUPANCY_AREA_SIZE).floor() as i32,
+        )
+    }
+
+    pub fn area_bounds((x, y): (i32, i32)) -> AABB {
+        let ll = vec2(x as f32, y as f32) * OCCUPANCY_AREA_SIZE;
+        AABB::new(ll, ll + Vec2::splat(OCCUPANCY_AREA_SIZE))
+    }
+
+    /// Areas containing at least one spot
+    pub fn areas(&self) -> impl Iterator<Item = ((i32, i32), &Occupancy)> + '_ {
+        self.areas.iter().map(|(&area, occ)| (area, occ))
+    }
+
+    pub fn total(&self) -> Occupancy {
+        let mut total = Occupancy::default();
+        for occ in self.areas.values() {
+            total.add(occ);
+        }
+        total
+    }
+}
+
+register_system!(parking_occupancy);
+#[system]
+pub fn parking_occupancy(
+    #[resource] occupancy: &mut ParkingOccupancy,
+    #[resource] pm: &ParkingManagement,
+    #[resource] map: &Map,
+    #[resource] time: &GameTime,
+) {
+    if !time.tick(OCCUPANCY_PERIOD) {
+        return;
+    }
+
+    occupancy.areas.clear();
+    for (id, spot) in map.parking.all_spots() {
+        let occ = occupancy
+            .areas
+            .entry(ParkingOccupancy::area_of(spot.trans.position.xy()))
+            .or_default();
+        let used = u32::from(!pm.is_spot_free(id));
+        if spot.building.is_some() {
+            occ.offstreet_used += used;
+            occ.offstreet_total += 1;
+        } else {
+            occ.street_used += used;
+            occ.street_total += 1;
+        }
+    }
 }
@@ -1,15 +1,18 @@
-use crate::map_dynamic::{Itinerary, ParkingManagement, SpotReservation};
+use crate::economy::{Government, Ledger, Money};
+use crate::map_dynamic::{BuildingInfos, Itinerary, ParkingManagement, SpotReservation};
 use crate::pedestrians::{put_pedestrian_in_coworld, Location};
 use crate::physics::{Collider, CollisionWorld, Kinematics};
+use crate::transit::{bike_time, drive_time, walk_time, Bus, BusLineID, BusStopID, Transit};
 use crate::utils::par_command_buffer::ComponentDrop;
-use crate::vehicles::{unpark, Vehicle, VehicleID, VehicleState};
-use crate::{Egregoria, ParCommandBuffer};
-use geom::{Spline3, Transform, Vec3};
+use crate::utils::time::GameTime;
+use crate::vehicles::{lock, unpark, Vehicle, VehicleID, VehicleState};
+use crate::{Egregoria, ParCommandBuffer, SoulID};
+use geom::{Spline3, Transform, Vec2, Vec3};
 use imgui_inspect_derive::Inspect;
 use legion::storage::Component;
 use legion::world::SubWorld;
 use legion::{system, Entity, EntityStore, IntoQuery, Resources};
-use map_model::{BuildingID, Map, PathKind};
+use map_model::{BuildingID, Map, ParkingSpots, PathKind, Pathfinder};
 use serde::{Deserialize, Serialize};
 
 #[derive(Inspect, Serialize, Deserialize)]
@@ -20,6 +23,9 @@ pub struct Router {
     cur_dest: Option<Destination>,
     vehicle: Option<VehicleID>,
     pub personal_car: Option<VehicleID>,
+    pub personal_bike: Option<VehicleID>,
+    /// The house whose car is the personal car, shared with the rest of the household
+    pub household: Option<BuildingID>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -36,63 +42,76 @@ pub enum RoutingStep {
     DriveTo(VehicleID, Vec3),
     Park(VehicleID, Option<SpotReservation>),
     Unpark(VehicleID),
+    /// Locks the vehicle where it is, without a parking spot
+    Lock(VehicleID),
     GetInVehicle(VehicleID),
     GetOutVehicle(VehicleID),
     GetInBuilding(BuildingID),
     GetOutBuilding(BuildingID),
+    /// Wait at the stop for a bus of the line
+    BoardBus(BusLineID, BusStopID),
+    GetOffBus(BusLineID, BusStopID),
+    /// Cruise around the objective until a parking spot frees up, with the number of tries
+    SearchParking(VehicleID, Vec3, u32),
 }
 
 debug_inspect_impl!(RoutingStep);
 
 register_system!(routing_changed);
 register_system!(routing_update);
+register_system!(parking_search);
+
+/// How often drivers searching for parking look for a free spot again, in seconds
+const PARKING_SEARCH_PERIOD: u32 = 5;
+/// How much farther from the objective each cruising loop goes, in meters
+const CRUISE_RADIUS_STEP: f32 = 40.0;
+const MAX_CRUISE_RADIUS: f32 = 200.0;
+/// Number of cruising loops before giving up on finding a spot near the objective
+const MAX_SEARCH_TRIES: u32 = 10;
 
 #[system(for_each)]
 #[read_component(Transform)]
 #[read_component(Vehicle)]
 #[read_component(Itinerary)]
+#[read_component(Bus)]
 pub fn routing_changed(
     #[resource] map: &Map,
     #[resource] parking: &mut ParkingManagement,
+    #[resource] transit: &Transit,
+    #[resource] binfos: &mut BuildingInfos,
+    body: &Entity,
+    trans: &Transform,
     router: &mut Router,
     loc: &Location,
     subworld: &SubWorld<'_>,
 ) {
-    if router.cur_dest != router.target_dest {
-        let dest = unwrap_ret!(router.target_dest);
-
-        router.clear_steps(parking);
-        match dest {
-            Destination::Outside(pos) => {
-                router.steps = unwrap_ret!(router.steps_to(pos, parking, map, loc, subworld));
-            }
-            Destination::Building(build) => {
-                if let Location::Building(cur_build) = loc {
-                    if *cur_build == build {
-                        router.cur_dest = router.target_dest;
-                        return;
-                    }
-                }
-
-                let door_pos = unwrap_ret!(map.buildings().get(build)).door_pos;
-                router.steps = unwrap_ret!(router.steps_to(door_pos, parking, map, loc, subworld));
-                router.steps.push(RoutingStep::GetInBuilding(build));
-            }
-        }
-
-        router.cur_dest = router.target_dest;
-
-        router.steps.reverse();
+    if router.cur_dest == router.target_dest {
+        return;
     }
+    let soul = SoulID(*body);
+
+    router.clear_steps(parking);
+    let pos = position(map, trans, loc, subworld);
+    let ctx = RoutingCtx {
+        parking,
+        map,
+        transit,
+        subworld,
+        car_free: router.claim_shared_car(binfos, soul, loc),
+    };
+    router.change_route(pos, loc, ctx);
+    router.release_unused_car(binfos, soul, loc);
 }
 
 #[system(par_for_each)]
 #[read_component(Transform)]
 #[read_component(Vehicle)]
 #[read_component(Itinerary)]
+#[read_component(Bus)]
 pub fn routing_update(
     #[resource] map: &Map,
     #[resource] cbuf: &ParCommandBuffer,
+    #[resource] transit: &Transit,
     body: &Entity,
     trans: &Transform,
     itin: &Itinerary,
@@ -105,17 +124,7 @@ pub fn routing_update(
         return;
     }
 
-    let pos = match *loc {
-        Location::Outside => trans.position,
-        Location::Vehicle(id) => comp::<Transform>(subworld, id.0)
-            .map(|x| x.position)
-            .unwrap_or_else(|| trans.position),
-        Location::Building(id) => map
-            .buildings()
-            .get(id)
-            .map(|b| b.door_pos)
-            .unwrap_or_else(|| trans.position),
-    };
+    let pos = position(map, trans, loc, subworld);
 
     let mut cur_step_over = true;
 
@@ -129,10 +138,15 @@ pub fn routing_update(
                 .map(|x| matches!(x.state, VehicleState::Parked(_)))
                 .unwrap_or(true),
             RoutingStep::Unpark(_) => true,
+            RoutingStep::Lock(_) => true,
             RoutingStep::GetInVehicle(_) => true,
             RoutingStep::GetOutVehicle(_) => true,
             RoutingStep::GetInBuilding(_) => true,
             RoutingStep::GetOutBuilding(_) => true,
+            RoutingStep::BoardBus(_, _) => true,
+            RoutingStep::GetOffBus(_, _) => true,
+            // Replaced by a DriveTo when a spot is found, see parking_search
+            RoutingStep::SearchParking(..) => false,
         };
     }
     let mut next_step_ready = true;
@@ -143,6 +157,7 @@ pub fn routing_update(
             RoutingStep::DriveTo(_, _) => true,
             RoutingStep::Park(_, _) => true,
             RoutingStep::Unpark(_) => true,
+            RoutingStep::Lock(_) => true,
             RoutingStep::GetInVehicle(vehicle) => comp::<Transform>(subworld, vehicle.0)
                 .map(|x| x.position.is_close(pos, 3.0))
                 .unwrap_or(true),
@@ -153,6 +168,19 @@ pub fn routing_update(
                 .map(|b| b.door_pos.is_close(pos, 3.0))
                 .unwrap_or(true),
             RoutingStep::GetOutBuilding(_) => true,
+            RoutingStep::BoardBus(line, stop) => {
+                !transit.serves(line, stop) || bus_at(transit, subworld, line, stop).is_some()
+            }
+            RoutingStep::GetOffBus(line, stop) => match *loc {
+                Location::Vehicle(bus) => {
+                    !transit.serves(line, stop)
+                        || comp::<Bus>(subworld, bus.0)
+                            .map(|b| b.is_at(stop))
+                            .unwrap_or(true)
+                }
+                _ => true,
+            },
+            RoutingStep::SearchParking(..) => true,
         };
     }
 
@@ -171,7 +199,10 @@ pub fn routing_update(
                 );
             }
             RoutingStep::DriveTo(vehicle, obj) => {
-                let route = Itinerary::wait_for_reroute(PathKind::Vehicle, obj);
+                let kind = comp::<Vehicle>(subworld, vehicle.0)
+                    .map(|v| v.kind.path_kind())
+                    .unwrap_or(PathKind::Vehicle);
+                let route = Itinerary::wait_for_reroute(kind, obj);
                 cbuf.add_component(vehicle.0, route);
             }
             RoutingStep::Park(vehicle, ref mut spot) => {
@@ -187,6 +218,9 @@ pub fn routing_update(
             RoutingStep::Unpark(vehicle) => {
                 cbuf.exec_ent(vehicle.0, move |goria| unpark(goria, vehicle));
             }
+            RoutingStep::Lock(vehicle) => {
+                cbuf.exec_ent(vehicle.0, move |goria| lock(goria, vehicle));
+            }
             RoutingStep::GetInVehicle(vehicle) => {
                 if subworld.entry_ref(vehicle.0).is_err() {
                     router.reset_dest();
@@ -200,6 +234,16 @@ pub fn routing_update(
                     .map(|vtrans| vtrans.position + vtrans.dir.cross(Vec3::Z) * 2.0)
                     .unwrap_or(pos);
                 walk_outside(*body, pos, cbuf, loc);
+
+                if let Some(house) = router
+                    .household
+                    .filter(|_| router.personal_car == Some(vehicle))
+                {
+                    let soul = SoulID(*body);
+                    cbuf.exec_ent(*body, move |goria| {
+                        goria.write::<BuildingInfos>().release_car(house, soul)
+                    });
+                }
             }
             RoutingStep::GetInBuilding(build) => {
                 if !map.buildings().contains_key(build) {
@@ -217,10 +261,132 @@ pub fn routing_update(
                     .unwrap_or(pos);
                 walk_outside(*body, wpos, cbuf, loc);
             }
+            RoutingStep::BoardBus(line, stop) => {
+                let bus = unwrap_or!(bus_at(transit, subworld, line, stop), {
+                    router.reset_dest();
+                    return;
+                });
+                *loc = Location::Vehicle(bus);
+                walk_inside(*body, cbuf, kin);
+            }
+            RoutingStep::GetOffBus(_, stop) => {
+                let wpos = transit.stops().get(stop).map(|x| x.wait_pos).unwrap_or(pos);
+                walk_outside(*body, wpos, cbuf, loc);
+            }
+            RoutingStep::SearchParking(vehicle, obj, _) => {
+                cbuf.add_component(
+                    vehicle.0,
+                    Itinerary::wait_for_reroute(PathKind::Vehicle, obj),
+                );
+            }
         }
     }
 }
 
+/// Drivers that couldn't find a spot near their objective drive loops around it, widening
+/// at each try, until one frees up.
+/// After `MAX_SEARCH_TRIES` loops they take the closest free spot wherever it is and pay
+/// its fee, or leave the car by the road and walk from there if there is none.
+#[system(for_each)]
+#[read_component(Itinerary)]
+pub fn parking_search(
+    #[resource] map: &Map,
+    #[resource] parking: &mut ParkingManagement,
+    #[resource] cbuf: &ParCommandBuffer,
+    #[resource] time: &GameTime,
+    router: &mut Router,
+    subworld: &SubWorld<'_>,
+) {
+    let (vehicle, obj, tries) = match router.cur_step {
+        Some(RoutingStep::SearchParking(vehicle, obj, ref mut tries)) => (vehicle, obj, tries),
+        _ => return,
+    };
+
+    let loop_ended = comp::<Itinerary>(subworld, vehicle.0)
+        .map(|x| x.has_ended(0.0))
+        .unwrap_or(true);
+
+    if !loop_ended && !time.tick(PARKING_SEARCH_PERIOD) {
+        return;
+    }
+
+    let give_up = loop_ended && *tries >= MAX_SEARCH_TRIES;
+
+    let spot_resa = match parking.reserve_near(obj, map) {
+        Some(x) => Some(x),
+        None if give_up => parking.reserve_closest(obj, map),
+        None => None,
+    };
+
+    if let Some(spot_resa) = spot_resa {
+        let parking_pos = match spot_resa.park_pos(map) {
+            Some(x) => x,
+            None => {
+                parking.free(spot_resa);
+                return;
+            }
+        };
+        cbuf.add_component(
+            vehicle.0,
+            Itinerary::wait_for_reroute(PathKind::Vehicle, parking_pos),
+        );
+        router.cur_step = Some(RoutingStep::DriveTo(vehicle, parking_pos));
+        router
+            .steps
+            .push(RoutingStep::Park(vehicle, Some(spot_resa)));
+        return;
+    }
+
+    if give_up {
+        cbuf.exec_ent(vehicle.0, move |goria| lock(goria, vehicle));
+        router.cur_step = Some(RoutingStep::Lock(vehicle));
+        return;
+    }
+
+    if !loop_ended {
+        return;
+    }
+
+    *tries += 1;
+    let radius = (CRUISE_RADIUS_STEP * *tries as f32).min(MAX_CRUISE_RADIUS);
+    // Golden angle so that successive loops go around the objective
+    let angle = *tries as f32 * 2.4;
+    let target = obj + (Vec2::from_angle(angle) * radius).z0();
+    cbuf.add_component(
+        vehicle.0,
+        Itinerary::wait_for_reroute(PathKind::Vehicle, target),
+    );
+}
+
+/// Where the pedestrian currently is, even when inside
+fn position(map: &Map, trans: &Transform, loc: &Location, subworld: &SubWorld<'_>) -> Vec3 {
+    match *loc {
+        Location::Outside => trans.position,
+        Location::Vehicle(id) => comp::<Transform>(subworld, id.0)
+            .map(|x| x.position)
+            .unwrap_or(trans.position),
+        Location::Building(id) => map
+            .buildings()
+            .get(id)
+            .map(|b| b.door_pos)
+            .unwrap_or(trans.position),
+    }
+}
+
+/// A bus of the line currently waiting at the stop
+fn bus_at(
+    transit: &Transit,
+    subworld: &SubWorld<'_>,
+    line: BusLineID,
+    stop: BusStopID,
+) -> Option<VehicleID> {
+    transit.lines().get(line)?.buses.iter().copied().find(|v| {
+        comp::<Bus>(subworld, v.0)
+            .map(|b| b.is_at(stop))
+            .unwrap_or(false)
+    })
+}
+
 impl ComponentDrop for Router {
     fn drop(&mut self, res: &mut Resources, _: Entity) {
         self.clear_steps(&mut *res.get_mut::<ParkingManagement>().unwrap())
@@ -264,23 +430,93 @@ fn park(vehicle: VehicleID, spot_resa: SpotReservation) -> impl FnOnce(&mut Egre
             from_derivative: trans.dir * 2.0,
             to_derivative: spot.trans.dir * 2.0,
         };
+        let fee = spot_resa.fee(&map, &goria.read::<ParkingManagement>());
         drop(map);
 
+        if fee > Money::ZERO {
+            let day = goria.read::<GameTime>().daytime.day;
+            goria.write::<Government>().money += fee;
+            goria.write::<Ledger>().day_mut(day).parking_fees += fee;
+        }
+
         unwrap_ret!(goria.comp_mut::<Vehicle>(vehicle.0)).state =
             VehicleState::RoadToPark(s, 0.0, spot_resa);
         unwrap_ret!(goria.comp_mut::<Kinematics>(vehicle.0)).velocity = Vec3::ZERO;
     }
 }
 
+struct RoutingCtx<'a, 'w> {
+    parking: &'a mut ParkingManagement,
+    map: &'a Map,
+    transit: &'a Transit,
+    subworld: &'a SubWorld<'w>,
+    /// Whether the car can be used, it is shared by the household
+    car_free: bool,
+}
+
 impl Router {
-    pub fn new(personal_car: Option<VehicleID>) -> Self {
+    pub fn new(personal_car: Option<VehicleID>, personal_bike: Option<VehicleID>) -> Self {
         Self {
             steps: vec![],
             cur_step: None,
             target_dest: None,
             personal_car,
+            personal_bike,
             vehicle: personal_car,
             cur_dest: None,
+            household: None,
+        }
+    }
+
+    /// Makes the personal car the one of the household living in the house
+    pub fn shared_with(mut self, house: BuildingID) -> Self {
+        self.household = Some(house);
+        self
+    }
+
+    fn change_route(&mut self, pos: Vec3, loc: &Location, ctx: RoutingCtx<'_, '_>) -> Option<()> {
+        let map = ctx.map;
+        match self.target_dest? {
+            Destination::Outside(obj) => {
+                self.steps = self.steps_to(obj, pos, loc, ctx)?;
+            }
+            Destination::Building(build) => {
+                if loc != &Location::Building(build) {
+                    let door_pos = map.buildings().get(build)?.door_pos;
+                    self.steps = self.steps_to(door_pos, pos, loc, ctx)?;
+                    self.steps.push(RoutingStep::GetInBuilding(build));
+                }
+            }
+        }
+
+        self.cur_dest = self.target_dest;
+
+        self.steps.reverse();
+        Some(())
+    }
+
+    /// Only one member of the household can use its car at a time
+    fn claim_shared_car(&self, binfos: &mut BuildingInfos, soul: SoulID, loc: &Location) -> bool {
+        let house = unwrap_ret!(self.household, true);
+        let car = unwrap_ret!(self.personal_car, true);
+        if self.vehicle != Some(car) || *loc == Location::Vehicle(car) {
+            return true;
+        }
+        binfos.release_car(house, soul);
+        binfos.claim_car(house, soul)
+    }
+
+    /// Lets the rest of the household use the car when the new route doesn't need it
+    fn release_unused_car(&self, binfos: &mut BuildingInfos, soul: SoulID, loc: &Location) {
+        let house = unwrap_ret!(self.household);
+        let car = unwrap_ret!(self.personal_car);
+        let uses_car = *loc == Location::Vehicle(car)
+            || self
+                .steps
+                .iter()
+                .any(|s| matches!(*s, RoutingStep::GetInVehicle(v) if v == car));
+        if !uses_car {
+            binfos.release_car(house, soul);
         }
     }
 
@@ -296,6 +532,22 @@ impl Router {
         }
     }
 
+    /// Drops the plan if it parks on a spot that no longer exists, so that a new one is made
+    pub(crate) fn forget_missing_spots(
+        &mut self,
+        parking: &mut ParkingManagement,
+        spots: &ParkingSpots,
+    ) {
+        let missing =
+            self.steps.iter().chain(self.cur_step.iter()).any(
+                |step| matches!(step, RoutingStep::Park(_, Some(spot)) if !spot.exists(spots)),
+            );
+        if missing {
+            self.clear_steps(parking);
+            self.reset_dest();
+        }
+    }
+
     pub fn reset_dest(&mut self) {
         self.cur_dest = None;
     }
@@ -314,44 +566,136 @@ impl Router {
     fn steps_to(
         &mut self,
         obj: Vec3,
-        parking: &mut ParkingManagement,
-        map: &Map,
+        mut pos: Vec3,
         loc: &Location,
-        subworld: &SubWorld<'_>,
+        ctx: RoutingCtx<'_, '_>,
     ) -> Option<Vec<RoutingStep>> {
+        let RoutingCtx {
+            parking,
+            map,
+            transit,
+            subworld,
+            car_free,
+        } = ctx;
+        let vehicle = self.vehicle.filter(|_| car_free);
         let mut steps = vec![];
-        if let Location::Building(cur_build) = loc {
-            steps.push(RoutingStep::GetOutBuilding(*cur_build));
+        let mut riding = None;
+        match *loc {
+            Location::Building(cur_build) => steps.push(RoutingStep::GetOutBuilding(cur_build)),
+            Location::Vehicle(v) => {
+                if let Some(bus) = comp::<Bus>(subworld, v.0) {
+                    let (stop_id, stop) = transit.next_stop(bus)?;
+                    steps.push(RoutingStep::GetOffBus(bus.line, stop_id));
+                    pos = stop.wait_pos;
+                } else {
+                    riding = Some(v);
+                }
+            }
+            Location::Outside => {}
         }
 
-        if let Some(car) = self.vehicle {
-            let spot_resa = parking.reserve_near(obj, map)?;
-            let parking_pos = match spot_resa.park_pos(map) {
-                Some(x) => x,
-                None => {
-                    parking.free(spot_resa);
-                    return None;
+        // The bike is only an option when not asked to use another vehicle, like a work truck
+        let bike = self
+            .personal_bike
+            .filter(|_| self.vehicle.is_none() || self.vehicle == self.personal_car);
+
+        if riding.is_some() && riding == bike {
+            return Self::ride_bike(riding?, None, obj, steps, map);
+        }
+
+        if riding.is_none() {
+            let mut other = match vehicle.and_then(|car| comp::<Transform>(subworld, car.0)) {
+                Some(car) => drive_time(pos, car.position, obj),
+                None => walk_time(pos, obj),
+            };
+
+            let mut use_bike = None;
+            if let Some(bike_trans) = bike.and_then(|b| comp::<Transform>(subworld, b.0)) {
+                let t = bike_time(pos, bike_trans.position, obj);
+                if t < other {
+                    other = t;
+                    use_bike = Some(bike_trans.position);
                 }
+            }
+
+            if let Some(ride) = transit.best_ride(pos, obj) {
+                let from = transit.stops().get(ride.from)?;
+                if ride.time < other {
+                    steps.push(RoutingStep::WalkTo(from.wait_pos));
+                    steps.push(RoutingStep::BoardBus(ride.line, ride.from));
+                    steps.push(RoutingStep::GetOffBus(ride.line, ride.to));
+                    steps.push(RoutingStep::WalkTo(obj));
+                    return Some(steps);
+                }
+            }
+
+            if use_bike.is_some() {
+                return Self::ride_bike(bike?, use_bike, obj, steps, map);
+            }
+        }
+
+        if let Some(car) = vehicle {
+            let spot = match parking.reserve_near(obj, map) {
+                Some(spot_resa) => match spot_resa.park_pos(map) {
+                    Some(parking_pos) => Some((spot_resa, parking_pos)),
+                    None => {
+                        parking.free(spot_resa);
+                        return None;
+                    }
+                },
+                None => None,
             };
 
-            if !matches!(loc, Location::Vehicle(_)) {
+            if riding.is_none() {
                 if let Some(trans) = comp::<Transform>(subworld, car.0) {
                     steps.push(RoutingStep::WalkTo(trans.position));
                     steps.push(RoutingStep::GetInVehicle(car));
                     steps.push(RoutingStep::Unpark(car));
                 } else {
-                    parking.free(spot_resa);
+                    if let Some((spot_resa, _)) = spot {
+                        parking.free(spot_resa);
+                    }
                     self.vehicle = None;
                     return None;
                 }
             }
 
-            steps.push(RoutingStep::DriveTo(car, parking_pos));
-            steps.push(RoutingStep::Park(car, Some(spot_resa)));
+            match spot {
+                Some((spot_resa, parking_pos)) => {
+                    steps.push(RoutingStep::DriveTo(car, parking_pos));
+                    steps.push(RoutingStep::Park(car, Some(spot_resa)));
+                }
+                // Nothing free near the objective, search for a spot once there
+                None => steps.push(RoutingStep::SearchParking(car, obj, 0)),
+            }
             steps.push(RoutingStep::GetOutVehicle(car));
         }
 
         steps.push(RoutingStep::WalkTo(obj));
         Some(steps)
     }
+
+    /// Walks to the bike if its position is given, rides it to the bike-accessible lane
+    /// nearest to the objective, locks it there and walks the rest
+    fn ride_bike(
+        bike: VehicleID,
+        bike_pos: Option<Vec3>,
+        obj: Vec3,
+        mut steps: Vec<RoutingStep>,
+        map: &Map,
+    ) -> Option<Vec<RoutingStep>> {
+        let lane = PathKind::Bike.nearest_lane(map, obj)?;
+        let lock_pos = map.lanes().get(lane)?.points.project(obj);
+
+        if let Some(bike_pos) = bike_pos {
+            steps.push(RoutingStep::WalkTo(bike_pos));
+            steps.push(RoutingStep::GetInVehicle(bike));
+            steps.push(RoutingStep::Unpark(bike));
+        }
+        steps.push(RoutingStep::DriveTo(bike, lock_pos));
+        steps.push(RoutingStep::Lock(bike));
+        steps.push(RoutingStep::GetOutVehicle(bike));
+        steps.push(RoutingStep::WalkTo(obj));
+        Some(steps)
+    }
 }
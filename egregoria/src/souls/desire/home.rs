@@ -14,6 +14,10 @@ impl Home {
         Home { house }
     }
 
+    pub fn house(&self) -> BuildingID {
+        self.house
+    }
+
     pub fn apply(&mut self) -> HumanDecisionKind {
         HumanDecisionKind::GoTo(Destination::Building(self.house))
     }
@@ -1,7 +1,9 @@
 mod buyfood;
 mod home;
+mod shop;
 mod work;
 
 pub use buyfood::*;
 pub use home::*;
+pub use shop::*;
 pub use work::*;
@@ -0,0 +1,149 @@
+use crate::economy::{Bought, CommodityKind, Market};
+use crate::map_dynamic::{BuildingInfos, Destination};
+use crate::pedestrians::Location;
+use crate::souls::human::HumanDecisionKind;
+use crate::utils::time::{GameInstant, GameTime};
+use crate::{my_hash, ParCommandBuffer, SoulID};
+use geom::{Transform, Vec2};
+use imgui_inspect_derive::Inspect;
+use map_model::BuildingID;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum ShoppingState {
+    Empty,
+    WaitingForTrade(CommodityKind),
+    BoughtAt(BuildingID),
+}
+
+debug_inspect_impl!(ShoppingState);
+
+/// Buys one unit of something from a store, then goes to the store to get it
+#[derive(Inspect, Clone, Serialize, Deserialize, Debug)]
+pub struct Shopping {
+    state: ShoppingState,
+}
+
+impl Shopping {
+    pub fn new() -> Self {
+        Self {
+            state: ShoppingState::Empty,
+        }
+    }
+
+    /// Overrides the score of the desire when nothing can be done
+    /// until the trade is made, or when already at the store.
+    pub fn score(&self, loc: &Location, bought: &Bought) -> Option<f32> {
+        match self.state {
+            ShoppingState::WaitingForTrade(kind)
+                if bought.0.get(&kind).map(Vec::is_empty).unwrap_or(false) =>
+            {
+                Some(0.0)
+            }
+            ShoppingState::BoughtAt(id) if loc == &Location::Building(id) => Some(1.0),
+            _ => None,
+        }
+    }
+
+    /// Returns the decision to take and whether the goods were just picked up
+    pub fn apply(
+        &mut self,
+        cbuf: &ParCommandBuffer,
+        binfos: &BuildingInfos,
+        soul: SoulID,
+        pos: Vec2,
+        kind: CommodityKind,
+        loc: &Location,
+        bought: &mut Bought,
+    ) -> (HumanDecisionKind, bool) {
+        use HumanDecisionKind::*;
+        match self.state {
+            ShoppingState::Empty => {
+                cbuf.exec_on(soul.0, move |market: &mut Market| {
+                    market.buy_retail(soul, pos, kind, 1)
+                });
+                self.state = ShoppingState::WaitingForTrade(kind);
+                (Yield, false)
+            }
+            ShoppingState::WaitingForTrade(kind) => {
+                for trade in bought.0.entry(kind).or_default().drain(..) {
+                    if let Some(b) = binfos.building_owned_by(trade.seller) {
+                        self.state = ShoppingState::BoughtAt(b);
+                    }
+                }
+                (Yield, false)
+            }
+            ShoppingState::BoughtAt(b) => {
+                if loc == &Location::Building(b) {
+                    self.state = ShoppingState::Empty;
+                    (Yield, true)
+                } else {
+                    (GoTo(Destination::Building(b)), false)
+                }
+            }
+        }
+    }
+}
+
+impl Default for Shopping {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Picks one of the goods, changing every day and from soul to soul
+pub fn pick_goods(goods: &[CommodityKind], soul: SoulID, time: &GameTime) -> CommodityKind {
+    goods[(my_hash((soul, time.daytime.day)) % goods.len() as u64) as usize]
+}
+
+/// Time between two shopping trips, in seconds
+const SHOPPING_PERIOD: i32 = 3 * GameTime::DAY;
+const SHOPPING_GOODS: &[CommodityKind] = &[
+    CommodityKind::Cloth,
+    CommodityKind::Furniture,
+    CommodityKind::HighTechProduct,
+    CommodityKind::Flower,
+];
+
+#[derive(Inspect, Clone, Serialize, Deserialize, Debug)]
+pub struct Shop {
+    last_shopped: GameInstant,
+    shopping: Shopping,
+}
+
+impl Shop {
+    pub fn new(start: GameInstant) -> Self {
+        Shop {
+            last_shopped: start,
+            shopping: Shopping::new(),
+        }
+    }
+
+    pub fn score(&self, time: &GameTime, loc: &Location, bought: &Bought) -> f32 {
+        if let Some(score) = self.shopping.score(loc, bought) {
+            return score;
+        }
+        self.last_shopped.elapsed(time) as f32 / SHOPPING_PERIOD as f32 - 1.0
+    }
+
+    pub fn apply(
+        &mut self,
+        cbuf: &ParCommandBuffer,
+        binfos: &BuildingInfos,
+        time: &GameTime,
+        soul: SoulID,
+        trans: &Transform,
+        loc: &Location,
+        bought: &mut Bought,
+    ) -> HumanDecisionKind {
+        let kind = pick_goods(SHOPPING_GOODS, soul, time);
+        let (decision, done) =
+            self.shopping
+                .apply(cbuf, binfos, soul, trans.position.xy(), kind, loc, bought);
+        if done {
+            self.last_shopped = time.instant();
+            log::info!("{:?} went shopping at {:?}", soul, loc);
+        }
+        decision
+    }
+}
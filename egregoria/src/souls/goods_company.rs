@@ -1,18 +1,22 @@
 use super::desire::Work;
 use crate::economy::{CommodityKind, Market, Sold, Workers};
 use crate::engine_interaction::Selectable;
-use crate::map_dynamic::BuildingInfos;
+use crate::map_dynamic::{BuildingInfos, ElectricityFlow};
+use crate::pedestrians::Location;
 use crate::souls::desire::WorkKind;
+use crate::souls::population::lose_job;
 use crate::utils::time::GameTime;
 use crate::vehicles::VehicleID;
 use crate::{my_hash, Egregoria, ParCommandBuffer, SoulID};
+use common::saveload::{Encoder, JSON};
 use geom::{Transform, Vec2};
 use imgui_inspect_derive::Inspect;
 use legion::world::SubWorld;
 use legion::{system, Entity, EntityStore};
-use map_model::{BuildingGen, BuildingID, BuildingKind, Map};
+use map_model::{BuildingGen, BuildingID, BuildingKind, LotKind, Map};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::path::Path;
 
 #[derive(Clone, Serialize, Deserialize, Inspect)]
 pub struct Recipe {
@@ -28,15 +32,37 @@ pub struct Recipe {
     pub storage_multiplier: i32,
 }
 
+#[derive(Clone, Serialize)]
 pub struct GoodsCompanyDescription {
-    pub name: &'static str,
+    pub name: String,
     pub bkind: BuildingKind,
     pub bgen: BuildingGen,
     pub kind: CompanyKind,
     pub recipe: Recipe,
+    /// Goods bought from producers and sold as is to consumers, with the quantity kept in store
+    pub stock: Vec<(CommodityKind, i32)>,
     pub n_workers: i32,
+    /// Power needed to operate at full speed, in kW
+    pub power_consumption: i32,
     pub size: f32,
-    pub asset_location: &'static str,
+    pub asset_location: String,
+}
+
+impl GoodsCompanyDescription {
+    /// The zone where the company grows by itself, network companies are always placed by hand
+    pub fn zone(&self) -> Option<LotKind> {
+        match self.kind {
+            CompanyKind::Store => Some(LotKind::Commercial),
+            CompanyKind::Factory { .. } => Some(LotKind::Industrial),
+            CompanyKind::Network => None,
+        }
+    }
+
+    /// Whether the company produces or sells the commodity
+    pub fn provides(&self, kind: CommodityKind) -> bool {
+        self.recipe.production.iter().any(|&(k, _)| k == kind)
+            || self.stock.iter().any(|&(k, _)| k == kind)
+    }
 }
 
 register_resource_noserialize!(GoodsCompanyRegistry);
@@ -46,10 +72,130 @@ pub struct GoodsCompanyRegistry {
 
 impl Default for GoodsCompanyRegistry {
     fn default() -> Self {
+        Self::load(COMPANIES_PATH).unwrap_or_else(Self::hardcoded)
+    }
+}
+
+/// How often stores fill their stock back up, in seconds
+const STOCK_PERIOD: u32 = GameTime::HOUR as u32;
+
+/// File from which modders and designers can change the production chains
+const COMPANIES_PATH: &str = "assets/companies.json";
+
+/// Same as `Recipe`, with the commodities as names so they can be validated
+#[derive(Deserialize)]
+struct RecipeFile {
+    consumption: Vec<(String, i32)>,
+    production: Vec<(String, i32)>,
+    complexity: i32,
+    storage_multiplier: i32,
+}
+
+/// Same as `GoodsCompanyDescription`, with the commodities as names so they can be validated
+#[derive(Deserialize)]
+struct GoodsCompanyDescriptionFile {
+    name: String,
+    bkind: BuildingKind,
+    bgen: BuildingGen,
+    kind: CompanyKind,
+    recipe: RecipeFile,
+    #[serde(default)]
+    stock: Vec<(String, i32)>,
+    n_workers: i32,
+    #[serde(default)]
+    power_consumption: i32,
+    size: f32,
+    asset_location: String,
+}
+
+impl GoodsCompanyRegistry {
+    /// Loads the registry from a json file, returns None and logs the reasons if the file is invalid
+    pub fn load(path: &str) -> Option<Self> {
+        let data = std::fs::read(path)
+            .map_err(|e| log::warn!("couldn't read {}, using default companies: {}", path, e))
+            .ok()?;
+        Self::parse(&data)
+            .map_err(|errors| {
+                for e in errors {
+                    log::error!("invalid {}: {}", path, e);
+                }
+                log::error!("using default companies instead of {}", path);
+            })
+            .ok()
+    }
+
+    /// Parses and validates a json list of company descriptions, returning every error found
+    pub fn parse(data: &[u8]) -> Result<Self, Vec<String>> {
+        Self::parse_in(data, Path::new("."))
+    }
+
+    /// Same as [`Self::parse`], with the asset locations relative to `root`
+    fn parse_in(data: &[u8], root: &Path) -> Result<Self, Vec<String>> {
+        let file: Vec<GoodsCompanyDescriptionFile> =
+            JSON::decode(data).map_err(|e| vec![e.to_string()])?;
+
+        let mut errors = vec![];
+        let mut descriptions = BTreeMap::new();
+
+        for d in file {
+            let name = &d.name;
+            let mut commodities = |v: Vec<(String, i32)>| {
+                v.into_iter()
+                    .filter_map(|(kind, qty)| match CommodityKind::from_name(&kind) {
+                        Some(kind) => Some((kind, qty)),
+                        None => {
+                            errors.push(format!("{}: unknown commodity {}", name, kind));
+                            None
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            };
+
+            let recipe = Recipe {
+                consumption: commodities(d.recipe.consumption),
+                production: commodities(d.recipe.production),
+                complexity: d.recipe.complexity,
+                storage_multiplier: d.recipe.storage_multiplier,
+            };
+            let stock = commodities(d.stock);
+
+            if !root.join(&d.asset_location).exists() {
+                errors.push(format!("{}: missing asset {}", d.name, d.asset_location));
+            }
+
+            if descriptions.contains_key(&d.bkind) {
+                errors.push(format!("{}: {:?} is used twice", d.name, d.bkind));
+            }
+
+            descriptions.insert(
+                d.bkind,
+                GoodsCompanyDescription {
+                    name: d.name,
+                    bkind: d.bkind,
+                    bgen: d.bgen,
+                    kind: d.kind,
+                    recipe,
+                    stock,
+                    n_workers: d.n_workers,
+                    power_consumption: d.power_consumption,
+                    size: d.size,
+                    asset_location: d.asset_location,
+                },
+            );
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Self { descriptions })
+    }
+
+    /// Used when the companies file is missing or invalid
+    pub fn hardcoded() -> Self {
         Self {
             descriptions: vec![
                 GoodsCompanyDescription {
-                    name: "Coal power plant", // doesn't do anything yet, was really to test my modeling skills..
+                    name: "Coal power plant".to_string(),
                     bkind: BuildingKind::GoodsCompany(25), // should've probably done an existing useful building instead..
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -61,12 +207,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 165.0,
-                    asset_location: "assets/coal_power_plant.glb",
+                    asset_location: "assets/coal_power_plant.glb".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Useless warehouse",
+                    name: "Useless warehouse".to_string(),
                     bkind: BuildingKind::GoodsCompany(24),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -78,54 +226,52 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 1000,
                         storage_multiplier: 0,
                     },
+                    stock: vec![],
                     n_workers: 100,
+                    power_consumption: 0,
                     size: 100.0,
-                    asset_location: "assets/warehouse.png",
+                    asset_location: "assets/warehouse.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Supermarket",
+                    name: "Supermarket".to_string(),
                     bkind: BuildingKind::GoodsCompany(23),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
                     },
                     kind: CompanyKind::Store,
                     recipe: Recipe {
-                        consumption: vec![
-                            (CommodityKind::Meat, 1),
-                            (CommodityKind::Vegetable, 1),
-                            (CommodityKind::Cereal, 1),
-                        ], // TODO: actually implement stores
-                        production: vec![
-                            (CommodityKind::Meat, 1),
-                            (CommodityKind::Vegetable, 1),
-                            (CommodityKind::Cereal, 1),
-                        ],
+                        consumption: vec![],
+                        production: vec![],
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![(CommodityKind::Meat, 3), (CommodityKind::Vegetable, 3)],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 80.0,
-                    asset_location: "assets/supermarket.png",
+                    asset_location: "assets/supermarket.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Clothes store",
+                    name: "Clothes store".to_string(),
                     bkind: BuildingKind::GoodsCompany(22),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
                     },
                     kind: CompanyKind::Store,
                     recipe: Recipe {
-                        consumption: vec![(CommodityKind::Cloth, 1)], // TODO: actually implement stores
-                        production: vec![(CommodityKind::Cloth, 1)],
+                        consumption: vec![],
+                        production: vec![],
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![(CommodityKind::Cloth, 3)],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 10.0,
-                    asset_location: "assets/clothes_store.png",
+                    asset_location: "assets/clothes_store.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Cloth factory",
+                    name: "Cloth factory".to_string(),
                     bkind: BuildingKind::GoodsCompany(21),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -137,12 +283,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 300,
                     size: 80.0,
-                    asset_location: "assets/cloth_factory.png",
+                    asset_location: "assets/cloth_factory.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Polyester refinery",
+                    name: "Polyester refinery".to_string(),
                     bkind: BuildingKind::GoodsCompany(20),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -154,12 +302,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 5,
+                    power_consumption: 400,
                     size: 80.0,
-                    asset_location: "assets/polyester_refinery.png",
+                    asset_location: "assets/polyester_refinery.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Oil pump",
+                    name: "Oil pump".to_string(),
                     bkind: BuildingKind::GoodsCompany(19),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -171,12 +321,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 5,
+                    power_consumption: 0,
                     size: 20.0,
-                    asset_location: "assets/oil_pump.png",
+                    asset_location: "assets/oil_pump.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Textile processing facility",
+                    name: "Textile processing facility".to_string(),
                     bkind: BuildingKind::GoodsCompany(18),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -188,12 +340,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 300,
                     size: 80.0,
-                    asset_location: "assets/textile_processing_facility.png",
+                    asset_location: "assets/textile_processing_facility.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Wool farm",
+                    name: "Wool farm".to_string(),
                     bkind: BuildingKind::GoodsCompany(17),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -205,29 +359,33 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 80.0,
-                    asset_location: "assets/wool_farm.png",
+                    asset_location: "assets/wool_farm.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Florist",
+                    name: "Florist".to_string(),
                     bkind: BuildingKind::GoodsCompany(16),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
                     },
                     kind: CompanyKind::Store,
                     recipe: Recipe {
-                        consumption: vec![(CommodityKind::Flower, 1)], // TODO: actually implement stores
-                        production: vec![(CommodityKind::Flower, 1)],
+                        consumption: vec![],
+                        production: vec![],
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![(CommodityKind::Flower, 3)],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 10.0,
-                    asset_location: "assets/florist.png",
+                    asset_location: "assets/florist.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Horticulturalist",
+                    name: "Horticulturalist".to_string(),
                     bkind: BuildingKind::GoodsCompany(15),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -239,29 +397,33 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 5,
+                    power_consumption: 0,
                     size: 80.0,
-                    asset_location: "assets/horticulturalist.png",
+                    asset_location: "assets/horticulturalist.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "High tech store",
+                    name: "High tech store".to_string(),
                     bkind: BuildingKind::GoodsCompany(14),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
                     },
                     kind: CompanyKind::Store,
                     recipe: Recipe {
-                        consumption: vec![(CommodityKind::HighTechProduct, 1)], // TODO: actually implement stores
-                        production: vec![(CommodityKind::HighTechProduct, 1)],
+                        consumption: vec![],
+                        production: vec![],
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![(CommodityKind::HighTechProduct, 3)],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 80.0,
-                    asset_location: "assets/hightech_store.png",
+                    asset_location: "assets/hightech_store.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "High tech facility",
+                    name: "High tech facility".to_string(),
                     bkind: BuildingKind::GoodsCompany(13),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -273,12 +435,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 400,
                     size: 80.0,
-                    asset_location: "assets/hightech_facility.png",
+                    asset_location: "assets/hightech_facility.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Rare metal mine",
+                    name: "Rare metal mine".to_string(),
                     bkind: BuildingKind::GoodsCompany(12),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -290,12 +454,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 80.0,
-                    asset_location: "assets/rare_metal_mine.png",
+                    asset_location: "assets/rare_metal_mine.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Furniture store",
+                    name: "Furniture store".to_string(),
                     bkind: BuildingKind::GoodsCompany(11),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -307,12 +473,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 80.0,
-                    asset_location: "assets/furniture_store.png",
+                    asset_location: "assets/furniture_store.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Foundry",
+                    name: "Foundry".to_string(),
                     bkind: BuildingKind::GoodsCompany(10),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -324,12 +492,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 500,
                     size: 80.0,
-                    asset_location: "assets/foundry.png",
+                    asset_location: "assets/foundry.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Iron mine",
+                    name: "Iron mine".to_string(),
                     bkind: BuildingKind::GoodsCompany(9),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -341,12 +511,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 80.0,
-                    asset_location: "assets/iron_mine.png",
+                    asset_location: "assets/iron_mine.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Woodmill",
+                    name: "Woodmill".to_string(),
                     bkind: BuildingKind::GoodsCompany(8),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -358,12 +530,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 300,
                     size: 80.0,
-                    asset_location: "assets/woodmill.png",
+                    asset_location: "assets/woodmill.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Lumber yard",
+                    name: "Lumber yard".to_string(),
                     bkind: BuildingKind::GoodsCompany(7),
                     bgen: BuildingGen::Farm,
                     kind: CompanyKind::Factory { n_trucks: 1 },
@@ -373,12 +547,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 200.0,
-                    asset_location: "assets/lumber_yard.png",
+                    asset_location: "assets/lumber_yard.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Meat facility",
+                    name: "Meat facility".to_string(),
                     bkind: BuildingKind::GoodsCompany(6),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 0.6,
@@ -390,12 +566,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 300,
                     size: 80.0,
-                    asset_location: "assets/meat_facility.png",
+                    asset_location: "assets/meat_facility.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Slaughterhouse",
+                    name: "Slaughterhouse".to_string(),
                     bkind: BuildingKind::GoodsCompany(5),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -407,12 +585,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 5,
+                    power_consumption: 200,
                     size: 50.0,
-                    asset_location: "assets/slaughterhouse.png",
+                    asset_location: "assets/slaughterhouse.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Animal Farm",
+                    name: "Animal Farm".to_string(),
                     bkind: BuildingKind::GoodsCompany(4),
                     bgen: BuildingGen::Farm,
                     kind: CompanyKind::Factory { n_trucks: 1 },
@@ -422,12 +602,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 5,
+                    power_consumption: 0,
                     size: 80.0,
-                    asset_location: "assets/animal_farm.png",
+                    asset_location: "assets/animal_farm.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Vegetable Farm",
+                    name: "Vegetable Farm".to_string(),
                     bkind: BuildingKind::GoodsCompany(3),
                     bgen: BuildingGen::Farm,
                     kind: CompanyKind::Factory { n_trucks: 1 },
@@ -437,12 +619,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 70.0,
-                    asset_location: "assets/vegetable_farm.png",
+                    asset_location: "assets/vegetable_farm.png".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Bakery",
+                    name: "Bakery".to_string(),
                     bkind: BuildingKind::GoodsCompany(2),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 1.0,
@@ -454,12 +638,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 100,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 3,
+                    power_consumption: 0,
                     size: 10.0,
-                    asset_location: "assets/bakery.glb",
+                    asset_location: "assets/bakery.glb".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Cereal Factory",
+                    name: "Cereal Factory".to_string(),
                     bkind: BuildingKind::GoodsCompany(1),
                     bgen: BuildingGen::CenteredDoor {
                         vertical_factor: 0.6,
@@ -471,12 +657,14 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 200,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 300,
                     size: 80.0,
-                    asset_location: "assets/flour_factory.glb",
+                    asset_location: "assets/flour_factory.glb".to_string(),
                 },
                 GoodsCompanyDescription {
-                    name: "Cereal Farm",
+                    name: "Cereal Farm".to_string(),
                     bkind: BuildingKind::GoodsCompany(0),
                     bgen: BuildingGen::Farm,
                     kind: CompanyKind::Factory { n_trucks: 1 },
@@ -486,9 +674,11 @@ impl Default for GoodsCompanyRegistry {
                         complexity: 200,
                         storage_multiplier: 5,
                     },
+                    stock: vec![],
                     n_workers: 10,
+                    power_consumption: 0,
                     size: 120.0,
-                    asset_location: "assets/cereal_farm.png",
+                    asset_location: "assets/cereal_farm.png".to_string(),
                 },
             ]
             .into_iter()
@@ -520,14 +710,18 @@ impl Recipe {
             })
     }
 
-    pub fn act(&self, soul: SoulID, near: Vec2, market: &mut Market) {
+    pub fn act(&self, soul: SoulID, near: Vec2, market: &mut Market, retail: bool) {
         for &(kind, qty) in &self.consumption {
             market.produce(soul, kind, -qty);
             market.buy_until(soul, near, kind, qty);
         }
         for &(kind, qty) in &self.production {
             market.produce(soul, kind, qty);
-            market.sell_all(soul, near, kind);
+            if retail {
+                market.sell_all_retail(soul, near, kind);
+            } else {
+                market.sell_all(soul, near, kind);
+            }
         }
     }
 }
@@ -548,14 +742,34 @@ debug_inspect_impl!(CompanyKind);
 pub struct GoodsCompany {
     pub kind: CompanyKind,
     pub recipe: Recipe,
+    pub stock: Vec<(CommodityKind, i32)>,
     pub building: BuildingID,
     pub max_workers: i32,
+    /// Power needed to operate at full speed, in kW
+    pub power_consumption: i32,
+    /// Power sent to the grid, in kW. Only network companies producing electricity have some
+    pub power_output: i32,
     /// In [0; 1] range, to show how much has been made until new product
     pub progress: f32,
     pub driver: Option<SoulID>,
     pub trucks: Vec<VehicleID>,
 }
 
+impl GoodsCompany {
+    /// Power sent to the grid when fully staffed, in kW
+    pub fn power_capacity(&self) -> i32 {
+        if !matches!(self.kind, CompanyKind::Network) {
+            return 0;
+        }
+        self.recipe
+            .production
+            .iter()
+            .filter(|&&(kind, _)| kind == CommodityKind::Electricity)
+            .map(|&(_, qty)| qty)
+            .sum()
+    }
+}
+
 pub fn company_soul(goria: &mut Egregoria, company: GoodsCompany) -> Option<SoulID> {
     let map = goria.map();
     let b = &map.buildings().get(company.building)?;
@@ -574,6 +788,7 @@ pub fn company_soul(goria: &mut Egregoria, company: GoodsCompany) -> Option<Soul
         m.sell_all(soul, door_pos.xy(), CommodityKind::JobOpening);
 
         company.recipe.init(soul, door_pos.xy(), m);
+        restock(&company.stock, soul, door_pos.xy(), m);
     }
 
     goria
@@ -594,6 +809,41 @@ pub fn company_soul(goria: &mut Egregoria, company: GoodsCompany) -> Option<Soul
     Some(soul)
 }
 
+/// Closes the company as its building is gone: the workers look for another job and the
+/// trucks nobody is driving are scrapped
+pub(crate) fn close_company(goria: &mut Egregoria, soul: SoulID) {
+    let company = unwrap_ret!(goria.comp::<GoodsCompany>(soul.0));
+    let trucks = company.trucks.clone();
+    let driver = company.driver;
+    let workers = goria
+        .comp::<Workers>(soul.0)
+        .map(|w| w.0.clone())
+        .unwrap_or_default();
+
+    for worker in workers {
+        lose_job(goria, worker);
+    }
+    goria.write::<Market>().remove(soul);
+
+    let cbuf = goria.read::<ParCommandBuffer>();
+    for truck in trucks {
+        let driven =
+            driver.and_then(|d| goria.comp::<Location>(d.0)) == Some(&Location::Vehicle(truck));
+        if !driven {
+            cbuf.kill(truck.0);
+        }
+    }
+    cbuf.kill(soul.0);
+}
+
+/// Buys from producers what's missing from the stock and puts everything up for sale
+fn restock(stock: &[(CommodityKind, i32)], soul: SoulID, near: Vec2, market: &mut Market) {
+    for &(kind, qty) in stock {
+        market.buy_until(soul, near, kind, qty);
+        market.sell_all_retail(soul, near, kind);
+    }
+}
+
 register_system!(company);
 #[system(par_for_each)]
 #[read_component(Work)]
@@ -603,6 +853,7 @@ pub fn company(
     #[resource] binfos: &BuildingInfos,
     #[resource] market: &Market,
     #[resource] map: &Map,
+    #[resource] electricity: &ElectricityFlow,
     me: &Entity,
     company: &mut GoodsCompany,
     sold: &mut Sold,
@@ -612,34 +863,62 @@ pub fn company(
     let n_workers = workers.0.len();
     let soul = SoulID(*me);
 
-    if company.recipe.should_produce(soul, market) {
-        company.progress += n_workers as f32
+    let power = if company.power_consumption > 0 {
+        electricity.satisfaction(company.building)
+    } else {
+        1.0
+    };
+
+    let producing = company.recipe.should_produce(soul, market);
+    if producing {
+        company.progress += power * n_workers as f32
             / (company.recipe.complexity as f32 * company.max_workers as f32)
             * time.delta;
     }
 
+    company.power_output = if producing {
+        company.power_capacity() * n_workers as i32 / company.max_workers.max(1)
+    } else {
+        0
+    };
+
     if company.progress >= 1.0 {
         company.progress = 0.0;
-        let recipe = company.recipe.clone();
+        let mut recipe = company.recipe.clone();
+        // electricity goes through the power lines instead of the market
+        recipe
+            .production
+            .retain(|&(kind, _)| kind != CommodityKind::Electricity);
         let bpos = unwrap_or!(map.buildings().get(company.building), {
             cbuf.kill(*me);
             return;
         })
         .door_pos;
 
+        let retail = matches!(company.kind, CompanyKind::Store);
         cbuf.exec_on(soul.0, move |market| {
-            recipe.act(soul, bpos.xy(), market);
+            recipe.act(soul, bpos.xy(), market, retail);
         });
         return;
     }
 
+    if !company.stock.is_empty() && time.tick(STOCK_PERIOD) {
+        let stock = company.stock.clone();
+        let bpos = unwrap_ret!(map.buildings().get(company.building)).door_pos;
+        cbuf.exec_on(soul.0, move |market| {
+            restock(&stock, soul, bpos.xy(), market)
+        });
+    }
+
     if_chain::if_chain! {
         if let Some(trade) = sold.0.drain(..1.min(sold.0.len())).next();
         if let Some(driver) = company.driver;
         if let Ok(ent) = sw.entry_ref(driver.0);
         if let Ok(w) = ent.get_component::<Work>();
         if matches!(w.kind, WorkKind::Driver { deliver_order: None, .. });
-        if let Some(owner_build) = binfos.building_owned_by(trade.buyer);
+        if let Some(owner_build) = binfos
+            .building_owned_by(trade.buyer)
+            .or_else(|| binfos.home_of(trade.buyer));
         then {
             log::info!("asked driver to deliver");
 
@@ -678,3 +957,48 @@ pub fn company(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::GoodsCompanyRegistry;
+    use common::saveload::{Encoder, JSON};
+    use std::path::Path;
+
+    #[test]
+    fn test_hardcoded_matches_file() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let data = std::fs::read(root.join(super::COMPANIES_PATH)).unwrap();
+        let file = GoodsCompanyRegistry::parse_in(&data, &root).unwrap();
+
+        let descriptions =
+            |r: &GoodsCompanyRegistry| JSON::encode(&r.descriptions.values().collect::<Vec<_>>());
+        assert_eq!(
+            descriptions(&file).unwrap(),
+            descriptions(&GoodsCompanyRegistry::hardcoded()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_registry_validation() {
+        let data = br#"[{
+            "name": "Gold mine",
+            "bkind": { "GoodsCompany": 0 },
+            "bgen": "Farm",
+            "kind": "Store",
+            "recipe": {
+                "consumption": [],
+                "production": [["Gold", 1], ["Metal", 1]],
+                "complexity": 100,
+                "storage_multiplier": 5
+            },
+            "n_workers": 10,
+            "size": 100.0,
+            "asset_location": "assets/gold_mine.png"
+        }]"#;
+
+        let errors = GoodsCompanyRegistry::parse(data).err().unwrap();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("unknown commodity Gold"));
+        assert!(errors[1].contains("missing asset assets/gold_mine.png"));
+    }
+}
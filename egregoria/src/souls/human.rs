@@ -1,10 +1,12 @@
 use crate::economy::CommodityKind::JobOpening;
 use crate::economy::{Bought, Market};
-use crate::map_dynamic::{BuildingInfos, Destination, Router};
+use crate::map_dynamic::{house_capacity, BuildingInfos, Destination, Router};
 use crate::pedestrians::{spawn_pedestrian, Location};
-use crate::souls::desire::{BuyFood, Home, Work};
+use crate::souls::desire::{BuyFood, Home, Shop, Work};
+use crate::souls::population::Happiness;
+use crate::utils::rand_world;
 use crate::utils::time::GameTime;
-use crate::vehicles::{spawn_parked_vehicle, VehicleID, VehicleKind};
+use crate::vehicles::{spawn_locked_bike, spawn_parked_vehicle, VehicleID, VehicleKind};
 use crate::{Egregoria, ParCommandBuffer, SoulID};
 use geom::Transform;
 use imgui_inspect_derive::Inspect;
@@ -64,9 +66,11 @@ enum NextDesire<'a> {
     Home(&'a mut Home),
     Work(&'a mut Work),
     Food(&'a mut BuyFood),
+    Shop(&'a mut Shop),
 }
 
 register_system!(update_decision);
+#[allow(clippy::too_many_arguments)]
 #[system(par_for_each)]
 pub fn update_decision(
     #[resource] cbuf: &ParCommandBuffer,
@@ -81,6 +85,7 @@ pub fn update_decision(
     food: Option<&mut BuyFood>,
     home: Option<&mut Home>,
     work: Option<&mut Work>,
+    shop: Option<&mut Shop>,
 ) {
     if decision.wait != 0 {
         decision.wait -= 1;
@@ -117,36 +122,95 @@ pub fn update_decision(
     if let Some(food) = food {
         let score = food.score(time, loc, bought);
 
-        #[allow(unused_assignments)]
         if score > max_score {
             max_score = score;
             decision_id = NextDesire::Food(food);
         }
     }
 
+    if let Some(shop) = shop {
+        let score = shop.score(time, loc, bought);
+
+        #[allow(unused_assignments)]
+        if score > max_score {
+            max_score = score;
+            decision_id = NextDesire::Shop(shop);
+        }
+    }
+
     match decision_id {
         NextDesire::Home(home) => decision.kind = home.apply(),
         NextDesire::Work(work) => decision.kind = work.apply(loc, router),
         NextDesire::Food(food) => {
             decision.kind = food.apply(cbuf, binfos, time, soul, trans, loc, bought)
         }
+        NextDesire::Shop(shop) => {
+            decision.kind = shop.apply(cbuf, binfos, time, soul, trans, loc, bought)
+        }
         NextDesire::None => {}
     }
 }
 
-pub fn spawn_human(goria: &mut Egregoria, house: BuildingID) -> Option<SoulID> {
+/// Proportion of humans owning a bike
+const BIKE_OWNERSHIP: f32 = 0.5;
+/// Proportion of households moving in as a couple rather than a single adult
+const COUPLE_CHANCE: f32 = 0.6;
+const MAX_CHILDREN_MOVING_IN: u32 = 2;
+
+/// Moves a new household in the empty house, the number of people depends on its capacity.
+/// Returns how many people moved in
+pub fn spawn_household(goria: &mut Egregoria, house: BuildingID) -> u32 {
+    let capacity = unwrap_ret!(goria.map().buildings().get(house).map(house_capacity), 0);
+    let n_adults = if capacity >= 2 && rand_world::<f32>(goria) < COUPLE_CHANCE {
+        2
+    } else {
+        1
+    };
+    let max_children = (capacity - n_adults).min(MAX_CHILDREN_MOVING_IN);
+    let n_children =
+        ((rand_world::<f32>(goria) * (max_children + 1) as f32) as u32).min(max_children);
+
+    let adults = (0..n_adults).filter(|_| spawn_human(goria, house, true).is_some());
+    let n_adults = adults.count() as u32;
+    let children = (0..n_children).filter(|_| spawn_human(goria, house, false).is_some());
+    n_adults + children.count() as u32
+}
+
+/// Spawns a human living in the house. Adults look for a job, buy food and share the car of
+/// the household, which is bought when the first adult moves in. Children stay home.
+pub fn spawn_human(goria: &mut Egregoria, house: BuildingID, adult: bool) -> Option<SoulID> {
     let map = goria.map();
     let housepos = map.buildings().get(house)?.door_pos;
     drop(map);
 
     let human = SoulID(spawn_pedestrian(goria, house)?);
-    let car = spawn_parked_vehicle(goria, VehicleKind::Car, housepos);
-
-    let mut m = goria.write::<Market>();
-    m.buy(human, housepos.xy(), JobOpening, 1);
-    drop(m);
+    let bike = if rand_world::<f32>(goria) < BIKE_OWNERSHIP {
+        Some(spawn_locked_bike(goria, housepos))
+    } else {
+        None
+    };
+
+    let mut router = Router::new(None, bike);
+    if adult {
+        let car = goria
+            .read::<BuildingInfos>()
+            .get(house)
+            .and_then(|b| b.household.car);
+        let car = car.or_else(|| {
+            let car = spawn_parked_vehicle(goria, VehicleKind::Car, housepos)?;
+            goria.write::<BuildingInfos>().get_mut(house)?.household.car = Some(car);
+            Some(car)
+        });
+        router = Router::new(car, bike).shared_with(house);
+
+        let mut m = goria.write::<Market>();
+        let price = m.bid_price(JobOpening);
+        m.buy(human, housepos.xy(), JobOpening, 1, price);
+    }
 
-    goria.write::<BuildingInfos>().set_owner(house, human);
+    goria
+        .write::<BuildingInfos>()
+        .add_resident(house, human, adult);
 
     let time = goria.read::<GameTime>().instant();
 
@@ -154,8 +218,12 @@ pub fn spawn_human(goria: &mut Egregoria, house: BuildingID) -> Option<SoulID> {
 
     e.add_component(HumanDecision::default());
     e.add_component(Home::new(house));
-    e.add_component(BuyFood::new(time));
     e.add_component(Bought::default());
-    e.add_component(Router::new(car));
+    e.add_component(router);
+    if adult {
+        e.add_component(BuyFood::new(time));
+        e.add_component(Shop::new(time));
+        e.add_component(Happiness::default());
+    }
     Some(human)
 }
@@ -1,6 +1,11 @@
-use crate::map_dynamic::BuildingInfos;
+use crate::economy::CommodityKind::JobOpening;
+use crate::economy::Market;
+use crate::map_dynamic::{house_capacity, BuildingInfos};
 use crate::souls::goods_company::{company_soul, CompanyKind, GoodsCompany, GoodsCompanyRegistry};
-use crate::souls::human::spawn_human;
+use crate::souls::human::{spawn_household, spawn_human};
+use crate::souls::population::Population;
+use crate::utils::rand_world;
+use crate::utils::time::GameTime;
 use crate::vehicles::{spawn_parked_vehicle, VehicleKind};
 use crate::Egregoria;
 use common::FastMap;
@@ -12,14 +17,37 @@ pub mod desire;
 
 pub mod goods_company;
 pub mod human;
+pub mod population;
+
+/// How often households with room left may welcome a new member, in seconds
+const HOUSEHOLD_GROWTH_PERIOD: u32 = GameTime::HOUR as u32;
+/// Chance for a household with room left to grow every period
+const HOUSEHOLD_GROWTH_CHANCE: f32 = 0.1;
+/// Households with less adults than this welcome an adult rather than a child,
+/// if there is a job for them
+const ADULTS_PER_HOUSEHOLD: usize = 2;
+
+/// Job openings nobody applied for yet, people only move in the city when there are some
+fn job_vacancies(goria: &Egregoria) -> i32 {
+    goria.read::<Market>().unmet_offer(JobOpening)
+}
 
 pub(crate) fn add_souls_to_empty_buildings(goria: &mut Egregoria) {
     let map = goria.map();
     let infos = goria.read::<BuildingInfos>();
+    let grow = goria.read::<GameTime>().tick(HOUSEHOLD_GROWTH_PERIOD);
     let mut empty_buildings: FastMap<BuildingKind, Vec<(BuildingID, Vec3)>> = FastMap::default();
+    let mut growing = vec![];
 
     for (id, building) in map.buildings() {
-        if unwrap_cont!(infos.get(id)).owner.is_some() {
+        let info = unwrap_cont!(infos.get(id));
+        if info.owner.is_some() {
+            if grow
+                && building.kind == BuildingKind::House
+                && (info.household.len() as u32) < house_capacity(building)
+            {
+                growing.push((id, info.household.adults.len() < ADULTS_PER_HOUSEHOLD));
+            }
             continue;
         }
 
@@ -33,16 +61,6 @@ pub(crate) fn add_souls_to_empty_buildings(goria: &mut Egregoria) {
 
     let mut n_souls_added = 0;
 
-    for &(build_id, _) in empty_buildings
-        .get(&BuildingKind::House)
-        .unwrap_or(&vec![])
-        .iter()
-        .take(100)
-    {
-        spawn_human(goria, build_id);
-        n_souls_added += 1;
-    }
-
     for (bkind, &(build_id, pos)) in empty_buildings
         .iter()
         .flat_map(|(bkind, v)| v.iter().map(move |x| (bkind, x)))
@@ -68,7 +86,10 @@ pub(crate) fn add_souls_to_empty_buildings(goria: &mut Egregoria) {
             kind: des.kind,
             building: build_id,
             recipe: des.recipe.clone(),
+            stock: des.stock.clone(),
             max_workers: des.n_workers,
+            power_consumption: des.power_consumption,
+            power_output: 0,
             progress: 0.0,
             driver: None,
             trucks: {
@@ -82,6 +103,38 @@ pub(crate) fn add_souls_to_empty_buildings(goria: &mut Egregoria) {
         n_souls_added += 1;
     }
 
+    // companies come first so the newcomers can find a job
+    let mut n_immigrants = 0;
+    for &(build_id, _) in empty_buildings
+        .get(&BuildingKind::House)
+        .unwrap_or(&vec![])
+        .iter()
+        .take(100)
+    {
+        if job_vacancies(goria) <= 0 {
+            break;
+        }
+        n_immigrants += spawn_household(goria, build_id);
+    }
+
+    for (build_id, wants_adult) in growing {
+        if rand_world::<f32>(goria) >= HOUSEHOLD_GROWTH_CHANCE {
+            continue;
+        }
+        let adult = wants_adult && job_vacancies(goria) > 0;
+        if spawn_human(goria, build_id, adult).is_none() {
+            continue;
+        }
+        if adult {
+            n_immigrants += 1;
+        } else {
+            n_souls_added += 1;
+        }
+    }
+
+    n_souls_added += n_immigrants;
+    goria.write::<Population>().immigrants += n_immigrants;
+
     if n_souls_added > 0 {
         log::info!("{} souls added", n_souls_added);
     }
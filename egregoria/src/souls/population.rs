@@ -0,0 +1,201 @@
+use crate::economy::CommodityKind::JobOpening;
+use crate::economy::{Bought, Market, Workers};
+use crate::map_dynamic::{BuildingInfos, Router};
+use crate::pedestrians::Location;
+use crate::souls::desire::{BuyFood, Home, Work};
+use crate::souls::goods_company::GoodsCompany;
+use crate::utils::time::GameTime;
+use crate::{Egregoria, ParCommandBuffer, SoulID};
+use imgui_inspect_derive::Inspect;
+use legion::system;
+use legion::world::SubWorld;
+use legion::{Entity, EntityStore};
+use map_model::{BuildingID, Map};
+use serde::{Deserialize, Serialize};
+
+/// How often happiness is updated, in seconds
+const HAPPINESS_PERIOD: u32 = GameTime::HOUR as u32;
+/// Part of the gap between happiness and satisfaction closed every period
+const HAPPINESS_ADAPTATION: f32 = 0.1;
+/// Humans less happy than this leave the city the next time they are home
+pub const LEAVE_THRESHOLD: f32 = 0.35;
+const UNEMPLOYED_SATISFACTION: f32 = 0.2;
+/// Commutes shorter than this don't bother anyone, in meters
+const SHORT_COMMUTE: f32 = 1000.0;
+/// Commutes longer than this are as bad as they get, in meters
+const LONG_COMMUTE: f32 = 5000.0;
+const LONG_COMMUTE_SATISFACTION: f32 = 0.2;
+
+/// How happy an adult is to live in the city, from 0 to 1.
+/// It slowly follows how well they eat and how good their job is, the unhappy end up leaving.
+#[derive(Inspect, Clone, Serialize, Deserialize, Debug)]
+pub struct Happiness {
+    pub value: f32,
+    /// Satisfaction with food at the last update, from 0 to 1
+    pub food: f32,
+    /// Satisfaction with work and commute at the last update, from 0 to 1
+    pub work: f32,
+}
+
+impl Default for Happiness {
+    fn default() -> Self {
+        Self {
+            value: 1.0,
+            food: 1.0,
+            work: 1.0,
+        }
+    }
+}
+
+impl Happiness {
+    /// Fine until a day without eating, as bad as it gets after two
+    pub fn food_satisfaction(food: &BuyFood, time: &GameTime) -> f32 {
+        let days = food.last_ate().elapsed(time) as f32 / GameTime::DAY as f32;
+        (2.0 - days).clamp(0.0, 1.0)
+    }
+
+    /// Having a job matters most, then how far it is from home
+    pub fn work_satisfaction(map: &Map, home: &Home, work: Option<&Work>) -> f32 {
+        let work = unwrap_ret!(work, UNEMPLOYED_SATISFACTION);
+        let buildings = map.buildings();
+        let (h, w) = match (buildings.get(home.house()), buildings.get(work.workplace())) {
+            (Some(h), Some(w)) => (h.door_pos.xy(), w.door_pos.xy()),
+            _ => return UNEMPLOYED_SATISFACTION,
+        };
+        let commute = (h.distance(w) - SHORT_COMMUTE) / (LONG_COMMUTE - SHORT_COMMUTE);
+        1.0 - commute.clamp(0.0, 1.0) * (1.0 - LONG_COMMUTE_SATISFACTION)
+    }
+}
+
+register_system!(update_happiness);
+#[allow(clippy::too_many_arguments)]
+#[system(par_for_each)]
+#[read_component(Work)]
+pub fn update_happiness(
+    #[resource] time: &GameTime,
+    #[resource] map: &Map,
+    #[resource] cbuf: &ParCommandBuffer,
+    me: &Entity,
+    loc: &Location,
+    home: &Home,
+    food: &BuyFood,
+    happiness: &mut Happiness,
+    subworld: &SubWorld<'_>,
+) {
+    if !time.tick(HAPPINESS_PERIOD) {
+        return;
+    }
+
+    happiness.food = Happiness::food_satisfaction(food, time);
+    let work = subworld
+        .entry_ref(*me)
+        .ok()
+        .and_then(|e| e.into_component::<Work>().ok());
+    happiness.work = Happiness::work_satisfaction(map, home, work);
+    let satisfaction = happiness.food * happiness.work;
+    happiness.value += (satisfaction - happiness.value) * HAPPINESS_ADAPTATION;
+
+    if happiness.value < LEAVE_THRESHOLD && loc == &Location::Building(home.house()) {
+        let soul = SoulID(*me);
+        cbuf.exec_ent(*me, move |goria| emigrate(goria, soul));
+    }
+}
+
+register_resource!(Population, "population");
+/// Counts the people who moved in and out of the city
+#[derive(Default, Serialize, Deserialize)]
+pub struct Population {
+    pub immigrants: u32,
+    pub emigrants: u32,
+}
+
+/// The human leaves the city, along with the children and the car of the household
+/// when no other adult stays
+pub fn emigrate(goria: &mut Egregoria, soul: SoulID) {
+    let house = unwrap_ret!(goria.read::<BuildingInfos>().home_of(soul));
+    remove_human(goria, soul);
+
+    let mut leaving = 1;
+    let mut binfos = goria.write::<BuildingInfos>();
+    let household = &mut unwrap_ret!(binfos.get_mut(house)).household;
+    if household.adults.is_empty() {
+        let children = std::mem::take(&mut household.children);
+        let car = household.car.take();
+        drop(binfos);
+
+        for &child in &children {
+            remove_human(goria, child);
+        }
+        leaving += children.len() as u32;
+        if let Some(car) = car {
+            goria.read::<ParCommandBuffer>().kill(car.0);
+        }
+    } else {
+        drop(binfos);
+    }
+
+    goria.write::<Population>().emigrants += leaving;
+    log::info!("{} people left {:?}", leaving, house);
+}
+
+/// Everyone living in the house leaves the city, as the house is gone
+pub(crate) fn evict(goria: &mut Egregoria, house: BuildingID) {
+    let adults = unwrap_ret!(goria.read::<BuildingInfos>().get(house))
+        .household
+        .adults
+        .clone();
+    for adult in adults {
+        emigrate(goria, adult);
+    }
+}
+
+fn remove_human(goria: &mut Egregoria, soul: SoulID) {
+    if let Some(&Location::Building(b)) = goria.comp::<Location>(soul.0) {
+        goria.write::<BuildingInfos>().get_out(b, soul);
+    }
+    goria.write::<BuildingInfos>().remove_resident(soul);
+    quit_job(goria, soul);
+    goria.write::<Market>().remove(soul);
+
+    let cbuf = goria.read::<ParCommandBuffer>();
+    if let Some(bike) = goria.comp::<Router>(soul.0).and_then(|r| r.personal_bike) {
+        cbuf.kill(bike.0);
+    }
+    cbuf.kill(soul.0);
+}
+
+/// Gives the job back to the employer, who puts it up for grabs again
+fn quit_job(goria: &mut Egregoria, soul: SoulID) {
+    let bought = unwrap_ret!(goria.comp::<Bought>(soul.0));
+    let trade = *unwrap_ret!(bought.0.get(&JobOpening).and_then(|v| v.last()));
+    let employer = trade.seller;
+
+    unwrap_ret!(goria.comp_mut::<Workers>(employer.0))
+        .0
+        .retain(|&w| w != soul);
+    if let Some(company) = goria.comp_mut::<GoodsCompany>(employer.0) {
+        if company.driver == Some(soul) {
+            company.driver = None;
+        }
+    }
+
+    let mut m = goria.write::<Market>();
+    m.produce(employer, JobOpening, 1);
+    m.sell_all(employer, trade.sell_pos, JobOpening);
+}
+
+/// The employer closed, the soul looks for another job from home
+pub(crate) fn lose_job(goria: &mut Egregoria, soul: SoulID) {
+    if let Some(bought) = goria.comp_mut::<Bought>(soul.0) {
+        bought.0.remove(&JobOpening);
+    }
+    if let Some(mut e) = goria.world.entry(soul.0) {
+        e.remove_component::<Work>();
+    }
+
+    let house = unwrap_ret!(goria.read::<BuildingInfos>().home_of(soul));
+    let housepos = unwrap_ret!(goria.map().buildings().get(house)).door_pos;
+    let mut m = goria.write::<Market>();
+    let price = m.bid_price(JobOpening);
+    m.buy(soul, housepos.xy(), JobOpening, 1, price);
+}
@@ -0,0 +1,72 @@
+use super::*;
+use crate::map_dynamic::{
+    ActuatedSignal, ActuatedSignals, SignalStage, MAX_GREEN, MIN_GREEN, ORANGE_LENGTH,
+};
+use geom::vec3;
+use map_model::{LightPolicy, TrafficBehavior, TrafficControl};
+
+#[test]
+fn test_actuated_signal_switching() {
+    let mut s = ActuatedSignal::new(0);
+
+    // nobody waits elsewhere, green rests on the first phase
+    s.update(100, &[0, 0, 0]);
+    assert_eq!(s.phase, 0);
+    assert_eq!(s.stage, SignalStage::Green);
+
+    // the green is extended while vehicles keep coming
+    let mut s = ActuatedSignal::new(0);
+    s.update(MIN_GREEN, &[2, 0, 1]);
+    assert_eq!(s.stage, SignalStage::Green);
+    s.update(MAX_GREEN, &[2, 0, 1]);
+    assert_eq!(s.stage, SignalStage::Orange);
+    assert_eq!(s.behavior(0), TrafficBehavior::ORANGE);
+
+    // the second phase is skipped since nobody waits there
+    s.update(MAX_GREEN + ORANGE_LENGTH, &[2, 0, 1]);
+    assert_eq!(s.phase, 2);
+    assert_eq!(s.stage, SignalStage::Green);
+    assert_eq!(s.behavior(2), TrafficBehavior::GREEN);
+    assert_eq!(s.behavior(0), TrafficBehavior::RED);
+}
+
+#[test]
+fn test_actuated_intersection() {
+    let mut ctx = TestCtx::init();
+
+    ctx.build_roads(&[
+        vec3(-100.0, 0.0, 0.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(100.0, 0.0, 0.0),
+    ]);
+    ctx.build_roads(&[
+        vec3(0.0, -100.0, 0.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(0.0, 100.0, 0.0),
+    ]);
+
+    let id = ctx
+        .g
+        .map()
+        .intersections()
+        .values()
+        .find(|inter| inter.roads.len() == 4)
+        .unwrap()
+        .id;
+    ctx.g
+        .map_mut()
+        .update_intersection(id, |inter| inter.light_policy = LightPolicy::Actuated);
+    ctx.tick();
+
+    assert_eq!(ctx.g.read::<ActuatedSignals>().get(id).unwrap().phase, 0);
+
+    let map = ctx.g.map();
+    let phases = LightPolicy::phases(&map.intersections()[id], map.roads());
+    assert_eq!(phases.len(), 2);
+    for lane in &phases[1] {
+        assert!(matches!(
+            map.lanes()[*lane].control,
+            TrafficControl::Actuated(TrafficBehavior::RED)
+        ));
+    }
+}
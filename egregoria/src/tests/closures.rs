@@ -0,0 +1,103 @@
+use super::*;
+use crate::map_dynamic::Itinerary;
+use crate::utils::time::GameTime;
+use geom::vec3;
+use map_model::{
+    ClosureKind, LaneID, LaneKind, Map, PathKind, Pathfinder, Traversable, TraverseDirection,
+    TraverseKind,
+};
+
+fn driving_lane(map: &Map, from: IntersectionID, to: IntersectionID) -> LaneID {
+    let road = &map.roads()[map.find_road(from, to).unwrap()];
+    road.outgoing_lanes_from(from)
+        .iter()
+        .find(|(_, kind)| *kind == LaneKind::Driving)
+        .unwrap()
+        .0
+}
+
+#[test]
+fn test_road_closure() {
+    let mut ctx = TestCtx::init();
+
+    // two routes of the same length from west to east, through the north or the south
+    ctx.build_roads(&[
+        vec3(-100.0, 0.0, 0.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(100.0, 0.0, 0.0),
+        vec3(100.0, 100.0, 0.0),
+        vec3(200.0, 100.0, 0.0),
+    ]);
+    ctx.build_roads(&[
+        vec3(0.0, 0.0, 0.0),
+        vec3(0.0, 100.0, 0.0),
+        vec3(100.0, 100.0, 0.0),
+    ]);
+
+    let w = ctx.inter_at(vec3(-100.0, 0.0, 0.0));
+    let a = ctx.inter_at(vec3(0.0, 0.0, 0.0));
+    let d = ctx.inter_at(vec3(100.0, 100.0, 0.0));
+    let e = ctx.inter_at(vec3(200.0, 100.0, 0.0));
+
+    let map = ctx.g.map();
+    let start = driving_lane(&map, w, a);
+    let end = driving_lane(&map, d, e);
+    let path = |map: &Map| {
+        let start = Traversable::new(TraverseKind::Lane(start), TraverseDirection::Forward);
+        PathKind::Vehicle.path(map, start, end).unwrap()
+    };
+    let closed = match path(&map)[2].kind {
+        TraverseKind::Lane(id) => map.lanes()[id].parent,
+        _ => panic!("expected a lane"),
+    };
+
+    let start_pos = map.lanes()[start].points.point_along(10.0);
+    let end_pos = map.lanes()[end].points.point_along(10.0);
+    let it = Itinerary::route(start_pos, end_pos, &map, PathKind::Vehicle).unwrap();
+
+    // another one is already driving on the road about to close
+    let on_closed = map.roads()[closed]
+        .lanes_iter()
+        .find(|&(_, kind)| kind == LaneKind::Driving)
+        .unwrap()
+        .0;
+    let on_pos = map.lanes()[on_closed].points.point_along(10.0);
+    let it_on_road = Itinerary::route(on_pos, end_pos, &map, PathKind::Vehicle).unwrap();
+    assert_eq!(
+        it_on_road.get_travers().map(|t| t.kind),
+        Some(TraverseKind::Lane(on_closed))
+    );
+    drop(map);
+
+    let vehicle = ctx.g.world.push((it,));
+    let on_road = ctx.g.world.push((it_on_road,));
+
+    let mut commands = WorldCommands::default();
+    commands.map_close_road(closed, ClosureKind::Closed, Some(60));
+    ctx.apply(commands);
+
+    // the vehicles going through the closure are rerouted, around it
+    for v in [vehicle, on_road] {
+        assert!(ctx
+            .g
+            .comp::<Itinerary>(v)
+            .unwrap()
+            .is_wait_for_reroute()
+            .is_some());
+    }
+    let map = ctx.g.map();
+    assert!(path(&map)
+        .iter()
+        .all(|t| map.lanes()[t.destination_lane()].parent != closed));
+    drop(map);
+
+    // the closure is lifted once it expired
+    let now = ctx.g.read::<GameTime>().timestamp;
+    let mut commands = WorldCommands::default();
+    commands.set_game_time(GameTime::new(0.05, now + 61.0));
+    ctx.apply(commands);
+    let map = ctx.g.map();
+    assert!(map.roads()[closed]
+        .lanes_iter()
+        .all(|(id, _)| map.lanes()[id].closure.is_none()));
+}
@@ -0,0 +1,67 @@
+use super::*;
+use geom::vec3;
+use map_model::{
+    LaneID, LaneKind, Map, PathKind, Pathfinder, Traversable, TraverseDirection, TraverseKind,
+};
+use std::collections::BTreeMap;
+
+fn driving_lane(map: &Map, from: IntersectionID, to: IntersectionID) -> LaneID {
+    let road = &map.roads()[map.find_road(from, to).unwrap()];
+    road.outgoing_lanes_from(from)
+        .iter()
+        .find(|(_, kind)| *kind == LaneKind::Driving)
+        .unwrap()
+        .0
+}
+
+#[test]
+fn test_congestion_reroute() {
+    let ctx = TestCtx::init();
+
+    // two routes of the same length from west to east, through the north or the south
+    ctx.build_roads(&[
+        vec3(-100.0, 0.0, 0.0),
+        vec3(0.0, 0.0, 0.0),
+        vec3(100.0, 0.0, 0.0),
+        vec3(100.0, 100.0, 0.0),
+        vec3(200.0, 100.0, 0.0),
+    ]);
+    ctx.build_roads(&[
+        vec3(0.0, 0.0, 0.0),
+        vec3(0.0, 100.0, 0.0),
+        vec3(100.0, 100.0, 0.0),
+    ]);
+
+    let w = ctx.inter_at(vec3(-100.0, 0.0, 0.0));
+    let a = ctx.inter_at(vec3(0.0, 0.0, 0.0));
+    let d = ctx.inter_at(vec3(100.0, 100.0, 0.0));
+    let e = ctx.inter_at(vec3(200.0, 100.0, 0.0));
+
+    let mut map = ctx.g.map_mut();
+    let start = Traversable::new(
+        TraverseKind::Lane(driving_lane(&map, w, a)),
+        TraverseDirection::Forward,
+    );
+    let end = driving_lane(&map, d, e);
+
+    let first_lane = |map: &Map| match PathKind::Vehicle.path(map, start, end).unwrap()[2].kind {
+        TraverseKind::Lane(id) => id,
+        _ => panic!("expected a lane"),
+    };
+
+    let taken = first_lane(&map);
+    let mut samples = BTreeMap::new();
+    samples.insert(taken, 0.0);
+    for _ in 0..10 {
+        map.observe_lane_speeds(&samples);
+    }
+    assert!(map.lanes()[taken].travel_speed() < 1.0);
+    assert_ne!(first_lane(&map), taken);
+
+    // without traffic the lane goes back to its speed limit
+    for _ in 0..50 {
+        map.observe_lane_speeds(&BTreeMap::new());
+    }
+    let lane = &map.lanes()[taken];
+    assert!(lane.speed_limit - lane.travel_speed() < 0.1);
+}
@@ -0,0 +1,67 @@
+use super::*;
+use crate::map_dynamic::ElectricityFlow;
+use crate::souls::goods_company::{company_soul, CompanyKind, GoodsCompany, Recipe};
+use geom::{vec2, vec3};
+
+fn consumer(building: BuildingID, power_consumption: i32) -> GoodsCompany {
+    GoodsCompany {
+        kind: CompanyKind::Store,
+        recipe: Recipe {
+            consumption: vec![],
+            production: vec![],
+            complexity: 100,
+            storage_multiplier: 5,
+        },
+        stock: vec![],
+        building,
+        max_workers: 1,
+        power_consumption,
+        power_output: 0,
+        progress: 0.0,
+        driver: None,
+        trucks: vec![],
+    }
+}
+
+#[test]
+fn test_power_grid() {
+    let mut ctx = TestCtx::init();
+
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+    let a = ctx.build_house_near(vec2(20.0, 20.0));
+    let b = ctx.build_house_near(vec2(150.0, 20.0));
+    let c = ctx.build_house_near(vec2(280.0, 20.0));
+    assert!(a != b && b != c && a != c);
+
+    let mut commands = WorldCommands::default();
+    commands.map_add_power_line(a, b);
+    commands.map_add_power_line(b, a);
+    ctx.apply(commands);
+    assert_eq!(ctx.g.map().power_lines().len(), 1);
+
+    company_soul(&mut ctx.g, consumer(b, 100)).unwrap();
+    company_soul(&mut ctx.g, consumer(c, 100)).unwrap();
+    ctx.tick();
+
+    {
+        let flow = ctx.g.read::<ElectricityFlow>();
+        assert_eq!(flow.grids().len(), 1);
+        let grid = flow.grid(a).unwrap();
+        assert_eq!(grid.buildings.len(), 2);
+        assert_eq!(grid.consumption, 100);
+        assert_eq!(grid.production, 0);
+        // nothing produces power, the consumer is in a blackout
+        assert_eq!(flow.satisfaction(b), 0.0);
+        // not connected to any grid, it doesn't depend on power plants
+        assert!(flow.grid(c).is_none());
+        assert_eq!(flow.satisfaction(c), 1.0);
+    }
+
+    let mut commands = WorldCommands::default();
+    commands.map_remove_building(a);
+    ctx.apply(commands);
+    ctx.tick();
+
+    assert!(ctx.g.map().power_lines().is_empty());
+    assert!(ctx.g.read::<ElectricityFlow>().grids().is_empty());
+}
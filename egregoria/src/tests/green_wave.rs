@@ -0,0 +1,49 @@
+use super::*;
+use geom::vec3;
+use map_model::{LightPolicy, TrafficBehavior};
+
+#[test]
+fn test_green_wave() {
+    let mut ctx = TestCtx::init();
+
+    ctx.build_roads(&[
+        vec3(0.0, 0.0, 0.0),
+        vec3(100.0, 0.0, 0.0),
+        vec3(200.0, 0.0, 0.0),
+    ]);
+    ctx.build_roads(&[
+        vec3(100.0, -100.0, 0.0),
+        vec3(100.0, 0.0, 0.0),
+        vec3(100.0, 100.0, 0.0),
+    ]);
+
+    let a = ctx.inter_at(vec3(0.0, 0.0, 0.0));
+    let b = ctx.inter_at(vec3(100.0, 0.0, 0.0));
+    let c = ctx.inter_at(vec3(200.0, 0.0, 0.0));
+    let d = ctx.inter_at(vec3(100.0, 100.0, 0.0));
+
+    // not a chain of neighbours
+    assert!(!ctx.g.map_mut().set_green_wave(&[a, c]));
+    assert!(!ctx.g.map_mut().set_green_wave(&[a, b, d, b]));
+
+    let mut commands = WorldCommands::default();
+    commands.map_set_green_wave(vec![a, b, c]);
+    ctx.apply(commands);
+
+    let map = ctx.g.map();
+    for id in [a, b, c] {
+        assert!(matches!(
+            map.intersections()[id].light_policy,
+            LightPolicy::Custom(_)
+        ));
+    }
+
+    // vehicles coming from a get the green light at b right when they arrive
+    let road = &map.roads()[map.find_road(a, b).unwrap()];
+    let (lane, _) = road.incoming_lanes_to(b)[0];
+    let lane = &map.lanes()[lane];
+    let arrival = (road.length() / lane.speed_limit) as u32;
+
+    assert_eq!(lane.control.get_behavior(arrival), TrafficBehavior::GREEN);
+    assert_eq!(lane.control.get_behavior(arrival - 1), TrafficBehavior::RED);
+}
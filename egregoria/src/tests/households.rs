@@ -0,0 +1,180 @@
+use super::*;
+use crate::economy::{Ledger, Taxes};
+use crate::map_dynamic::{house_capacity, Router};
+use crate::souls::desire::{BuyFood, Work, WorkKind};
+use crate::souls::population::Happiness;
+use crate::utils::time::GameTime;
+use geom::{vec2, vec3};
+use legion::IntoQuery;
+
+#[test]
+fn test_household() {
+    let mut ctx = TestCtx::init();
+
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+    let houses = ctx.build_houses_on_all_lots();
+    ctx.add_jobs(vec2(150.0, 0.0), 1000);
+    let capacity = |ctx: &TestCtx| -> usize {
+        let map = ctx.g.map();
+        houses
+            .iter()
+            .map(|&h| house_capacity(&map.buildings()[h]) as usize)
+            .sum()
+    };
+    let population = |ctx: &TestCtx| -> usize {
+        let binfos = ctx.g.read::<BuildingInfos>();
+        houses
+            .iter()
+            .map(|&h| binfos.get(h).unwrap().household.len())
+            .sum()
+    };
+
+    // a household moves in each empty house
+    ctx.tick();
+    assert!(population(&ctx) < capacity(&ctx));
+
+    let binfos = ctx.g.read::<BuildingInfos>();
+    for &house in &houses {
+        let info = binfos.get(house).unwrap();
+        let household = &info.household;
+        assert!(!household.adults.is_empty());
+        assert_eq!(info.owner, Some(household.adults[0]));
+
+        // adults share the car of the household, children don't drive
+        assert!(household.car.is_some());
+        for &adult in &household.adults {
+            let router = ctx.g.comp::<Router>(adult.0).unwrap();
+            assert_eq!(router.personal_car, household.car);
+        }
+        for &child in &household.children {
+            assert!(ctx
+                .g
+                .comp::<Router>(child.0)
+                .unwrap()
+                .personal_car
+                .is_none());
+            assert!(ctx.g.comp::<BuyFood>(child.0).is_none());
+            assert!(ctx.g.comp::<Work>(child.0).is_none());
+        }
+    }
+    drop(binfos);
+
+    // only one adult can use the car at a time
+    let house = houses[0];
+    let (a, b) = (SoulID(ctx.g.world.push(())), SoulID(ctx.g.world.push(())));
+    let mut binfos = ctx.g.write::<BuildingInfos>();
+    binfos.get_mut(house).unwrap().household.car_user = None;
+    assert!(binfos.claim_car(house, a));
+    assert!(!binfos.claim_car(house, b));
+    binfos.release_car(house, a);
+    assert!(binfos.claim_car(house, b));
+    binfos.release_car(house, b);
+    drop(binfos);
+
+    // households grow until the houses are full
+    for _ in 0..300 {
+        let now = ctx.g.read::<GameTime>().timestamp;
+        let next_hour = ((now / GameTime::HOUR as f64).floor() + 1.0) * GameTime::HOUR as f64;
+        let mut commands = WorldCommands::default();
+        commands.set_game_time(GameTime::new(0.05, next_hour - 0.01));
+        ctx.apply(commands);
+        ctx.tick();
+
+        // nobody leaves, even without food
+        <&mut Happiness>::query().for_each_mut(&mut ctx.g.world, |h| h.value = 1.0);
+    }
+    assert_eq!(population(&ctx), capacity(&ctx));
+
+    let binfos = ctx.g.read::<BuildingInfos>();
+    for &house in &houses {
+        for soul in binfos.get(house).unwrap().household.members() {
+            assert_eq!(binfos.home_of(soul), Some(house));
+        }
+    }
+}
+
+#[test]
+fn test_resident_taxes() {
+    let mut ctx = TestCtx::init();
+
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+    let houses = ctx.build_houses_on_all_lots();
+    ctx.add_jobs(vec2(150.0, 0.0), 1000);
+    ctx.tick();
+
+    // let the households get their second adult
+    let skip_to = |ctx: &mut TestCtx, period: u32| {
+        let now = ctx.g.read::<GameTime>().timestamp;
+        let next = ((now / period as f64).floor() + 1.0) * period as f64;
+        let mut commands = WorldCommands::default();
+        commands.set_game_time(GameTime::new(0.05, next - 0.01));
+        ctx.apply(commands);
+        <&mut Happiness>::query().for_each_mut(&mut ctx.g.world, |h| h.value = 1.0);
+    };
+    for _ in 0..20 {
+        skip_to(&mut ctx, GameTime::HOUR as u32);
+        ctx.tick();
+    }
+
+    let binfos = ctx.g.read::<BuildingInfos>();
+    let owners = binfos.owners().count();
+    let adults: usize = houses
+        .iter()
+        .map(|&h| binfos.get(h).unwrap().household.adults.len())
+        .sum();
+    drop(binfos);
+    assert!(adults > owners);
+
+    // every adult pays, not only the owner
+    skip_to(&mut ctx, GameTime::DAY as u32);
+    ctx.tick();
+    let tax = ctx.g.read::<Taxes>().resident_tax_per_day;
+    let day = ctx.g.read::<GameTime>().daytime.day;
+    let collected = ctx
+        .g
+        .read::<Ledger>()
+        .days()
+        .find(|&&(d, _)| d == day)
+        .unwrap()
+        .1
+        .resident_taxes;
+    assert_eq!(collected, tax * adults as i64);
+}
+
+#[test]
+fn test_work_satisfaction() {
+    let mut ctx = TestCtx::init();
+
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+    let houses = ctx.build_houses_on_all_lots();
+    ctx.add_jobs(vec2(150.0, 0.0), 1000);
+    ctx.tick();
+
+    let binfos = ctx.g.read::<BuildingInfos>();
+    let adults: Vec<_> = houses
+        .iter()
+        .flat_map(|&h| binfos.get(h).unwrap().household.adults.clone())
+        .collect();
+    drop(binfos);
+    let (worker, jobless) = (adults[0], adults[1]);
+
+    // working next door is as good as it gets
+    ctx.g
+        .world
+        .entry(worker.0)
+        .unwrap()
+        .add_component(Work::new(houses[0], WorkKind::Worker, 0.0));
+
+    let now = ctx.g.read::<GameTime>().timestamp;
+    let hour = GameTime::HOUR as f64;
+    let mut commands = WorldCommands::default();
+    commands.set_game_time(GameTime::new(
+        0.05,
+        ((now / hour).floor() + 1.0) * hour - 0.01,
+    ));
+    ctx.apply(commands);
+    ctx.tick();
+
+    assert_eq!(ctx.g.comp::<Happiness>(worker.0).unwrap().work, 1.0);
+    assert!(ctx.g.comp::<Happiness>(jobless.0).unwrap().work < 1.0);
+}
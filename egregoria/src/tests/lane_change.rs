@@ -0,0 +1,89 @@
+use super::*;
+use crate::map_dynamic::Itinerary;
+use geom::vec3;
+use map_model::{
+    LaneID, LaneKind, Map, PathKind, Pathfinder, Traversable, TraverseDirection, TraverseKind,
+};
+
+/// Driving lanes going from one intersection to the other, from the inside of the road
+fn driving_lanes(map: &Map, from: IntersectionID, to: IntersectionID) -> Vec<LaneID> {
+    let road = &map.roads()[map.find_road(from, to).unwrap()];
+    road.outgoing_lanes_from(from)
+        .iter()
+        .filter(|(_, kind)| *kind == LaneKind::Driving)
+        .map(|&(id, _)| id)
+        .collect()
+}
+
+#[test]
+fn test_lane_change() {
+    let ctx = TestCtx::init();
+
+    ctx.build_roads_with(
+        &[
+            vec3(-100.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 0.0),
+            vec3(100.0, 0.0, 0.0),
+        ],
+        &LanePatternBuilder::new().n_lanes(2).build(),
+    );
+
+    let w = ctx.inter_at(vec3(-100.0, 0.0, 0.0));
+    let a = ctx.inter_at(vec3(0.0, 0.0, 0.0));
+    let e = ctx.inter_at(vec3(100.0, 0.0, 0.0));
+
+    let map = ctx.g.map();
+    let start = driving_lanes(&map, w, a)[0];
+    let end = driving_lanes(&map, a, e)[1];
+
+    // lanes are linked one to one through the intersection, so changing lanes is needed
+    let path = PathKind::Vehicle
+        .path(
+            &map,
+            Traversable::new(TraverseKind::Lane(start), TraverseDirection::Forward),
+            end,
+        )
+        .unwrap();
+    let changes = path
+        .iter()
+        .filter(|t| matches!(t.kind, TraverseKind::LaneChange { .. }))
+        .count();
+    assert_eq!(changes, 1);
+    assert_eq!(path.last().unwrap().kind, TraverseKind::Lane(end));
+
+    let along = |lane: LaneID, t: f32| {
+        let points = &map.lanes()[lane].points;
+        points.point_along(points.length() * t)
+    };
+    let start_pos = along(start, 0.2);
+    let end_pos = along(end, 0.8);
+    let mut it = Itinerary::route(start_pos, end_pos, &map, PathKind::Vehicle).unwrap();
+
+    let mut pos = start_pos;
+    for time in 0..100 {
+        if it.is_changing_lane() {
+            break;
+        }
+        pos = it.get_point().unwrap();
+        it.update(pos, time, &map);
+    }
+    let to = match it.get_travers().unwrap().kind {
+        TraverseKind::LaneChange { to, .. } => to,
+        _ => panic!("expected a lane change"),
+    };
+
+    // merging into the lane a bit ahead
+    it.change_lane(&map, pos);
+    assert_eq!(it.get_travers().unwrap().kind, TraverseKind::Lane(to));
+    let merge = it.get_point().unwrap();
+    assert!(map.lanes()[to].points.project_dist(merge) < 0.1);
+    assert!(merge.distance(pos) > 5.0);
+
+    for time in 0..100 {
+        if it.has_ended(0.0) {
+            break;
+        }
+        it.update(it.get_point().unwrap(), time, &map);
+    }
+    assert!(it.has_ended(0.0));
+}
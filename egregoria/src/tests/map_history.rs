@@ -0,0 +1,275 @@
+use super::*;
+use crate::economy::{Government, Money};
+use crate::map_dynamic::MapHistory;
+use crate::souls::desire::Work;
+use crate::souls::goods_company::{company_soul, CompanyKind, GoodsCompany, Recipe};
+use crate::souls::population::Population;
+use crate::SerPreparedEgregoria;
+use geom::{vec2, vec3};
+use legion::IntoQuery;
+use map_model::{ClosureKind, LotID, RoadID, Terraform};
+use std::convert::TryFrom;
+
+#[test]
+fn test_undo_redo() {
+    let mut ctx = TestCtx::init();
+
+    let mut commands = WorldCommands::default();
+    commands.map_load_testfield(Vec2::ZERO, 3, 100.0);
+    ctx.apply(commands);
+
+    let roads: Vec<RoadID> = ctx.g.map().roads().keys().collect();
+    assert!(!roads.is_empty());
+    assert!(ctx.g.read::<MapHistory>().can_undo());
+
+    let mut commands = WorldCommands::default();
+    commands.undo();
+    ctx.apply(commands);
+
+    assert_eq!(ctx.g.map().roads().len(), 0);
+    assert!(!ctx.g.read::<MapHistory>().can_undo());
+    assert!(ctx.g.read::<MapHistory>().can_redo());
+
+    // a rejected edit doesn't change the history
+    ctx.g.write::<Government>().money = Money::ZERO;
+    let mut commands = WorldCommands::default();
+    commands.map_terraform(Vec2::ZERO, 100.0, Terraform::Raise(10.0));
+    ctx.apply(commands);
+    assert!(!ctx.g.read::<MapHistory>().can_undo());
+    assert!(ctx.g.read::<MapHistory>().can_redo());
+
+    let mut commands = WorldCommands::default();
+    commands.redo();
+    ctx.apply(commands);
+
+    // the same roads are back, with the same ids
+    let mut redone: Vec<RoadID> = ctx.g.map().roads().keys().collect();
+    redone.sort();
+    let mut roads = roads;
+    roads.sort();
+    assert_eq!(redone, roads);
+    assert!(!ctx.g.read::<MapHistory>().can_redo());
+}
+
+fn lot_near(ctx: &TestCtx, p: Vec2) -> LotID {
+    ctx.g
+        .map()
+        .lots()
+        .values()
+        .min_by_key(|lot| lot.shape.center().distance2(p) as i32)
+        .unwrap()
+        .id
+}
+
+#[test]
+fn test_undo_cleans_dependents() {
+    let mut ctx = TestCtx::init();
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+
+    let mut commands = WorldCommands::default();
+    commands.map_build_house(lot_near(&ctx, vec2(20.0, 20.0)));
+    ctx.apply(commands);
+    let house = ctx.g.map().buildings().keys().next().unwrap();
+
+    let mut commands = WorldCommands::default();
+    commands.map_build_house(lot_near(&ctx, vec2(280.0, 20.0)));
+    ctx.apply(commands);
+    let workplace = ctx
+        .g
+        .map()
+        .buildings()
+        .keys()
+        .find(|&b| b != house)
+        .unwrap();
+    let company = company_soul(
+        &mut ctx.g,
+        GoodsCompany {
+            kind: CompanyKind::Store,
+            recipe: Recipe {
+                consumption: vec![],
+                production: vec![],
+                complexity: 100,
+                storage_multiplier: 5,
+            },
+            stock: vec![],
+            building: workplace,
+            max_workers: 1,
+            power_consumption: 0,
+            power_output: 0,
+            progress: 0.0,
+            driver: None,
+            trucks: vec![],
+        },
+    )
+    .unwrap();
+
+    // someone moves in for the job
+    let mut worker = None;
+    for _ in 0..200 {
+        ctx.tick();
+        worker = ctx.g.read::<BuildingInfos>().get(house).and_then(|b| {
+            b.household
+                .adults
+                .iter()
+                .copied()
+                .find(|a| ctx.g.comp::<Work>(a.0).is_some())
+        });
+        if worker.is_some() {
+            break;
+        }
+    }
+    let worker = worker.unwrap();
+
+    // the workplace goes away with its company, the worker looks for another job
+    let mut commands = WorldCommands::default();
+    commands.undo();
+    ctx.apply(commands);
+    ctx.tick();
+
+    assert!(!ctx.g.map().buildings().contains_key(workplace));
+    assert!(ctx.g.read::<BuildingInfos>().get(workplace).is_none());
+    assert!(ctx.g.comp::<GoodsCompany>(company.0).is_none());
+    assert_eq!(<&GoodsCompany>::query().iter(&ctx.g.world).count(), 0);
+    assert!(ctx.g.comp::<Work>(worker.0).is_none());
+
+    // the house goes away, its household leaves the city
+    let mut commands = WorldCommands::default();
+    commands.undo();
+    ctx.apply(commands);
+    ctx.tick();
+
+    assert!(ctx.g.map().buildings().is_empty());
+    assert!(ctx.g.read::<BuildingInfos>().get(house).is_none());
+    assert!(ctx.g.read::<BuildingInfos>().home_of(worker).is_none());
+    assert!(ctx.g.world.entry(worker.0).is_none());
+    assert!(ctx.g.read::<Population>().emigrants > 0);
+
+    // both come back empty
+    let mut commands = WorldCommands::default();
+    commands.redo();
+    commands.redo();
+    ctx.apply(commands);
+
+    assert!(ctx.g.map().buildings().contains_key(house));
+    assert!(ctx.g.map().buildings().contains_key(workplace));
+    assert!(ctx.g.read::<BuildingInfos>().get(house).is_some());
+    assert!(ctx.g.read::<BuildingInfos>().get(workplace).is_some());
+}
+
+#[test]
+fn test_undo_money() {
+    let mut ctx = TestCtx::init();
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+    let start = ctx.g.read::<Government>().money;
+
+    let mut commands = WorldCommands::default();
+    commands.map_build_house(lot_near(&ctx, vec2(20.0, 20.0)));
+    ctx.apply(commands);
+    let house = ctx.g.map().buildings().keys().next().unwrap();
+    let built = ctx.g.read::<Government>().money;
+    assert!(built < start);
+
+    let mut commands = WorldCommands::default();
+    commands.map_remove_building(house);
+    ctx.apply(commands);
+    assert!(ctx.g.read::<Government>().money > built);
+
+    // undoing the demolition takes back the refund, then undoing the house gives back its cost
+    let mut commands = WorldCommands::default();
+    commands.undo();
+    ctx.apply(commands);
+    assert_eq!(ctx.g.read::<Government>().money, built);
+
+    let mut commands = WorldCommands::default();
+    commands.undo();
+    ctx.apply(commands);
+    assert_eq!(ctx.g.read::<Government>().money, start);
+
+    // redoing costs again
+    ctx.g.write::<Government>().money = Money::ZERO;
+    let mut commands = WorldCommands::default();
+    commands.redo();
+    ctx.apply(commands);
+    assert!(ctx.g.map().buildings().is_empty());
+    assert!(ctx.g.read::<MapHistory>().can_redo());
+
+    ctx.g.write::<Government>().money = start;
+    let mut commands = WorldCommands::default();
+    commands.redo();
+    ctx.apply(commands);
+    assert!(ctx.g.map().buildings().contains_key(house));
+    assert_eq!(ctx.g.read::<Government>().money, built);
+}
+
+#[test]
+fn test_history_is_saved() {
+    let mut ctx = TestCtx::init();
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+
+    let mut commands = WorldCommands::default();
+    commands.map_build_house(lot_near(&ctx, vec2(20.0, 20.0)));
+    ctx.apply(commands);
+
+    // a player joining now gets the history with the world, and undoes the same edit
+    let ser = SerPreparedEgregoria::try_from(&ctx.g).unwrap();
+    let mut joined = TestCtx::init();
+    joined.g = Egregoria::try_from(ser).unwrap();
+    assert!(joined.g.read::<MapHistory>().can_undo());
+    assert_eq!(joined.g.hashes(), ctx.g.hashes());
+
+    for ctx in [&mut ctx, &mut joined] {
+        let mut commands = WorldCommands::default();
+        commands.undo();
+        ctx.apply(commands);
+        assert!(ctx.g.map().buildings().is_empty());
+    }
+    assert_eq!(joined.g.hashes(), ctx.g.hashes());
+}
+
+#[test]
+fn test_undo_keeps_other_changes() {
+    let mut ctx = TestCtx::init();
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+    let road = ctx.g.map().roads().keys().next().unwrap();
+
+    let mut commands = WorldCommands::default();
+    commands.map_build_house(lot_near(&ctx, vec2(20.0, 20.0)));
+    ctx.apply(commands);
+
+    // closing a road isn't an edit, undoing the house leaves it closed
+    let mut commands = WorldCommands::default();
+    commands.map_close_road(road, ClosureKind::Closed, None);
+    ctx.apply(commands);
+
+    let mut commands = WorldCommands::default();
+    commands.undo();
+    ctx.apply(commands);
+
+    assert!(ctx.g.map().buildings().is_empty());
+    let map = ctx.g.map();
+    assert!(map.roads()[road]
+        .lanes_iter()
+        .filter(|(_, kind)| kind.vehicles())
+        .all(|(lane, _)| map.lanes()[lane].is_closed()));
+    drop(map);
+    assert!(!ctx.g.read::<MapHistory>().can_undo());
+}
+
+#[test]
+fn test_failed_edit_is_free() {
+    let mut ctx = TestCtx::init();
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+    let lot = lot_near(&ctx, vec2(20.0, 20.0));
+
+    let mut commands = WorldCommands::default();
+    commands.map_build_house(lot);
+    ctx.apply(commands);
+    let built = ctx.g.read::<Government>().money;
+
+    // the lot is gone, nothing gets built so nothing is paid
+    let mut commands = WorldCommands::default();
+    commands.map_build_house(lot);
+    ctx.apply(commands);
+    assert_eq!(ctx.g.map().buildings().len(), 1);
+    assert_eq!(ctx.g.read::<Government>().money, built);
+}
@@ -1,15 +1,29 @@
 #![allow(dead_code)]
 #![cfg(test)]
 
+use crate::economy::{CommodityKind, Market, Workers};
 use crate::engine_interaction::WorldCommands;
 use crate::map_dynamic::BuildingInfos;
 use crate::utils::scheduler::SeqSchedule;
-use crate::Egregoria;
+use crate::{Egregoria, SoulID};
 use common::logger::MyLog;
 use geom::{Vec2, Vec3};
-use map_model::{BuildingID, LanePatternBuilder};
+use map_model::{BuildingID, IntersectionID, LanePattern, LanePatternBuilder};
 
+mod actuated_signals;
+mod closures;
+mod congestion;
+mod electricity;
+mod green_wave;
+mod households;
+mod lane_change;
+mod map_history;
+mod parking;
+mod population;
+mod terraform;
+mod transit;
 mod vehicles;
+mod zoning;
 
 struct TestCtx {
     pub g: Egregoria,
@@ -27,14 +41,28 @@ impl TestCtx {
     }
 
     fn build_roads(&self, v: &[Vec3]) {
+        self.build_roads_with(v, &LanePatternBuilder::default().build());
+    }
+
+    fn build_roads_with(&self, v: &[Vec3], pattern: &LanePattern) {
         let mut m = self.g.map_mut();
         for w in v.windows(2) {
             let a = m.project(w[0], 0.0).unwrap();
             let b = m.project(w[1], 0.0).unwrap();
-            m.make_connection(a, b, None, &LanePatternBuilder::default().build());
+            m.make_connection(a, b, None, pattern);
         }
     }
 
+    fn inter_at(&self, p: Vec3) -> IntersectionID {
+        self.g
+            .map()
+            .intersections()
+            .values()
+            .find(|inter| inter.pos.is_close(p, 1.0))
+            .unwrap()
+            .id
+    }
+
     fn build_house_near(&self, p: Vec2) -> BuildingID {
         let lot = self
             .g
@@ -50,7 +78,31 @@ impl TestCtx {
         b
     }
 
+    fn build_houses_on_all_lots(&self) -> Vec<BuildingID> {
+        let lots: Vec<_> = self.g.map().lots().keys().collect();
+        lots.into_iter()
+            .flat_map(|lot| {
+                let b = self.g.map_mut().build_house(lot)?;
+                self.g.write::<BuildingInfos>().insert(b);
+                Some(b)
+            })
+            .collect()
+    }
+
+    /// An employer offering jobs near p, it never puts anyone to work
+    fn add_jobs(&mut self, p: Vec2, n: i32) -> SoulID {
+        let employer = SoulID(self.g.world.push((Workers::default(),)));
+        let mut m = self.g.write::<Market>();
+        m.produce(employer, CommodityKind::JobOpening, n);
+        m.sell_all(employer, p, CommodityKind::JobOpening);
+        employer
+    }
+
     fn tick(&mut self) {
         self.g.tick(&mut self.sched, &WorldCommands::default());
     }
+
+    fn apply(&mut self, commands: WorldCommands) {
+        self.g.tick(&mut self.sched, &commands);
+    }
 }
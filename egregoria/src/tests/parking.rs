@@ -0,0 +1,86 @@
+use super::*;
+use crate::map_dynamic::{ParkingManagement, ParkingOccupancy};
+use crate::utils::time::GameTime;
+use geom::{vec2, vec3, OBB};
+use map_model::{BuildingGen, BuildingKind};
+
+#[test]
+fn test_parking_lot() {
+    let mut ctx = TestCtx::init();
+
+    let pattern = LanePatternBuilder::new().parking(false).build();
+    ctx.build_roads_with(&[vec3(0.0, 0.0, 0.0), vec3(200.0, 0.0, 0.0)], &pattern);
+
+    let road = ctx.g.map().roads().keys().next().unwrap();
+    let obb = OBB::new(vec2(100.0, 30.0), Vec2::Y, 40.0, 40.0);
+    let lot = ctx
+        .g
+        .map_mut()
+        .build_special_building(
+            road,
+            &obb,
+            BuildingKind::ParkingLot,
+            BuildingGen::Parking { levels: 1 },
+        )
+        .unwrap();
+
+    let map = ctx.g.map();
+    let capacity = map.parking.building_spots(lot).len();
+    assert!(capacity > 0);
+    let door = map.buildings()[lot].door_pos;
+
+    // without street parking, every car ends up in the lot until it is full
+    let mut pm = ctx.g.write::<ParkingManagement>();
+    let mut reserved = vec![];
+    for _ in 0..capacity {
+        let resa = pm.reserve_near(door, &map).unwrap();
+        assert_eq!(resa.get(&map.parking).unwrap().building, Some(lot));
+        assert!(resa.park_pos(&map).is_some());
+        reserved.push(resa);
+    }
+    assert!(pm.reserve_near(door, &map).is_none());
+    drop(pm);
+    drop(map);
+
+    let now = ctx.g.read::<GameTime>().timestamp;
+    let mut commands = WorldCommands::default();
+    commands.set_game_time(GameTime::new(0.05, now + 9.99));
+    ctx.apply(commands);
+    for _ in 0..5 {
+        ctx.tick();
+    }
+
+    let total = ctx.g.read::<ParkingOccupancy>().total();
+    assert_eq!(total.offstreet_total as usize, capacity);
+    assert_eq!(total.offstreet_used as usize, capacity);
+    assert_eq!(total.street_total, 0);
+}
+
+#[test]
+fn test_reserve_closest() {
+    let mut ctx = TestCtx::init();
+
+    let pattern = LanePatternBuilder::new().parking(false).build();
+    ctx.build_roads_with(&[vec3(0.0, 0.0, 0.0), vec3(1000.0, 0.0, 0.0)], &pattern);
+
+    let road = ctx.g.map().roads().keys().next().unwrap();
+    let obb = OBB::new(vec2(900.0, 30.0), Vec2::Y, 40.0, 40.0);
+    let lot = ctx
+        .g
+        .map_mut()
+        .build_special_building(
+            road,
+            &obb,
+            BuildingKind::ParkingLot,
+            BuildingGen::Parking { levels: 1 },
+        )
+        .unwrap();
+
+    // the lot is too far to be considered, until the driver gives up on parking nearby
+    let map = ctx.g.map();
+    let mut pm = ctx.g.write::<ParkingManagement>();
+    assert!(pm.reserve_near(Vec3::ZERO, &map).is_none());
+    let resa = pm.reserve_closest(Vec3::ZERO, &map).unwrap();
+    assert_eq!(resa.get(&map.parking).unwrap().building, Some(lot));
+    pm.free(resa);
+}
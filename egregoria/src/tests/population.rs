@@ -0,0 +1,71 @@
+use super::*;
+use crate::economy::{CommodityKind, Market, Workers};
+use crate::souls::population::Population;
+use crate::utils::time::GameTime;
+use geom::{vec2, vec3};
+
+#[test]
+fn test_population() {
+    let mut ctx = TestCtx::init();
+
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+    let houses = ctx.build_houses_on_all_lots();
+    let adults = |ctx: &TestCtx| -> Vec<SoulID> {
+        let binfos = ctx.g.read::<BuildingInfos>();
+        houses
+            .iter()
+            .flat_map(|&h| binfos.get(h).unwrap().household.adults.clone())
+            .collect()
+    };
+
+    // nobody moves in without jobs
+    ctx.tick();
+    assert!(adults(&ctx).is_empty());
+
+    let employer = ctx.add_jobs(vec2(150.0, 0.0), 3);
+    for _ in 0..10 {
+        ctx.tick();
+    }
+    let first_adults = adults(&ctx);
+    assert!((3..=4).contains(&first_adults.len()));
+    assert_eq!(
+        ctx.g.comp::<Workers>(employer.0).unwrap().0.len(),
+        first_adults.len().min(3)
+    );
+    assert_eq!(ctx.g.read::<Population>().emigrants, 0);
+
+    // without food nor work, they end up leaving
+    for _ in 0..48 {
+        let now = ctx.g.read::<GameTime>().timestamp;
+        let next_hour = ((now / GameTime::HOUR as f64).floor() + 1.0) * GameTime::HOUR as f64;
+        let mut commands = WorldCommands::default();
+        commands.set_game_time(GameTime::new(0.05, next_hour - 0.01));
+        ctx.apply(commands);
+        ctx.tick();
+    }
+    for _ in 0..10 {
+        ctx.tick();
+    }
+
+    let population = ctx.g.read::<Population>();
+    assert!(population.emigrants as usize >= first_adults.len());
+    drop(population);
+    let newcomers = adults(&ctx);
+    assert!(!newcomers.is_empty());
+
+    let binfos = ctx.g.read::<BuildingInfos>();
+    let workers = &ctx.g.comp::<Workers>(employer.0).unwrap().0;
+    for soul in first_adults {
+        assert!(!ctx.g.world.contains(soul.0));
+        assert!(binfos.home_of(soul).is_none());
+        assert!(binfos.building_owned_by(soul).is_none());
+        assert!(!workers.contains(&soul));
+        assert!(!newcomers.contains(&soul));
+    }
+    drop(binfos);
+
+    // the jobs they left were taken by newcomers
+    let market = ctx.g.read::<Market>();
+    assert_eq!(market.capital(employer, CommodityKind::JobOpening), 0);
+    assert_eq!(workers.len(), 3);
+}
@@ -0,0 +1,63 @@
+use super::*;
+use crate::economy::Government;
+use geom::{vec2, vec3};
+use map_model::Terraform;
+
+#[test]
+fn test_terraform() {
+    let mut ctx = TestCtx::init();
+
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(1000.0, 0.0, 0.0)]);
+    let n_lots = ctx.g.map().lots().len();
+    assert!(n_lots > 0);
+
+    let height = |ctx: &TestCtx, p: Vec2| ctx.g.map().terrain.height(p).unwrap();
+    let dirt = |ctx: &TestCtx| ctx.g.map().terrain.chunks[&(0, 0)].dirt_id;
+    let money = |ctx: &TestCtx| ctx.g.read::<Government>().money;
+
+    // the road would float above the hill, nothing happens and nothing is charged
+    let near = vec2(200.0, 20.0);
+    let h_near = height(&ctx, near);
+    let m0 = money(&ctx);
+    let d0 = dirt(&ctx);
+    let mut commands = WorldCommands::default();
+    commands.map_terraform(near, 100.0, Terraform::Raise(20.0));
+    commands.map_terraform(vec2(200.0, 500.0), 0.0, Terraform::Raise(20.0));
+    commands.map_terraform(vec2(200.0, 500.0), 100.0, Terraform::Raise(f32::NAN));
+    ctx.apply(commands);
+
+    assert_eq!(height(&ctx, near), h_near);
+    assert_eq!(dirt(&ctx), d0);
+    assert_eq!(money(&ctx), m0);
+
+    // a hill grows away from the road
+    let center = vec2(200.0, 300.0);
+    let h0 = height(&ctx, center);
+    let mut commands = WorldCommands::default();
+    commands.map_terraform(center, 100.0, Terraform::Raise(20.0));
+    ctx.apply(commands);
+
+    assert!(height(&ctx, center) > h0 + 10.0);
+    assert_ne!(dirt(&ctx), d0);
+    assert!(money(&ctx) < m0);
+    let map = ctx.g.map();
+    assert_eq!(map.lots().len(), n_lots);
+    assert!(map
+        .lots()
+        .values()
+        .all(|lot| (map.terrain.height(lot.shape.center()).unwrap() - lot.height).abs() < 5.0));
+    drop(map);
+
+    // smoothing takes the top off
+    let before = height(&ctx, center);
+    let mut commands = WorldCommands::default();
+    commands.map_terraform(center, 100.0, Terraform::Smooth);
+    ctx.apply(commands);
+    assert!(height(&ctx, center) < before);
+
+    // flattening brings it back down
+    let mut commands = WorldCommands::default();
+    commands.map_terraform(center, 200.0, Terraform::Flatten(h0));
+    ctx.apply(commands);
+    assert!((height(&ctx, center) - h0).abs() < 5.0);
+}
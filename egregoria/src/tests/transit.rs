@@ -0,0 +1,116 @@
+use super::*;
+use crate::map_dynamic::Itinerary;
+use crate::physics::Collider;
+use crate::transit::{Bus, Transit};
+use crate::utils::time::GameTime;
+use crate::vehicles::{lock, spawn_locked_bike, unpark, Vehicle, VehicleState};
+use geom::vec3;
+use legion::IntoQuery;
+use map_model::{LaneKind, PathKind};
+
+#[test]
+fn test_bus_line() {
+    let mut ctx = TestCtx::init();
+
+    {
+        let mut m = ctx.g.map_mut();
+        let pat = LanePatternBuilder::new().bus_lanes(true).build();
+        let pts = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(200.0, 0.0, 0.0),
+            vec3(200.0, 150.0, 0.0),
+        ];
+        for w in pts.windows(2) {
+            let a = m.project(w[0], 0.0).unwrap();
+            let b = m.project(w[1], 0.0).unwrap();
+            m.make_connection(a, b, None, &pat);
+        }
+        assert!(m.lanes().values().any(|l| l.kind == LaneKind::Bus));
+    }
+
+    let mut commands = WorldCommands::default();
+    commands.add_bus_stop(vec3(100.0, -8.0, 0.0));
+    commands.add_bus_stop(vec3(208.0, 100.0, 0.0));
+    ctx.apply(commands);
+
+    let stops: Vec<_> = ctx.g.read::<Transit>().stops().keys().collect();
+    assert_eq!(stops.len(), 2);
+
+    let mut commands = WorldCommands::default();
+    commands.add_bus_line(stops.clone());
+    ctx.apply(commands);
+
+    let line = ctx
+        .g
+        .read::<Transit>()
+        .lines()
+        .values()
+        .next()
+        .unwrap()
+        .clone();
+    assert_eq!(line.buses.len(), 1);
+    let bus = line.buses[0];
+
+    let ride = ctx
+        .g
+        .read::<Transit>()
+        .best_ride(vec3(90.0, -10.0, 0.0), vec3(210.0, 110.0, 0.0))
+        .unwrap();
+    assert_eq!(ride.from, stops[0]);
+    assert_eq!(ride.to, stops[1]);
+
+    for _ in 0..3000 {
+        ctx.tick();
+        if ctx.g.comp::<Bus>(bus.0).unwrap().is_at(stops[1]) {
+            let mut commands = WorldCommands::default();
+            commands.remove_bus_stop(stops[1]);
+            ctx.apply(commands);
+            ctx.tick();
+
+            assert!(ctx.g.read::<Transit>().lines().is_empty());
+            assert_eq!(<&Vehicle>::query().iter(ctx.g.world()).count(), 0);
+            return;
+        }
+    }
+
+    panic!("bus has not reached the second stop after 3000 ticks")
+}
+
+#[test]
+fn test_bike_ride() {
+    let mut ctx = TestCtx::init();
+
+    {
+        let mut m = ctx.g.map_mut();
+        let pat = LanePatternBuilder::new().bike_lanes(true).build();
+        let a = m.project(vec3(0.0, 0.0, 0.0), 0.0).unwrap();
+        let b = m.project(vec3(200.0, 0.0, 0.0), 0.0).unwrap();
+        m.make_connection(a, b, None, &pat);
+        assert!(m.lanes().values().any(|l| l.kind == LaneKind::Biking));
+    }
+
+    let bike = spawn_locked_bike(&mut ctx.g, vec3(10.0, -5.0, 0.0));
+    assert!(ctx.g.comp::<Collider>(bike.0).is_none());
+    unpark(&mut ctx.g, bike);
+    ctx.tick();
+
+    let end = vec3(190.0, -5.0, 0.0);
+    let itin = Itinerary::route(vec3(10.0, -5.0, 0.0), end, &ctx.g.map(), PathKind::Bike).unwrap();
+    *ctx.g.comp_mut::<Itinerary>(bike.0).unwrap() = itin;
+
+    for _ in 0..3000 {
+        ctx.tick();
+        let g = &mut ctx.g;
+        let time = g.read::<GameTime>().timestamp;
+        if g.comp::<Itinerary>(bike.0).unwrap().has_ended(time) {
+            lock(g, bike);
+            ctx.tick();
+            let v = ctx.g.comp::<Vehicle>(bike.0).unwrap();
+            assert!(matches!(v.state, VehicleState::Locked));
+            assert!(ctx.g.comp::<Collider>(bike.0).is_none());
+            return;
+        }
+    }
+
+    panic!("bike has not arrived after 3000 ticks")
+}
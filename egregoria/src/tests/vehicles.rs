@@ -55,7 +55,7 @@ fn test_router_and_back() {
     ctx.build_roads(&[vec2(0.0, 0.0), vec2(100.0, 0.0), vec2(100.0, 50.0)]);
 
     let b1 = ctx.build_house_near(vec2(0.0, 0.0));
-    let human = spawn_human(&mut ctx.g, b1).unwrap();
+    let human = spawn_human(&mut ctx.g, b1, true).unwrap();
 
     ctx.g
         .write::<ParCommandBuffer>()
@@ -108,7 +108,7 @@ fn test_router_and_back_change_middle() {
     ctx.build_roads(&[vec2(0.0, 0.0), vec2(100.0, 0.0), vec2(100.0, 50.0)]);
 
     let b1 = ctx.build_house_near(vec2(0.0, 0.0));
-    let human = spawn_human(&mut ctx.g, b1).unwrap();
+    let human = spawn_human(&mut ctx.g, b1, true).unwrap();
 
     ctx.g
         .write::<ParCommandBuffer>()
@@ -0,0 +1,69 @@
+use super::*;
+use crate::economy::{CommodityKind, Market, ZoneDemand};
+use crate::souls::goods_company::{GoodsCompany, GoodsCompanyRegistry};
+use crate::utils::time::GameTime;
+use geom::{vec2, vec3};
+use legion::IntoQuery;
+use map_model::{BuildingKind, LotKind};
+
+#[test]
+fn test_zoning() {
+    let mut ctx = TestCtx::init();
+
+    ctx.build_roads(&[vec3(0.0, 0.0, 0.0), vec3(300.0, 0.0, 0.0)]);
+    let lots: Vec<_> = ctx.g.map().lots().keys().collect();
+    let mut commands = WorldCommands::default();
+    for &lot in &lots {
+        commands.map_set_lot_kind(lot, LotKind::Industrial);
+    }
+    ctx.apply(commands);
+    assert!(ctx
+        .g
+        .map()
+        .lots()
+        .values()
+        .all(|lot| lot.kind == LotKind::Industrial));
+
+    // a company can't find iron ore
+    let buyer = SoulID(ctx.g.world.push(()));
+    {
+        let mut m = ctx.g.write::<Market>();
+        let price = m.bid_price(CommodityKind::IronOre);
+        m.buy(buyer, vec2(150.0, 0.0), CommodityKind::IronOre, 5, price);
+    }
+
+    let now = ctx.g.read::<GameTime>().timestamp;
+    let next_hour = ((now / GameTime::HOUR as f64).floor() + 1.0) * GameTime::HOUR as f64;
+    let mut commands = WorldCommands::default();
+    commands.set_game_time(GameTime::new(0.05, next_hour - 0.01));
+    ctx.apply(commands);
+    ctx.tick();
+
+    let demand = ctx.g.read::<ZoneDemand>().clone();
+    assert_eq!(demand.industrial.most_wanted, Some(CommodityKind::IronOre));
+    assert_eq!(demand.industrial.total, 5);
+    assert_eq!(demand.commercial.total, 0);
+
+    // an iron mine grew on the zone and got a company
+    let registry = ctx.g.read::<GoodsCompanyRegistry>();
+    let map = ctx.g.map();
+    let grown: Vec<_> = map
+        .buildings()
+        .values()
+        .filter(|b| b.kind != BuildingKind::House)
+        .collect();
+    assert_eq!(grown.len(), 1);
+    let des = &registry.descriptions[&grown[0].kind];
+    assert!(des.provides(CommodityKind::IronOre));
+    assert_eq!(des.zone(), Some(LotKind::Industrial));
+    assert!(map.lots().len() < lots.len());
+    let building = grown[0].id;
+    drop(map);
+    drop(registry);
+
+    let companies: Vec<_> = <&GoodsCompany>::query()
+        .iter(&ctx.g.world)
+        .map(|c| c.building)
+        .collect();
+    assert_eq!(companies, vec![building]);
+}
@@ -0,0 +1,317 @@
+use crate::map_dynamic::Itinerary;
+use crate::utils::time::GameInstant;
+use crate::vehicles::{make_vehicle_entity, Vehicle, VehicleID, VehicleKind};
+use crate::{Egregoria, ParCommandBuffer};
+use geom::{Color, Transform, Vec3};
+use imgui_inspect_derive::Inspect;
+use map_model::{LaneKind, LanePatternBuilder, Map};
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use slotmap::{new_key_type, SlotMap};
+
+new_key_type! {
+    pub struct BusStopID;
+    pub struct BusLineID;
+}
+
+debug_inspect_impl!(BusLineID);
+
+/// Maximum distance between the requested position and the road for a stop to be placed
+const MAX_STOP_DIST: f32 = 15.0;
+/// How long a bus waits at each stop, in seconds
+pub const BUS_DWELL_TIME: f64 = 15.0;
+/// One bus is spawned for every `STOPS_PER_BUS` stops of a line
+const STOPS_PER_BUS: usize = 3;
+
+const BUS_COLOR: Color = Color {
+    r: 0.9,
+    g: 0.7,
+    b: 0.1,
+    a: 1.0,
+};
+
+// Constants used to estimate travel times when choosing how to get somewhere
+const WALK_SPEED: f32 = 1.34;
+const CAR_SPEED: f32 = LanePatternBuilder::new().speed_limit;
+const BUS_SPEED: f32 = CAR_SPEED * 0.8;
+/// Roads are not straight lines
+const DETOUR_FACTOR: f32 = 1.3;
+/// Time spent getting in the car, unparking and parking
+const CAR_OVERHEAD: f32 = 30.0;
+const BIKE_SPEED: f32 = 5.0;
+/// Time spent unlocking and locking the bike
+const BIKE_OVERHEAD: f32 = 10.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusStop {
+    /// Where buses stop, on the road
+    pub pos: Vec3,
+    pub dir: Vec3,
+    /// Where passengers wait, on the sidewalk
+    pub wait_pos: Vec3,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusLine {
+    /// Buses go through the stops in order then loop back to the first one
+    pub stops: Vec<BusStopID>,
+    pub buses: Vec<VehicleID>,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum BusState {
+    Driving,
+    AtStop(BusStopID, GameInstant),
+}
+
+debug_inspect_impl!(BusState);
+
+#[derive(Debug, Serialize, Deserialize, Inspect)]
+pub struct Bus {
+    pub line: BusLineID,
+    /// Index in the line of the stop the bus is going to or waiting at
+    pub next_stop: usize,
+    pub state: BusState,
+}
+
+impl Bus {
+    pub fn is_at(&self, stop: BusStopID) -> bool {
+        matches!(self.state, BusState::AtStop(s, _) if s == stop)
+    }
+}
+
+/// A trip by bus, as estimated by `Transit::best_ride`
+#[derive(Debug, Copy, Clone)]
+pub struct Ride {
+    pub line: BusLineID,
+    pub from: BusStopID,
+    pub to: BusStopID,
+    /// Estimated duration of the whole trip in seconds, walking included
+    pub time: f32,
+}
+
+register_resource!(Transit, "transit");
+#[derive(Default, Serialize, Deserialize)]
+pub struct Transit {
+    stops: SlotMap<BusStopID, BusStop>,
+    lines: SlotMap<BusLineID, BusLine>,
+}
+
+pub fn walk_time(from: Vec3, to: Vec3) -> f32 {
+    from.distance(to) * DETOUR_FACTOR / WALK_SPEED
+}
+
+/// Time to walk to the car, then drive to the destination
+pub fn drive_time(from: Vec3, car: Vec3, to: Vec3) -> f32 {
+    walk_time(from, car) + car.distance(to) * DETOUR_FACTOR / CAR_SPEED + CAR_OVERHEAD
+}
+
+/// Time to walk to the bike, then ride to the destination
+pub fn bike_time(from: Vec3, bike: Vec3, to: Vec3) -> f32 {
+    walk_time(from, bike) + bike.distance(to) * DETOUR_FACTOR / BIKE_SPEED + BIKE_OVERHEAD
+}
+
+impl Transit {
+    pub fn stops(&self) -> &SlotMap<BusStopID, BusStop> {
+        &self.stops
+    }
+
+    pub fn lines(&self) -> &SlotMap<BusLineID, BusLine> {
+        &self.lines
+    }
+
+    /// Whether the stop is still part of the line
+    pub fn serves(&self, line: BusLineID, stop: BusStopID) -> bool {
+        self.lines
+            .get(line)
+            .map(|l| l.stops.contains(&stop))
+            .unwrap_or(false)
+    }
+
+    pub fn next_stop(&self, bus: &Bus) -> Option<(BusStopID, &BusStop)> {
+        let line = self.lines.get(bus.line)?;
+        let id = *line.stops.get(bus.next_stop % line.stops.len().max(1))?;
+        Some((id, self.stops.get(id)?))
+    }
+
+    pub fn add_stop(&mut self, map: &Map, pos: Vec3) -> Option<BusStopID> {
+        let stop = Self::place_stop(map, pos)?;
+        Some(self.stops.insert(stop))
+    }
+
+    /// Places a stop on the outermost bus or driving lane of the road closest to pos
+    pub fn place_stop(map: &Map, pos: Vec3) -> Option<BusStop> {
+        let lanes = map.lanes();
+        let (_, nearest) = lanes
+            .iter()
+            .filter(|(_, l)| matches!(l.kind, LaneKind::Driving | LaneKind::Bus))
+            .min_by_key(|(_, l)| OrderedFloat(l.points.project_dist2(pos)))?;
+        if nearest.points.project_dist(pos) > MAX_STOP_DIST {
+            return None;
+        }
+
+        let road = map.roads().get(nearest.parent)?;
+        let outgoing = road.outgoing_lanes_from(nearest.src);
+        let &(lane, _) = outgoing
+            .iter()
+            .rfind(|&&(_, kind)| kind == LaneKind::Bus)
+            .or_else(|| {
+                outgoing
+                    .iter()
+                    .rfind(|&&(_, kind)| kind == LaneKind::Driving)
+            })?;
+        let (pos, _, dir) = lanes.get(lane)?.points.project_segment_dir(pos);
+
+        let wait_pos = outgoing
+            .iter()
+            .rfind(|&&(_, kind)| kind == LaneKind::Walking)
+            .and_then(|&(id, _)| lanes.get(id))
+            .map(|l| l.points.project(pos))
+            .unwrap_or(pos);
+
+        Some(BusStop { pos, dir, wait_pos })
+    }
+
+    pub fn valid_line(&self, stops: &[BusStopID]) -> bool {
+        stops.len() >= 2 && stops.iter().all(|&s| self.stops.contains_key(s))
+    }
+
+    /// Estimates the fastest trip by bus between start and end, if any
+    pub fn best_ride(&self, start: Vec3, end: Vec3) -> Option<Ride> {
+        self.lines
+            .iter()
+            .filter_map(|(id, line)| self.ride_on(id, line, start, end))
+            .min_by_key(|r| OrderedFloat(r.time))
+    }
+
+    fn ride_on(&self, id: BusLineID, line: &BusLine, start: Vec3, end: Vec3) -> Option<Ride> {
+        if line.buses.is_empty() {
+            return None;
+        }
+        let stops = line
+            .stops
+            .iter()
+            .map(|&s| self.stops.get(s))
+            .collect::<Option<Vec<_>>>()?;
+        let n = stops.len();
+
+        let nearest = |p: Vec3| {
+            stops
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, s)| OrderedFloat(s.wait_pos.distance2(p)))
+                .map(|(i, _)| i)
+        };
+        let i_from = nearest(start)?;
+        let i_to = nearest(end)?;
+        if i_from == i_to {
+            return None;
+        }
+
+        let hop = |i: usize| {
+            stops[i].pos.distance(stops[(i + 1) % n].pos) * DETOUR_FACTOR / BUS_SPEED
+                + BUS_DWELL_TIME as f32
+        };
+
+        let cycle: f32 = (0..n).map(hop).sum();
+        let mut ride = 0.0;
+        let mut i = i_from;
+        while i != i_to {
+            ride += hop(i);
+            i = (i + 1) % n;
+        }
+        let wait = cycle / (2.0 * line.buses.len() as f32);
+
+        Some(Ride {
+            line: id,
+            from: line.stops[i_from],
+            to: line.stops[i_to],
+            time: walk_time(start, stops[i_from].wait_pos)
+                + wait
+                + ride
+                + walk_time(stops[i_to].wait_pos, end),
+        })
+    }
+}
+
+pub fn n_buses(n_stops: usize) -> usize {
+    (n_stops / STOPS_PER_BUS).max(1)
+}
+
+/// Creates a line going through the stops and spawns its buses, spread evenly along it
+pub fn spawn_bus_line(goria: &mut Egregoria, stops: Vec<BusStopID>) -> Option<BusLineID> {
+    let mut transit = goria.write::<Transit>();
+    if !transit.valid_line(&stops) {
+        log::warn!("not creating bus line with invalid stops {:?}", stops);
+        return None;
+    }
+
+    let n = n_buses(stops.len());
+    let starts: Vec<(usize, Transform)> = (0..n)
+        .map(|k| {
+            let idx = k * stops.len() / n;
+            let stop = &transit.stops[stops[idx]];
+            (idx, Transform::new_dir(stop.pos, stop.dir))
+        })
+        .collect();
+
+    let id = transit.lines.insert(BusLine {
+        stops,
+        buses: vec![],
+    });
+    drop(transit);
+
+    let buses = starts
+        .into_iter()
+        .map(|(next_stop, trans)| {
+            let e = make_vehicle_entity(
+                goria,
+                trans,
+                Vehicle::driving(VehicleKind::Bus, BUS_COLOR),
+                Itinerary::none(),
+                true,
+            );
+            goria.add_comp(
+                e,
+                Bus {
+                    line: id,
+                    next_stop,
+                    state: BusState::Driving,
+                },
+            );
+            VehicleID(e)
+        })
+        .collect();
+
+    goria.write::<Transit>().lines[id].buses = buses;
+    Some(id)
+}
+
+pub fn remove_bus_line(goria: &mut Egregoria, id: BusLineID) {
+    let line = unwrap_ret!(goria.write::<Transit>().lines.remove(id));
+    let cbuf = goria.read::<ParCommandBuffer>();
+    for bus in line.buses {
+        cbuf.kill(bus.0);
+    }
+}
+
+/// Removes the stop from every line going through it, lines left with less than two stops are removed
+pub fn remove_bus_stop(goria: &mut Egregoria, id: BusStopID) {
+    let mut transit = goria.write::<Transit>();
+    if transit.stops.remove(id).is_none() {
+        return;
+    }
+
+    let mut to_remove = vec![];
+    for (line_id, line) in &mut transit.lines {
+        line.stops.retain(|&s| s != id);
+        if line.stops.len() < 2 {
+            to_remove.push(line_id);
+        }
+    }
+    drop(transit);
+
+    for line in to_remove {
+        remove_bus_line(goria, line);
+    }
+}
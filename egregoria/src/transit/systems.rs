@@ -0,0 +1,56 @@
+use crate::map_dynamic::Itinerary;
+use crate::transit::{Bus, BusState, Transit, BUS_DWELL_TIME};
+use crate::utils::time::GameTime;
+use crate::ParCommandBuffer;
+use geom::Transform;
+use legion::system;
+use legion::Entity;
+use map_model::PathKind;
+
+/// Distance from the stop under which a bus that finished its route is considered arrived
+const STOP_OK_DIST: f32 = 10.0;
+
+register_system!(bus_update);
+/// Makes buses go from stop to stop, waiting a bit at each one for passengers
+#[system(par_for_each)]
+pub fn bus_update(
+    #[resource] transit: &Transit,
+    #[resource] time: &GameTime,
+    #[resource] cbuf: &ParCommandBuffer,
+    me: &Entity,
+    trans: &Transform,
+    bus: &mut Bus,
+    it: &mut Itinerary,
+) {
+    let n_stops = unwrap_or!(transit.lines().get(bus.line), {
+        cbuf.kill(*me);
+        return;
+    })
+    .stops
+    .len();
+
+    let (stop_id, stop) = unwrap_ret!(transit.next_stop(bus));
+
+    match bus.state {
+        BusState::Driving => {
+            if !it.has_ended(time.timestamp) {
+                return;
+            }
+            if trans.position.is_close(stop.pos, STOP_OK_DIST) {
+                bus.state = BusState::AtStop(stop_id, time.instant());
+            } else {
+                *it = Itinerary::wait_for_reroute(PathKind::Bus, stop.pos);
+            }
+        }
+        BusState::AtStop(_, since) => {
+            if since.elapsed(time) < BUS_DWELL_TIME {
+                return;
+            }
+            bus.next_stop = (bus.next_stop + 1) % n_stops;
+            bus.state = BusState::Driving;
+            if let Some((_, next)) = transit.next_stop(bus) {
+                *it = Itinerary::wait_for_reroute(PathKind::Bus, next.pos);
+            }
+        }
+    }
+}
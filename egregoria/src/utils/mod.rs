@@ -2,6 +2,7 @@ use crate::Egregoria;
 
 pub mod par_command_buffer;
 pub mod rand_provider;
+pub mod replay;
 pub mod scheduler;
 pub mod time;
 
@@ -0,0 +1,97 @@
+use crate::engine_interaction::WorldCommands;
+use crate::{Egregoria, SerPreparedEgregoria};
+use common::saveload::{CompressedBincode, Encoder};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// A starting world and every non-empty batch of commands applied to it since.
+/// Since the simulation is deterministic, this is enough to reproduce a whole session.
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    start: SerPreparedEgregoria,
+    /// (tick before applying, commands), sorted by tick
+    commands: Vec<(u32, WorldCommands)>,
+}
+
+pub struct ReplayRecorder {
+    replay: Replay,
+}
+
+impl ReplayRecorder {
+    /// Starts a recording from the current state of the world
+    pub fn new(goria: &Egregoria) -> Option<Self> {
+        let start = SerPreparedEgregoria::try_from(goria)
+            .map_err(|e| log::error!("couldn't start recording: {}", e))
+            .ok()?;
+        Some(Self {
+            replay: Replay {
+                start,
+                commands: vec![],
+            },
+        })
+    }
+
+    /// Must be called with the world tick *before* calling `Egregoria::tick` with the commands
+    pub fn record(&mut self, tick: u32, commands: &WorldCommands) {
+        if commands.is_empty() {
+            return;
+        }
+        self.replay.commands.push((tick, commands.clone()));
+    }
+
+    pub fn n_recorded(&self) -> usize {
+        self.replay.commands.len()
+    }
+
+    pub fn save(&self, name: &'static str) {
+        CompressedBincode::save(&self.replay, name);
+    }
+}
+
+pub struct ReplayPlayer {
+    commands: Vec<(u32, WorldCommands)>,
+    idx: usize,
+}
+
+impl ReplayPlayer {
+    /// Loads the replay and the world it starts from
+    pub fn load(name: &'static str) -> Option<(Egregoria, Self)> {
+        let replay: Replay = CompressedBincode::load(name)?;
+        let goria = Egregoria::try_from(replay.start)
+            .map_err(|e| log::error!("couldn't load replay start: {}", e))
+            .ok()?;
+        Some((
+            goria,
+            Self {
+                commands: replay.commands,
+                idx: 0,
+            },
+        ))
+    }
+
+    /// Returns the commands to pass to `Egregoria::tick` when the world is at `tick`
+    pub fn next(&mut self, tick: u32) -> WorldCommands {
+        let mut commands = WorldCommands::default();
+        while let Some((t, c)) = self.commands.get(self.idx) {
+            if *t > tick {
+                break;
+            }
+            if *t < tick {
+                log::warn!("replay skipped commands from tick {} (now at {})", t, tick);
+            } else {
+                commands.merge(c);
+            }
+            self.idx += 1;
+        }
+        commands
+    }
+
+    /// The tick of the last recorded commands, if any
+    pub fn last_tick(&self) -> Option<u32> {
+        self.commands.last().map(|(t, _)| *t)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.idx >= self.commands.len()
+    }
+}
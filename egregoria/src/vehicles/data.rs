@@ -4,12 +4,13 @@ use crate::physics::{Collider, CollisionWorld, Kinematics, PhysicsGroup, Physics
 use crate::utils::par_command_buffer::ComponentDrop;
 use crate::utils::rand_provider::RandProvider;
 use crate::utils::time::GameInstant;
-use crate::Egregoria;
+use crate::{Egregoria, ParCommandBuffer};
 use geom::Transform;
 use geom::{Color, Spline3, Vec3};
 use imgui_inspect::InspectDragf;
 use imgui_inspect_derive::Inspect;
 use legion::{Entity, Resources};
+use map_model::PathKind;
 use serde::{Deserialize, Serialize};
 
 /// The duration for the parking animation.
@@ -28,6 +29,9 @@ pub enum VehicleState {
     /// Panicked when it notices it's in a gridlock
     Panicking(GameInstant),
     RoadToPark(Spline3, f32, SpotReservation),
+    /// Bikes are locked wherever their rider gets off, without needing a parking spot.
+    /// Cars are too when their driver gave up on finding a spot
+    Locked,
 }
 
 debug_inspect_impl!(VehicleState);
@@ -37,6 +41,7 @@ pub enum VehicleKind {
     Car,
     Truck,
     Bus,
+    Bike,
 }
 
 #[derive(Debug, Serialize, Deserialize, Inspect)]
@@ -83,6 +88,7 @@ impl VehicleKind {
             VehicleKind::Car => 4.5,
             VehicleKind::Truck => 6.0,
             VehicleKind::Bus => 9.0,
+            VehicleKind::Bike => 2.0,
         }
     }
 
@@ -91,12 +97,14 @@ impl VehicleKind {
             VehicleKind::Car => 3.0,
             VehicleKind::Truck => 2.5,
             VehicleKind::Bus => 2.0,
+            VehicleKind::Bike => 1.5,
         }
     }
 
     pub fn deceleration(self) -> f32 {
         match self {
             VehicleKind::Car | VehicleKind::Bus | VehicleKind::Truck => 11.0,
+            VehicleKind::Bike => 6.0,
         }
     }
 
@@ -105,21 +113,39 @@ impl VehicleKind {
             VehicleKind::Car => 2.0,
             VehicleKind::Truck => 3.0,
             VehicleKind::Bus => 4.0,
+            VehicleKind::Bike => 1.0,
         }
     }
 
     pub fn speed_factor(self) -> f32 {
         match self {
-            VehicleKind::Car => 1.0,
+            VehicleKind::Car | VehicleKind::Bike => 1.0,
             VehicleKind::Truck | VehicleKind::Bus => 0.8,
         }
     }
 
+    /// Speed that can't be exceeded whatever the speed limit
+    pub fn max_speed(self) -> f32 {
+        match self {
+            VehicleKind::Bike => 6.0,
+            VehicleKind::Car | VehicleKind::Truck | VehicleKind::Bus => f32::INFINITY,
+        }
+    }
+
+    pub fn path_kind(self) -> PathKind {
+        match self {
+            VehicleKind::Car | VehicleKind::Truck => PathKind::Vehicle,
+            VehicleKind::Bus => PathKind::Bus,
+            VehicleKind::Bike => PathKind::Bike,
+        }
+    }
+
     pub fn ang_acc(self) -> f32 {
         match self {
             VehicleKind::Car => 1.0,
             VehicleKind::Truck => 0.9,
             VehicleKind::Bus => 0.8,
+            VehicleKind::Bike => 1.5,
         }
     }
 }
@@ -128,16 +154,41 @@ pub fn unpark(goria: &mut Egregoria, vehicle: VehicleID) {
     let v = unwrap_ret!(goria.comp_mut::<Vehicle>(vehicle.0));
     let w = v.kind.width();
 
-    if let VehicleState::Parked(spot) = std::mem::replace(&mut v.state, VehicleState::Driving) {
-        goria.write::<ParkingManagement>().free(spot);
-    } else {
-        log::warn!("Trying to unpark {:?} that wasn't parked", vehicle);
+    match std::mem::replace(&mut v.state, VehicleState::Driving) {
+        VehicleState::Parked(spot) => goria.write::<ParkingManagement>().free(spot),
+        VehicleState::Locked => {}
+        _ => log::warn!("Trying to unpark {:?} that wasn't parked", vehicle),
     }
 
     let coll = put_vehicle_in_coworld(goria, w, *unwrap_ret!(goria.comp::<Transform>(vehicle.0)));
     goria.add_comp(vehicle.0, coll);
 }
 
+/// Moves the vehicle parked (or parking) on a spot that disappeared to a free spot nearby.
+/// It stays where it is if there is none.
+pub fn repark(goria: &mut Egregoria, vehicle: VehicleID) {
+    let pos = unwrap_ret!(goria.comp::<Transform>(vehicle.0)).position;
+    let map = goria.map();
+    let resa = unwrap_ret!(goria.write::<ParkingManagement>().reserve_near(pos, &map));
+    let trans = resa.get(&map.parking).map(|spot| spot.trans);
+    drop(map);
+
+    let v = unwrap_ret!(goria.comp_mut::<Vehicle>(vehicle.0));
+    let old = std::mem::replace(&mut v.state, VehicleState::Parked(resa));
+    let was_parking = matches!(old, VehicleState::RoadToPark(..));
+    if let VehicleState::Parked(old) | VehicleState::RoadToPark(_, _, old) = old {
+        goria.write::<ParkingManagement>().free(old);
+    }
+    if let (Some(trans), Some(t)) = (trans, goria.comp_mut::<Transform>(vehicle.0)) {
+        *t = trans;
+    }
+    if was_parking {
+        goria
+            .read::<ParCommandBuffer>()
+            .remove_component_drop::<Collider>(vehicle.0);
+    }
+}
+
 pub fn spawn_parked_vehicle(
     goria: &mut Egregoria,
     kind: VehicleKind,
@@ -170,6 +221,32 @@ pub fn spawn_parked_vehicle(
     )))
 }
 
+/// Spawns a locked bike at the given position, it can then be ridden by unparking it
+pub fn spawn_locked_bike(goria: &mut Egregoria, pos: Vec3) -> VehicleID {
+    let tint = get_random_car_color(&mut *goria.write::<RandProvider>());
+    let vehicle = Vehicle {
+        state: VehicleState::Locked,
+        ..Vehicle::driving(VehicleKind::Bike, tint)
+    };
+    VehicleID(make_vehicle_entity(
+        goria,
+        Transform::new(pos),
+        vehicle,
+        Itinerary::none(),
+        false,
+    ))
+}
+
+/// Stops the vehicle and locks it where it is, it must be a bike
+pub fn lock(goria: &mut Egregoria, vehicle: VehicleID) {
+    goria
+        .read::<ParCommandBuffer>()
+        .remove_component_drop::<Collider>(vehicle.0);
+    unwrap_ret!(goria.comp_mut::<Vehicle>(vehicle.0)).state = VehicleState::Locked;
+    unwrap_ret!(goria.comp_mut::<Kinematics>(vehicle.0)).velocity = Vec3::ZERO;
+    *unwrap_ret!(goria.comp_mut::<Itinerary>(vehicle.0)) = Itinerary::none();
+}
+
 pub fn make_vehicle_entity(
     goria: &mut Egregoria,
     trans: Transform,
@@ -232,6 +309,18 @@ impl Vehicle {
             flag: 0,
         }
     }
+
+    /// A vehicle spawned directly on the road, without a parking spot
+    pub fn driving(kind: VehicleKind, tint: Color) -> Vehicle {
+        Self {
+            ang_velocity: 0.0,
+            wait_time: 0.0,
+            state: VehicleState::Driving,
+            kind,
+            tint,
+            flag: 0,
+        }
+    }
 }
 
 debug_inspect_impl!(VehicleKind);
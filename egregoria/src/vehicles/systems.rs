@@ -7,7 +7,12 @@ use crate::ParCommandBuffer;
 use geom::{angle_lerpxy, Ray, Transform, Vec2, Vec3};
 use legion::system;
 use legion::Entity;
-use map_model::{Map, TrafficBehavior, Traversable, TraverseKind};
+use map_model::{Lane, Map, TrafficBehavior, Traversable, TraverseKind};
+
+/// Room needed between two vehicles on top of their size to merge between them, in meters
+const MERGE_GAP: f32 = 3.0;
+/// Time kept between a merging vehicle and the vehicles going faster behind it, in seconds
+const MERGE_HEADWAY: f32 = 1.5;
 
 register_system!(vehicle_decision);
 #[system(par_for_each)]
@@ -39,6 +44,22 @@ pub fn vehicle_decision(
         let (s, d) = calc_decision(*me, vehicle, map, time, trans, self_obj, it, objs);
         desired_speed = s;
         desired_dir = d;
+
+        if let Some(Traversable {
+            kind: TraverseKind::LaneChange { to, .. },
+            ..
+        }) = it.get_travers()
+        {
+            if let Some(lane) = map.lanes().get(*to) {
+                let neighbors = cow
+                    .query_around(trans.position.xy(), 12.0 + danger_length)
+                    .filter(|&(id, _)| id != collider.0)
+                    .map(|(id, pos)| (pos, cow.get(id).expect("Handle not in collision world").1));
+                if can_merge(lane, trans, self_obj, neighbors) {
+                    it.change_lane(map, trans.position);
+                }
+            }
+        }
     }
 
     physics(
@@ -209,7 +230,7 @@ pub fn calc_decision<'a>(
     let mut speed = 9.0;
 
     if let Some(Traversable {
-        kind: TraverseKind::Lane(l_id),
+        kind: TraverseKind::Lane(l_id) | TraverseKind::LaneChange { to: l_id, .. },
         ..
     }) = it.get_travers()
     {
@@ -249,7 +270,47 @@ pub fn calc_decision<'a>(
         return (6.0, dir_to_pos);
     }
 
-    (vehicle.kind.speed_factor() * speed, dir_to_pos)
+    (
+        (vehicle.kind.speed_factor() * speed).min(vehicle.kind.max_speed()),
+        dir_to_pos,
+    )
+}
+
+/// Whether there is enough room ahead and behind on the lane to merge into it
+fn can_merge<'a>(
+    lane: &Lane,
+    trans: &Transform,
+    self_obj: &PhysicsObject,
+    neighs: impl Iterator<Item = (Vec2, &'a PhysicsObject)>,
+) -> bool {
+    let position = trans.position;
+    let dir2 = trans.dir.xy();
+    let half_width = lane.kind.width() * 0.5;
+
+    for (his_pos, his_obj) in neighs {
+        if !matches!(his_obj.group, PhysicsGroup::Vehicles)
+            || (his_obj.height - position.z).abs() > 5.0
+        {
+            continue;
+        }
+        let proj = lane.points.project(his_pos.z(position.z));
+        if !proj.xy().is_close(his_pos, half_width) {
+            continue;
+        }
+
+        let along = (his_pos - position.xy()).dot(dir2);
+        let closing_speed = if along > 0.0 {
+            self_obj.speed - his_obj.speed
+        } else {
+            his_obj.speed - self_obj.speed
+        };
+        let needed =
+            self_obj.radius + his_obj.radius + MERGE_GAP + closing_speed.max(0.0) * MERGE_HEADWAY;
+        if along.abs() < needed {
+            return false;
+        }
+    }
+    true
 }
 
 /// Calculates the distance to the closest problematic object in front of the car.
@@ -25,15 +25,18 @@
 use common::logger::MyLog;
 use common::unwrap_or;
 use egregoria::engine_interaction::WorldCommands;
+use egregoria::utils::replay::ReplayRecorder;
 use egregoria::{Egregoria, SerPreparedEgregoria};
 use networking::{Frame, Server, ServerConfiguration, ServerPollResult};
 use std::convert::TryFrom;
 use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
+mod simulate;
+
 #[derive(StructOpt, Debug)]
 #[structopt(name = "Egregoria headless", no_version, author = "by Uriopass")]
-struct Opt {
+pub struct Opt {
     /// Optional server port
     #[structopt(long)]
     port: Option<u16>,
@@ -50,12 +53,34 @@ struct Opt {
     /// i.e. 20ms = 50FPS
     #[structopt(long, default_value = "20")]
     timestep: u64,
+
+    /// Run the given number of ticks as fast as possible without networking, then
+    /// write the final save, the system timings and the world hashes to disk
+    #[structopt(long)]
+    simulate: Option<u32>,
+
+    /// Map to generate when no save is found in simulate mode: "empty", "paris" or "testfield"
+    #[structopt(long, default_value = "empty")]
+    preset: simulate::Preset,
+
+    /// Record every applied command to world/replay.zip
+    #[structopt(long)]
+    record: bool,
+
+    /// Play back world/replay.zip in simulate mode
+    #[structopt(long)]
+    replay: bool,
 }
 
 fn main() {
     let opt: Opt = Opt::from_args();
     MyLog::init();
 
+    if opt.simulate.is_some() || opt.replay {
+        simulate::simulate(&opt);
+        return;
+    }
+
     log::info!("starting server with version: {}", goria_version::VERSION);
 
     let mut w = unwrap_or!(Egregoria::load_from_disk("world"), {
@@ -84,6 +109,12 @@ fn main() {
 
     let mut last_saved = Instant::now();
 
+    let mut recorder = if opt.record {
+        ReplayRecorder::new(&w)
+    } else {
+        None
+    };
+
     loop {
         if let ServerPollResult::Input(inputs) = server.poll(
             &|| {
@@ -97,12 +128,19 @@ fn main() {
             for frame in inputs {
                 assert_eq!(frame.frame.0, w.get_tick() + 1);
                 let merged = frame.inputs.into_iter().map(|x| x.inp).collect();
+                if let Some(ref mut r) = recorder {
+                    r.record(w.get_tick(), &merged);
+                }
                 w.tick(&mut sched, &merged);
+                server.check_hashes(Frame(w.get_tick()), || w.hashes());
             }
         }
 
         if last_saved.elapsed().as_secs() > opt.autosave {
             w.save_to_disk("world");
+            if let Some(ref r) = recorder {
+                r.save("replay");
+            }
             last_saved = Instant::now();
         }
 
@@ -0,0 +1,127 @@
+use crate::Opt;
+use common::saveload::{Encoder, JSON};
+use common::unwrap_or;
+use egregoria::engine_interaction::WorldCommands;
+use egregoria::utils::replay::{ReplayPlayer, ReplayRecorder};
+use egregoria::Egregoria;
+use std::str::FromStr;
+use std::time::Instant;
+
+#[derive(Debug, Copy, Clone)]
+pub enum Preset {
+    Empty,
+    Paris,
+    TestField,
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "empty" => Ok(Preset::Empty),
+            "paris" => Ok(Preset::Paris),
+            "testfield" => Ok(Preset::TestField),
+            _ => Err(format!(
+                "unknown preset {}, expected one of: empty, paris, testfield",
+                s
+            )),
+        }
+    }
+}
+
+impl Preset {
+    fn commands(self) -> WorldCommands {
+        let mut commands = WorldCommands::default();
+        match self {
+            Preset::Empty => {}
+            Preset::Paris => commands.map_load_paris(),
+            Preset::TestField => commands.map_load_testfield(geom::Vec2::ZERO, 10, 150.0),
+        }
+        commands
+    }
+}
+
+/// Runs the simulation as fast as possible, then writes
+/// `world/simulate_world.zip`, `world/simulate_times.json` and `world/simulate_hashes.json`.
+///
+/// When replaying, the world and the commands come from `world/replay.zip` and the
+/// simulation runs until the last recorded command if no tick count is given.
+pub fn simulate(opt: &Opt) {
+    log::info!("simulating with version: {}", goria_version::VERSION);
+
+    let mut player = None;
+    let (mut w, first_commands) = if opt.replay {
+        let (w, p) = unwrap_or!(ReplayPlayer::load("replay"), {
+            log::error!("couldn't load replay");
+            return;
+        });
+        player = Some(p);
+        (w, WorldCommands::default())
+    } else {
+        match Egregoria::load_from_disk("world") {
+            Some(w) => (w, WorldCommands::default()),
+            None => {
+                log::info!("savegame not found, generating {:?} preset", opt.preset);
+                (Egregoria::new(10), opt.preset.commands())
+            }
+        }
+    };
+
+    let start_tick = w.get_tick();
+    let ticks = match (opt.simulate, &player) {
+        (Some(ticks), _) => ticks,
+        (None, Some(p)) => p
+            .last_tick()
+            .map_or(0, |t| (t + 1).saturating_sub(start_tick)),
+        (None, None) => 0,
+    };
+
+    let mut recorder = if opt.record {
+        ReplayRecorder::new(&w)
+    } else {
+        None
+    };
+
+    let mut sched = Egregoria::schedule();
+
+    let start = Instant::now();
+    for i in 0..ticks {
+        let commands = match player {
+            Some(ref mut p) => p.next(w.get_tick()),
+            None if i == 0 => first_commands.clone(),
+            None => WorldCommands::default(),
+        };
+        if let Some(ref mut r) = recorder {
+            r.record(w.get_tick(), &commands);
+        }
+        w.tick(&mut sched, &commands);
+
+        if (i + 1) % 1000 == 0 {
+            log::info!("simulated {}/{} ticks", i + 1, ticks);
+        }
+    }
+    let elapsed = start.elapsed();
+
+    log::info!(
+        "simulated ticks {} to {} in {:.2}s ({:.2}ms/tick)",
+        start_tick,
+        w.get_tick(),
+        elapsed.as_secs_f32(),
+        1000.0 * elapsed.as_secs_f32() / ticks.max(1) as f32
+    );
+
+    if let Some(ref p) = player {
+        if !p.is_finished() {
+            log::warn!("simulation stopped before the end of the replay");
+        }
+    }
+
+    if let Some(ref r) = recorder {
+        r.save("replay");
+    }
+
+    w.save_to_disk("simulate_world");
+    JSON::save(&sched.times(), "simulate_times");
+    JSON::save(&w.hashes(), "simulate_hashes");
+}
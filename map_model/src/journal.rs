@@ -0,0 +1,305 @@
+use crate::{Building, ChunksDiff, Intersection, Lane, Lot, ParkingDiff, PowerLine, Road};
+use crate::{BuildingID, IntersectionID, LaneID, LotID, PowerLineID, RoadID};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use slotmap::{Key, SecondaryMap, SlotMap};
+use std::collections::BTreeMap;
+use std::ops::{Deref, Index, IndexMut};
+
+/// Previous state of everything changed in the map while it was recording.
+/// Applying it with [`crate::Map::apply_diff`] puts the map back in that state.
+#[derive(Default, Serialize, Deserialize)]
+pub struct MapDiff {
+    pub(crate) roads: SlotsDiff<RoadID, Road>,
+    pub(crate) lanes: SlotsDiff<LaneID, Lane>,
+    pub(crate) intersections: SlotsDiff<IntersectionID, Intersection>,
+    pub(crate) buildings: SlotsDiff<BuildingID, Building>,
+    pub(crate) lots: SlotsDiff<LotID, Lot>,
+    pub(crate) power_lines: SlotsDiff<PowerLineID, PowerLine>,
+    pub(crate) parking: ParkingDiff,
+    pub(crate) chunks: ChunksDiff,
+}
+
+impl MapDiff {
+    pub fn is_empty(&self) -> bool {
+        self.roads.is_empty()
+            && self.lanes.is_empty()
+            && self.intersections.is_empty()
+            && self.buildings.is_empty()
+            && self.lots.is_empty()
+            && self.power_lines.is_empty()
+            && self.parking.is_empty()
+            && self.chunks.is_empty()
+    }
+}
+
+/// Previous value of the keys of a slotmap (or secondary map) changed while recording,
+/// None if the key had no value
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SlotsDiff<K: Key, V>(BTreeMap<K, Option<V>>);
+
+impl<K: Key, V> Default for SlotsDiff<K, V> {
+    fn default() -> Self {
+        Self(BTreeMap::new())
+    }
+}
+
+impl<K: Key + Ord, V: Clone> SlotsDiff<K, V> {
+    /// Remembers `value`, the value at `k` before the change, if `k` wasn't already recorded
+    pub(crate) fn record(&mut self, k: K, value: Option<&V>) {
+        self.0.entry(k).or_insert_with(|| value.cloned());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn contains(&self, k: K) -> bool {
+        self.0.contains_key(&k)
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = K> + '_ {
+        self.0.keys().copied()
+    }
+
+    /// Puts the recorded values back into `m`, except under the keys that aren't `alive` anymore.
+    /// The diff then holds the values it replaced, so swapping it again cancels it.
+    pub(crate) fn swap(&mut self, m: &mut SecondaryMap<K, V>, alive: impl Fn(K) -> bool) {
+        for (&k, v) in &mut self.0 {
+            let cur = m.remove(k);
+            if let Some(v) = v.take().filter(|_| alive(k)) {
+                m.insert(k, v);
+            }
+            *v = cur;
+        }
+    }
+}
+
+/// A slotmap remembering the previous state of the values changed while it is recording.
+/// Reads go through `Deref`, changes must go through its methods.
+///
+/// Keys of values removed while recording stay allocated so that undoing the change
+/// can put the values back under the same key, until `release` frees them.
+pub struct Journaled<K: Key, V> {
+    keys: SlotMap<K, ()>,
+    map: SecondaryMap<K, V>,
+    journal: Option<SlotsDiff<K, V>>,
+}
+
+impl<K: Key + Ord, V: Clone> Journaled<K, V> {
+    pub(crate) fn start_recording(&mut self) {
+        if self.journal.is_none() {
+            self.journal = Some(SlotsDiff::default());
+        }
+    }
+
+    pub(crate) fn stop_recording(&mut self) -> SlotsDiff<K, V> {
+        self.journal.take().unwrap_or_default()
+    }
+
+    /// Puts back the values of the diff, see [`SlotsDiff::swap`]
+    pub(crate) fn swap(&mut self, diff: &mut SlotsDiff<K, V>) {
+        let keys = &self.keys;
+        diff.swap(&mut self.map, |k| keys.contains_key(k))
+    }
+
+    /// Frees the keys of a diff leaving the history that have no value anymore,
+    /// unless `kept` says another diff of the history can still bring them back
+    pub(crate) fn release(&mut self, dropped: &SlotsDiff<K, V>, kept: impl Fn(K) -> bool) {
+        for k in dropped.keys() {
+            if !self.map.contains_key(k) && !kept(k) {
+                self.keys.remove(k);
+            }
+        }
+    }
+
+    fn record(&mut self, k: K) {
+        if let Some(ref mut journal) = self.journal {
+            journal.record(k, self.map.get(k));
+        }
+    }
+
+    pub fn insert_with_key(&mut self, f: impl FnOnce(K) -> V) -> K {
+        let k = self.keys.insert(());
+        if let Some(ref mut journal) = self.journal {
+            journal.record(k, None);
+        }
+        self.map.insert(k, f(k));
+        k
+    }
+
+    pub fn insert(&mut self, v: V) -> K {
+        self.insert_with_key(move |_| v)
+    }
+
+    pub fn remove(&mut self, k: K) -> Option<V> {
+        self.record(k);
+        let v = self.map.remove(k);
+        if self.journal.is_none() {
+            self.keys.remove(k);
+        }
+        v
+    }
+
+    pub fn get_mut(&mut self, k: K) -> Option<&mut V> {
+        self.record(k);
+        self.map.get_mut(k)
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(K, &V) -> bool) {
+        let removed: Vec<K> = self
+            .map
+            .iter()
+            .filter(|&(k, v)| !f(k, v))
+            .map(|(k, _)| k)
+            .collect();
+        for k in removed {
+            self.remove(k);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.retain(|_, _| false);
+    }
+
+    pub fn iter_mut(&mut self) -> slotmap::secondary::IterMut<'_, K, V> {
+        if let Some(ref mut journal) = self.journal {
+            for (k, v) in &self.map {
+                journal.record(k, Some(v));
+            }
+        }
+        self.map.iter_mut()
+    }
+
+    pub fn values_mut(&mut self) -> slotmap::secondary::ValuesMut<'_, K, V> {
+        self.iter_mut();
+        self.map.values_mut()
+    }
+
+    /// For changes that don't need to be undone, like the observed speeds.
+    /// Must not insert or remove anything.
+    pub(crate) fn untracked_mut(&mut self) -> &mut SecondaryMap<K, V> {
+        &mut self.map
+    }
+}
+
+impl<K: Key, V> Deref for Journaled<K, V> {
+    type Target = SecondaryMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<K: Key, V> Index<K> for Journaled<K, V> {
+    type Output = V;
+
+    fn index(&self, k: K) -> &V {
+        &self.map[k]
+    }
+}
+
+impl<K: Key + Ord, V: Clone> IndexMut<K> for Journaled<K, V> {
+    fn index_mut(&mut self, k: K) -> &mut V {
+        self.get_mut(k).expect("invalid slotmap key used")
+    }
+}
+
+impl<'a, K: Key, V> IntoIterator for &'a Journaled<K, V> {
+    type Item = (K, &'a V);
+    type IntoIter = slotmap::secondary::Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.iter()
+    }
+}
+
+impl<K: Key, V> Default for Journaled<K, V> {
+    fn default() -> Self {
+        Self {
+            keys: SlotMap::with_key(),
+            map: SecondaryMap::new(),
+            journal: None,
+        }
+    }
+}
+
+/// Only clones the values, the copy isn't recording
+impl<K: Key, V: Clone> Clone for Journaled<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            keys: self.keys.clone(),
+            map: self.map.clone(),
+            journal: None,
+        }
+    }
+}
+
+impl<K: Key, V: Serialize> Serialize for Journaled<K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.keys, &self.map).serialize(serializer)
+    }
+}
+
+impl<'de, K: Key, V: Deserialize<'de>> Deserialize<'de> for Journaled<K, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (keys, map) = Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            keys,
+            map,
+            journal: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::procgen::load_testfield;
+    use crate::{Journaled, Map, Terraform};
+    use common::saveload::{Bincode, Encoder};
+    use geom::{vec2, Vec2};
+
+    /// The live objects with their ids and the ground heights, free slots and dirt ids don't matter
+    fn objects(m: &Map) -> Vec<Vec<u8>> {
+        fn live<K: slotmap::Key + serde::Serialize, V: serde::Serialize>(
+            objs: &Journaled<K, V>,
+        ) -> Vec<u8> {
+            Bincode::encode(&objs.iter().collect::<Vec<_>>()).unwrap()
+        }
+        vec![
+            live(m.roads()),
+            live(m.lanes()),
+            live(m.intersections()),
+            live(m.buildings()),
+            live(m.lots()),
+            Bincode::encode(&m.parking.all_spots().collect::<Vec<_>>()).unwrap(),
+            Bincode::encode(
+                &(0..40)
+                    .flat_map(|x| (0..40).map(move |y| vec2(x as f32, y as f32) * 20.0))
+                    .map(|p| m.terrain.height(p))
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_diff_roundtrip() {
+        let mut m = Map::empty();
+        m.terrain.generate_chunk((0, 0));
+        load_testfield(&mut m, Vec2::ZERO, 2, 100.0);
+        let before = objects(&m);
+
+        m.start_recording();
+        load_testfield(&mut m, vec2(50.0, 50.0), 2, 100.0);
+        m.terraform(vec2(600.0, 600.0), 150.0, Terraform::Raise(10.0));
+        let edited = objects(&m);
+        let mut diff = m.stop_recording();
+        assert!(!diff.is_empty());
+
+        m.apply_diff(&mut diff);
+        assert_eq!(objects(&m), before);
+
+        m.apply_diff(&mut diff);
+        assert_eq!(objects(&m), edited);
+    }
+}
@@ -34,6 +34,7 @@ mod objects {
     mod lane;
     mod lot;
     mod parking;
+    mod power_line;
     mod road;
     mod turn;
 
@@ -42,6 +43,7 @@ mod objects {
     pub use lane::*;
     pub use lot::*;
     pub use parking::*;
+    pub use power_line::*;
     pub use road::*;
     pub use turn::*;
 }
@@ -57,9 +59,11 @@ pub mod procgen {
     pub use presets::*;
 }
 
+mod journal;
 mod light_policy;
 mod map;
 mod pathfinding;
+mod routing_index;
 mod serializing;
 mod spatial_map;
 mod terrain;
@@ -69,8 +73,10 @@ mod turn_policy;
 
 // Use self or else it would be ambiguous with "pathfinding" crate
 pub use self::pathfinding::*;
+pub use journal::*;
 pub use light_policy::*;
 pub use map::*;
+pub use routing_index::*;
 pub use spatial_map::*;
 pub use terrain::*;
 pub use traffic_control::*;
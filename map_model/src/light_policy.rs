@@ -1,4 +1,7 @@
-use crate::{Intersection, LaneID, Lanes, Roads, TrafficControl, TrafficLightSchedule};
+use crate::{
+    Intersection, LaneID, Lanes, RoadID, Roads, TrafficBehavior, TrafficControl,
+    TrafficLightSchedule,
+};
 use imgui_inspect::{
     imgui::{im_str, Ui},
     InspectArgsDefault, InspectRenderDefault,
@@ -6,41 +9,164 @@ use imgui_inspect::{
 use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LightPolicy {
     NoLights,
     StopSigns,
     Lights,
     Auto,
+    Custom(CustomLights),
+    /// Lights switched according to the vehicles waiting, see `LightPolicy::phases`
+    Actuated,
 }
 
-impl Default for LightPolicy {
+/// Minimum green time given to the corridor roads of a green wave, in seconds
+pub const CORRIDOR_GREEN: u32 = 20;
+
+/// Part of a custom light schedule during which some roads have the green light together
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightPhase {
+    /// Incoming roads having the green light during this phase
+    pub roads: Vec<RoadID>,
+    /// How long the green light lasts, in seconds
+    pub green: u32,
+}
+
+/// Light schedule defined phase by phase by the player.
+/// A road listed in several phases should have them in a row, otherwise only the first run counts.
+/// Roads that aren't part of any phase get a stop sign.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomLights {
+    pub phases: Vec<LightPhase>,
+    /// How long the orange light lasts at the end of each phase, in seconds
+    pub orange: u32,
+    /// Shifts the whole schedule in time, in seconds
+    pub offset: u32,
+}
+
+impl Default for CustomLights {
     fn default() -> Self {
-        LightPolicy::Auto
+        Self {
+            phases: vec![],
+            orange: 4,
+            offset: 0,
+        }
     }
 }
 
-impl LightPolicy {
-    pub fn apply(self, inter: &Intersection, lanes: &mut Lanes, roads: &Roads) {
-        let in_road_lanes: Vec<Vec<LaneID>> = inter
+impl CustomLights {
+    /// Same phases as the ones `LightPolicy::Lights` would use, to have something to start from
+    pub fn from_intersection(inter: &Intersection) -> Self {
+        let n_phases = (inter.roads.len() + 1) / 2;
+        let mut phases = vec![
+            LightPhase {
+                roads: vec![],
+                green: 10,
+            };
+            n_phases
+        ];
+        for (i, &road) in inter.roads.iter().enumerate() {
+            phases[i % n_phases].roads.push(road);
+        }
+        Self {
+            phases,
+            ..Default::default()
+        }
+    }
+
+    /// Phases of an intersection on a green wave corridor: the corridor roads get the green light
+    /// together first, then the other roads are paired like `from_intersection`.
+    /// See `Map::set_green_wave` for the timing.
+    pub fn for_corridor(inter: &Intersection, corridor: &[RoadID]) -> Self {
+        let others: Vec<RoadID> = inter
             .roads
             .iter()
-            .map(|&x| {
-                roads
-                    .get(x)
-                    .into_iter()
-                    .flat_map(|r| {
-                        r.incoming_lanes_to(inter.id)
-                            .iter()
-                            .filter(|(_, kind)| kind.needs_light())
-                            .map(|&(id, _)| id)
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .filter(|v| !v.is_empty())
+            .copied()
+            .filter(|r| !corridor.contains(r))
             .collect();
+        let n_others = (others.len() + 1) / 2;
+
+        let mut phases = vec![LightPhase {
+            roads: corridor.to_vec(),
+            green: CORRIDOR_GREEN,
+        }];
+        phases.extend(vec![
+            LightPhase {
+                roads: vec![],
+                green: 10,
+            };
+            n_others
+        ]);
+        for (i, road) in others.into_iter().enumerate() {
+            phases[1 + i % n_others].roads.push(road);
+        }
+
+        Self {
+            phases,
+            ..Default::default()
+        }
+    }
+
+    pub fn period(&self) -> u32 {
+        self.phases.iter().map(|p| p.green + self.orange).sum()
+    }
 
-        for incoming_lanes in &in_road_lanes {
+    /// Start and length, in seconds, of the time window in which the road isn't red
+    pub fn green_window(&self, road: RoadID) -> Option<(u32, u32)> {
+        let n = self.phases.len();
+        let has = |i: usize| self.phases[i % n].roads.contains(&road);
+        let first = (0..n).find(|&i| has(i) && !has(i + n - 1))?;
+
+        let start = self.phases[..first]
+            .iter()
+            .map(|p| p.green + self.orange)
+            .sum();
+        let length = (first..)
+            .take_while(|&i| has(i))
+            .map(|i| self.phases[i % n].green + self.orange)
+            .sum();
+        Some((start, length))
+    }
+
+    fn control(&self, road: RoadID) -> TrafficControl {
+        let period = self.period();
+        if period == 0 {
+            return TrafficControl::StopSign;
+        }
+        if self.phases.iter().all(|p| p.roads.contains(&road)) {
+            return TrafficControl::Always;
+        }
+        let (start, length) = unwrap_or!(self.green_window(road), {
+            return TrafficControl::StopSign;
+        });
+        let orange = self.orange.min(length);
+        TrafficControl::Light(TrafficLightSchedule::from_basic(
+            (length - orange) as usize,
+            orange as usize,
+            (period - length) as usize,
+            ((period - start + self.offset) % period) as usize,
+        ))
+    }
+}
+
+impl Default for LightPolicy {
+    fn default() -> Self {
+        LightPolicy::Auto
+    }
+}
+
+impl LightPolicy {
+    pub fn has_lights(&self) -> bool {
+        matches!(
+            self,
+            LightPolicy::Lights | LightPolicy::Custom(_) | LightPolicy::Actuated
+        )
+    }
+
+    pub fn apply(&self, inter: &Intersection, lanes: &mut Lanes, roads: &Roads) {
+        let in_road_lanes = Self::incoming_lanes(inter, roads);
+
+        for (_, incoming_lanes) in &in_road_lanes {
             for &lane in incoming_lanes {
                 unwrap_cont!(lanes.get_mut(lane)).control = TrafficControl::Always;
             }
@@ -65,18 +191,74 @@ impl LightPolicy {
                     Self::stop_signs(in_road_lanes, lanes);
                 }
             }
+            LightPolicy::Custom(custom) => {
+                for (road, incoming_lanes) in in_road_lanes {
+                    let control = custom.control(road);
+                    for lane in incoming_lanes {
+                        unwrap_cont!(lanes.get_mut(lane)).control = control;
+                    }
+                }
+            }
+            LightPolicy::Actuated => {
+                // The controller takes over from there, start with the first phase
+                for (i, phase) in Self::phases(inter, roads).into_iter().enumerate() {
+                    let behavior = if i == 0 {
+                        TrafficBehavior::GREEN
+                    } else {
+                        TrafficBehavior::RED
+                    };
+                    for lane in phase {
+                        unwrap_cont!(lanes.get_mut(lane)).control =
+                            TrafficControl::Actuated(behavior);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Incoming lanes needing a light, grouped by the phase in which they get the green light.
+    /// Roads are paired the same way as with `LightPolicy::Lights`
+    pub fn phases(inter: &Intersection, roads: &Roads) -> Vec<Vec<LaneID>> {
+        let in_road_lanes = Self::incoming_lanes(inter, roads);
+        let n_phases = (in_road_lanes.len() + 1) / 2;
+
+        let mut phases = vec![vec![]; n_phases];
+        for (i, (_, incoming_lanes)) in in_road_lanes.into_iter().enumerate() {
+            phases[i % n_phases].extend(incoming_lanes);
         }
+        phases
+    }
+
+    fn incoming_lanes(inter: &Intersection, roads: &Roads) -> Vec<(RoadID, Vec<LaneID>)> {
+        inter
+            .roads
+            .iter()
+            .map(|&x| {
+                let v = roads
+                    .get(x)
+                    .into_iter()
+                    .flat_map(|r| {
+                        r.incoming_lanes_to(inter.id)
+                            .iter()
+                            .filter(|(_, kind)| kind.needs_light())
+                            .map(|&(id, _)| id)
+                    })
+                    .collect::<Vec<_>>();
+                (x, v)
+            })
+            .filter(|(_, v)| !v.is_empty())
+            .collect()
     }
 
-    fn stop_signs(in_road_lanes: Vec<Vec<LaneID>>, lanes: &mut Lanes) {
-        for incoming_lanes in in_road_lanes {
+    fn stop_signs(in_road_lanes: Vec<(RoadID, Vec<LaneID>)>, lanes: &mut Lanes) {
+        for (_, incoming_lanes) in in_road_lanes {
             for lane in incoming_lanes {
                 unwrap_cont!(lanes.get_mut(lane)).control = TrafficControl::StopSign;
             }
         }
     }
 
-    fn lights(in_road_lanes: Vec<Vec<LaneID>>, inter: &Intersection, lanes: &mut Lanes) {
+    fn lights(in_road_lanes: Vec<(RoadID, Vec<LaneID>)>, inter: &Intersection, lanes: &mut Lanes) {
         let n_cycles = (in_road_lanes.len() + 1) / 2;
         let cycle_size = 14;
         let orange_length = 4;
@@ -87,7 +269,7 @@ impl LightPolicy {
         let inter_offset: usize =
             rand::rngs::SmallRng::seed_from_u64(offset as u64).gen_range(0..total_length);
 
-        for (i, incoming_lanes) in in_road_lanes.into_iter().enumerate() {
+        for (i, (_, incoming_lanes)) in in_road_lanes.into_iter().enumerate() {
             let light = TrafficControl::Light(TrafficLightSchedule::from_basic(
                 cycle_size - orange_length,
                 orange_length,
@@ -122,6 +304,8 @@ impl InspectRenderDefault<LightPolicy> for LightPolicy {
             LightPolicy::StopSigns => 1,
             LightPolicy::Lights => 2,
             LightPolicy::Auto => 3,
+            LightPolicy::Custom(_) => 4,
+            LightPolicy::Actuated => 5,
         };
 
         #[allow(clippy::indexing_slicing)]
@@ -134,6 +318,8 @@ impl InspectRenderDefault<LightPolicy> for LightPolicy {
                     &im_str!("Stop signs"),
                     &im_str!("Lights"),
                     &im_str!("Auto"),
+                    &im_str!("Custom"),
+                    &im_str!("Actuated"),
                 ],
             );
 
@@ -143,6 +329,8 @@ impl InspectRenderDefault<LightPolicy> for LightPolicy {
                 1 => **p = LightPolicy::StopSigns,
                 2 => **p = LightPolicy::Lights,
                 3 => **p = LightPolicy::Auto,
+                4 => **p = LightPolicy::Custom(CustomLights::default()),
+                5 => **p = LightPolicy::Actuated,
                 _ => unreachable!(),
             }
         }
@@ -150,3 +338,51 @@ impl InspectRenderDefault<LightPolicy> for LightPolicy {
         changed
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{CustomLights, LightPhase};
+    use crate::{RoadID, TrafficBehavior, TrafficControl};
+    use slotmap::SlotMap;
+
+    #[test]
+    fn test_custom_lights() {
+        let mut ids = SlotMap::<RoadID, ()>::with_key();
+        let (a, b, c, d) = (
+            ids.insert(()),
+            ids.insert(()),
+            ids.insert(()),
+            ids.insert(()),
+        );
+
+        let custom = CustomLights {
+            phases: vec![
+                LightPhase {
+                    roads: vec![a, c],
+                    green: 20,
+                },
+                LightPhase {
+                    roads: vec![b, c],
+                    green: 6,
+                },
+            ],
+            orange: 4,
+            offset: 0,
+        };
+
+        assert_eq!(custom.period(), 34);
+        assert_eq!(custom.green_window(a), Some((0, 24)));
+        assert_eq!(custom.green_window(b), Some((24, 10)));
+        assert_eq!(custom.green_window(d), None);
+
+        assert!(custom.control(c).is_always());
+        assert!(custom.control(d).is_stop_sign());
+
+        let b_light = custom.control(b);
+        assert!(matches!(b_light, TrafficControl::Light(_)));
+        assert!(matches!(b_light.get_behavior(10), TrafficBehavior::RED));
+        assert!(matches!(b_light.get_behavior(25), TrafficBehavior::GREEN));
+        assert!(matches!(b_light.get_behavior(31), TrafficBehavior::ORANGE));
+        assert!(matches!(b_light.get_behavior(34), TrafficBehavior::RED));
+    }
+}
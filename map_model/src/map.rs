@@ -1,21 +1,28 @@
-use crate::serializing::SerializedMap;
+use crate::serializing::{mk_spatial_map, SerializedMap};
 use crate::{
-    Building, BuildingGen, BuildingID, BuildingKind, Intersection, IntersectionID, Lane, LaneID,
-    LaneKind, LanePattern, Lot, LotID, LotKind, ParkingSpotID, ParkingSpots, ProjectFilter,
-    ProjectKind, Road, RoadID, RoadSegmentKind, SpatialMap, Terrain,
+    Building, BuildingGen, BuildingID, BuildingKind, Closure, CustomLights, Intersection,
+    IntersectionID, Lane, LaneID, LaneKind, LanePattern, LightPolicy, Lot, LotID, LotKind,
+    ParkingSpotID, ParkingSpots, PowerLine, PowerLineID, ProjectFilter, ProjectKind, Road, RoadID,
+    RoadSegmentKind, RoutingIndex, SpatialMap, Terraform, Terrain, TrafficBehavior, TrafficControl,
+    CELL_SIZE, MAX_POWER_LINE_LENGTH,
 };
+use crate::{Journaled, MapDiff};
 use geom::OBB;
 use geom::{pseudo_angle, Circle, Intersect, Shape, Spline3, Vec2, Vec3};
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
-use slotmap::DenseSlotMap;
+use std::collections::BTreeMap;
 use std::num::Wrapping;
 
-pub type Roads = DenseSlotMap<RoadID, Road>;
-pub type Lanes = DenseSlotMap<LaneID, Lane>;
-pub type Intersections = DenseSlotMap<IntersectionID, Intersection>;
-pub type Buildings = DenseSlotMap<BuildingID, Building>;
-pub type Lots = DenseSlotMap<LotID, Lot>;
+/// How much of a new speed sample goes into the observed speed of lanes
+const SPEED_SMOOTHING: f32 = 0.2;
+
+pub type Roads = Journaled<RoadID, Road>;
+pub type Lanes = Journaled<LaneID, Lane>;
+pub type Intersections = Journaled<IntersectionID, Intersection>;
+pub type Buildings = Journaled<BuildingID, Building>;
+pub type Lots = Journaled<LotID, Lot>;
+pub type PowerLines = Journaled<PowerLineID, PowerLine>;
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct MapProject {
@@ -29,7 +36,9 @@ pub struct Map {
     pub(crate) intersections: Intersections,
     pub(crate) buildings: Buildings,
     pub(crate) lots: Lots,
+    pub(crate) power_lines: PowerLines,
     pub(crate) spatial_map: SpatialMap,
+    pub(crate) routing: RoutingIndex,
     pub terrain: Terrain,
     pub parking: ParkingSpots,
     pub dirt_id: Wrapping<u32>,
@@ -53,9 +62,11 @@ impl Map {
             parking: ParkingSpots::default(),
             buildings: Buildings::default(),
             lots: Lots::default(),
+            power_lines: PowerLines::default(),
             terrain: Terrain::default(),
             dirt_id: Wrapping(1),
             spatial_map: SpatialMap::default(),
+            routing: RoutingIndex::default(),
         }
     }
 
@@ -99,6 +110,8 @@ impl Map {
 
         let b = self.buildings.remove(b)?;
         self.spatial_map.remove(b.id);
+        self.parking.remove_building_spots(b.id);
+        self.power_lines.retain(|_, l| !l.connects(b.id));
 
         self.dirt_id += Wrapping(1);
 
@@ -108,6 +121,50 @@ impl Map {
         Some(b)
     }
 
+    /// Links two buildings with a power line, returns None if they are already linked or too far apart
+    pub fn add_power_line(&mut self, src: BuildingID, dst: BuildingID) -> Option<PowerLineID> {
+        info!("add_power_line {:?} {:?}", src, dst);
+
+        self.power_line_length(src, dst)?;
+
+        self.dirt_id += Wrapping(1);
+        let id = self
+            .power_lines
+            .insert_with_key(|id| PowerLine { id, src, dst });
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+
+        Some(id)
+    }
+
+    /// Length of the power line that would link the two buildings, None if it can't be built
+    pub fn power_line_length(&self, src: BuildingID, dst: BuildingID) -> Option<f32> {
+        if src == dst
+            || self
+                .power_lines
+                .values()
+                .any(|l| l.connects(src) && l.connects(dst))
+        {
+            return None;
+        }
+        let a = self.buildings.get(src)?.obb.center();
+        let b = self.buildings.get(dst)?.obb.center();
+        Some(a.distance(b)).filter(|&d| d <= MAX_POWER_LINE_LENGTH)
+    }
+
+    pub fn remove_power_line(&mut self, id: PowerLineID) -> Option<PowerLine> {
+        info!("remove_power_line {:?}", id);
+
+        let line = self.power_lines.remove(id)?;
+        self.dirt_id += Wrapping(1);
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+
+        Some(line)
+    }
+
     pub fn make_connection(
         &mut self,
         from: MapProject,
@@ -186,6 +243,14 @@ impl Map {
             kind,
             gen,
         );
+
+        if let (Some(id), BuildingGen::Parking { levels }) = (v, gen) {
+            let building = &self.buildings[id];
+            if let Some(lane) = self.nearest_lane(building.door_pos, LaneKind::Driving) {
+                self.parking.generate_building_spots(building, lane, levels);
+            }
+        }
+
         #[cfg(debug_assertions)]
         self.check_invariants();
         v
@@ -257,11 +322,290 @@ impl Map {
         }
     }
 
+    /// Whether the terrain can be sculpted there. Roads, intersections and buildings keep their
+    /// heights, so the ground under them must not move.
+    pub fn can_terraform(&self, center: Vec2, radius: f32, kind: Terraform) -> bool {
+        let amount_ok = match kind {
+            Terraform::Raise(x) | Terraform::Lower(x) | Terraform::Flatten(x) => x.is_finite(),
+            Terraform::Smooth => true,
+        };
+        if !amount_ok || !center.is_finite() || !radius.is_finite() || radius <= 0.0 {
+            return false;
+        }
+        // heights are interpolated from the cells up to one cell away
+        self.spatial_map
+            .query_around(
+                center,
+                radius + CELL_SIZE,
+                ProjectFilter::ROAD | ProjectFilter::INTER | ProjectFilter::BUILDING,
+            )
+            .next()
+            .is_none()
+    }
+
+    /// Sculpts the terrain around the point, the lots that aren't level with their road anymore are removed.
+    /// Returns whether the ground changed
+    pub fn terraform(&mut self, center: Vec2, radius: f32, kind: Terraform) -> bool {
+        info!("terraform {:?} {} {:?}", center, radius, kind);
+        if !self.can_terraform(center, radius, kind) {
+            return false;
+        }
+        if !self.terrain.terraform(center, radius, kind) {
+            return false;
+        }
+        // heights are interpolated from the cells up to one cell away
+        Lot::revalidate_heights(self, Circle::new(center, radius + CELL_SIZE));
+        self.dirt_id += Wrapping(1);
+        true
+    }
+
+    /// Where a building of the given size would stand if it grew on the zoned lot, facing the same road.
+    /// It may only cover lots of the same zone, no road nor building.
+    pub fn zoned_building_obb(&self, lot: LotID, size: f32) -> Option<OBB> {
+        let lot = self.lots.get(lot)?;
+        let depth = lot.shape.axis()[1];
+        let dir = depth.try_normalize()?;
+        let front = lot.shape.center() - depth * 0.5;
+        // a bit smaller so it doesn't touch the neighbouring lots
+        let obb = OBB::new(front + dir * size * 0.5, dir, size - 1.0, size - 1.0);
+
+        if (self.terrain.height(obb.center())? - lot.height).abs() > 5.0 {
+            return None;
+        }
+        for obj in self.spatial_map.query(obb, ProjectFilter::ALL) {
+            match obj {
+                ProjectKind::Lot(id) if self.lots.get(id).map(|l| l.kind) == Some(lot.kind) => {}
+                _ => return None,
+            }
+        }
+        Some(obb)
+    }
+
+    /// Coordinates the lights along a chain of intersections so that vehicles driving from the
+    /// first to the last one at the speed limit keep getting the green light.
+    /// All the lights share the same cycle and the green of each one is shifted by the time it
+    /// takes to drive there. Does nothing if consecutive intersections aren't connected.
+    pub fn set_green_wave(&mut self, corridor: &[IntersectionID]) -> bool {
+        info!("set_green_wave {:?}", corridor);
+        if corridor.len() < 2 {
+            return false;
+        }
+        for (i, id) in corridor.iter().enumerate() {
+            if corridor.iter().take(i).any(|x| x == id) || !self.intersections.contains_key(*id) {
+                return false;
+            }
+        }
+
+        let mut corridor_roads = Vec::with_capacity(corridor.len() - 1);
+        for w in corridor.windows(2) {
+            corridor_roads.push(unwrap_or!(self.find_road(w[0], w[1]), return false));
+        }
+
+        let mut arrival = 0.0;
+        let mut lights = Vec::with_capacity(corridor.len());
+        for (i, &id) in corridor.iter().enumerate() {
+            let inter = unwrap_ret!(self.intersections.get(id), false);
+            let roads: Vec<RoadID> = i
+                .checked_sub(1)
+                .and_then(|prev| corridor_roads.get(prev))
+                .into_iter()
+                .chain(corridor_roads.get(i))
+                .copied()
+                .collect();
+            lights.push((id, arrival, CustomLights::for_corridor(inter, &roads)));
+
+            if let Some(road) = corridor_roads.get(i).and_then(|&r| self.roads.get(r)) {
+                let speed = road
+                    .lanes_iter()
+                    .filter(|(_, kind)| kind.vehicles())
+                    .filter_map(|(lane, _)| self.lanes.get(lane))
+                    .map(|lane| lane.speed_limit)
+                    .fold(0.0, f32::max);
+                arrival += road.length() / speed.max(1.0);
+            }
+        }
+
+        let period = lights.iter().map(|(_, _, l)| l.period()).max().unwrap_or(0);
+        for (id, arrival, mut custom) in lights {
+            let slack = period - custom.period();
+            if let Some(corridor_phase) = custom.phases.first_mut() {
+                corridor_phase.green += slack;
+            }
+            // the corridor phase comes first so its green starts when the wave arrives
+            custom.offset = (period - arrival as u32 % period) % period;
+
+            let inter = unwrap_cont!(self.intersections.get_mut(id));
+            inter.light_policy = LightPolicy::Custom(custom);
+            inter.update_traffic_control(&mut self.lanes, &self.roads);
+        }
+
+        self.dirt_id += Wrapping(1);
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+        true
+    }
+
+    /// Blends the average speed of the vehicles on each lane into its `observed_speed`.
+    /// Lanes without any vehicle drift back to their speed limit.
+    /// Doesn't change the `dirt_id` as nothing needs to be rebuilt.
+    pub fn observe_lane_speeds(&mut self, samples: &BTreeMap<LaneID, f32>) {
+        for (id, lane) in self.lanes.untracked_mut().iter_mut() {
+            if !lane.kind.vehicles() {
+                continue;
+            }
+            let sample = samples
+                .get(&id)
+                .copied()
+                .unwrap_or(lane.speed_limit)
+                .min(lane.speed_limit);
+            let speed = lane.observed_speed.unwrap_or(lane.speed_limit);
+            lane.observed_speed = Some(speed + (sample - speed) * SPEED_SMOOTHING);
+        }
+    }
+
+    /// Closes the vehicle lanes of the road, or lifts their closure if `closure` is None
+    pub fn set_road_closure(&mut self, road: RoadID, closure: Option<Closure>) {
+        info!("set road closure {:?} {:?}", road, closure);
+        let road = unwrap_ret!(self.roads.get(road));
+        for (id, kind) in road.lanes_iter() {
+            if !kind.vehicles() {
+                continue;
+            }
+            if let Some(lane) = self.lanes.get_mut(id) {
+                lane.closure = closure;
+            }
+        }
+        self.dirt_id += Wrapping(1);
+    }
+
+    /// Lifts the closures that ended before `now`, in game seconds
+    pub fn lift_expired_closures(&mut self, now: u32) {
+        let expired: Vec<LaneID> = self
+            .lanes
+            .values()
+            .filter(|lane| {
+                matches!(lane.closure, Some(Closure { until: Some(until), .. }) if until <= now)
+            })
+            .map(|lane| lane.id)
+            .collect();
+        for &id in &expired {
+            if let Some(lane) = self.lanes.get_mut(id) {
+                lane.closure = None;
+            }
+        }
+        if !expired.is_empty() {
+            self.dirt_id += Wrapping(1);
+        }
+    }
+
+    /// Brings the routing index up to date with the map and the observed lane speeds.
+    /// While it is out of date, vehicle paths are found without it.
+    pub fn update_routing_index(&mut self, now: u32) {
+        self.routing.update(
+            &self.lanes,
+            &self.intersections,
+            &self.roads,
+            self.dirt_id.0,
+            now,
+        );
+    }
+
+    /// Switches the light of a lane controlled by `LightPolicy::Actuated`.
+    /// Doesn't change the `dirt_id` as nothing needs to be rebuilt.
+    pub fn set_actuated_light(&mut self, lane: LaneID, behavior: TrafficBehavior) {
+        let lane = unwrap_ret!(self.lanes.untracked_mut().get_mut(lane));
+        if let TrafficControl::Actuated(ref mut b) = lane.control {
+            *b = behavior;
+        }
+    }
+
+    /// Starts remembering the previous state of what changes, until `stop_recording`
+    pub fn start_recording(&mut self) {
+        self.roads.start_recording();
+        self.lanes.start_recording();
+        self.intersections.start_recording();
+        self.buildings.start_recording();
+        self.lots.start_recording();
+        self.power_lines.start_recording();
+        self.parking.start_recording();
+        self.terrain.start_recording();
+    }
+
+    /// Returns the previous state of what changed since `start_recording`
+    pub fn stop_recording(&mut self) -> MapDiff {
+        MapDiff {
+            roads: self.roads.stop_recording(),
+            lanes: self.lanes.stop_recording(),
+            intersections: self.intersections.stop_recording(),
+            buildings: self.buildings.stop_recording(),
+            lots: self.lots.stop_recording(),
+            power_lines: self.power_lines.stop_recording(),
+            parking: self.parking.stop_recording(),
+            chunks: self.terrain.stop_recording(),
+        }
+    }
+
+    /// Frees the ids kept for a diff that leaves the history, `kept` being the diffs still in it
+    pub fn release(&mut self, dropped: &MapDiff, kept: &[&MapDiff]) {
+        self.roads
+            .release(&dropped.roads, |k| kept.iter().any(|d| d.roads.contains(k)));
+        self.lanes
+            .release(&dropped.lanes, |k| kept.iter().any(|d| d.lanes.contains(k)));
+        self.intersections.release(&dropped.intersections, |k| {
+            kept.iter().any(|d| d.intersections.contains(k))
+        });
+        self.buildings.release(&dropped.buildings, |k| {
+            kept.iter().any(|d| d.buildings.contains(k))
+        });
+        self.lots
+            .release(&dropped.lots, |k| kept.iter().any(|d| d.lots.contains(k)));
+        self.power_lines.release(&dropped.power_lines, |k| {
+            kept.iter().any(|d| d.power_lines.contains(k))
+        });
+        self.parking.release(&dropped.parking, |k| {
+            kept.iter().any(|d| d.parking.contains_spot(k))
+        });
+    }
+
+    /// Puts the map back in the state recorded by the diff, objects keep their ids.
+    /// The diff then holds the state it replaced, so applying it again cancels it.
+    pub fn apply_diff(&mut self, diff: &mut MapDiff) {
+        info!("apply_diff");
+        self.roads.swap(&mut diff.roads);
+        self.lanes.swap(&mut diff.lanes);
+        self.intersections.swap(&mut diff.intersections);
+        self.buildings.swap(&mut diff.buildings);
+        self.lots.swap(&mut diff.lots);
+        self.power_lines.swap(&mut diff.power_lines);
+        self.parking.swap(&mut diff.parking);
+        self.terrain.swap(&mut diff.chunks);
+
+        self.spatial_map = mk_spatial_map(
+            &self.buildings,
+            &self.roads,
+            &self.intersections,
+            &self.lots,
+        );
+        self.dirt_id += Wrapping(1);
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+    }
+
     pub fn clear(&mut self) {
         info!("clear");
-        let before = std::mem::replace(self, Self::empty());
-        self.terrain = before.terrain;
-        self.dirt_id = before.dirt_id + Wrapping(1);
+        // removed one by one so that it can be undone
+        self.roads.retain(|_, _| false);
+        self.lanes.retain(|_, _| false);
+        self.intersections.retain(|_, _| false);
+        self.buildings.retain(|_, _| false);
+        self.lots.retain(|_, _| false);
+        self.power_lines.retain(|_, _| false);
+        self.parking.clear();
+        self.spatial_map = SpatialMap::default();
+        self.routing = RoutingIndex::default();
+        self.dirt_id += Wrapping(1);
 
         #[cfg(debug_assertions)]
         self.check_invariants();
@@ -399,31 +743,31 @@ impl Map {
         let r1 = self.roads.get(r1)?;
         let r2 = self.roads.get(r2)?;
 
-        let spatial = &mut self.spatial_map;
-        self.lots.retain(|_, lot| {
-            if lot.parent != r_id {
-                return true;
-            }
+        let split_lots: Vec<LotID> = self
+            .lots
+            .values()
+            .filter(|lot| lot.parent == r_id)
+            .map(|lot| lot.id)
+            .collect();
+        for lot_id in split_lots {
+            let lot = unwrap_cont!(self.lots.get_mut(lot_id));
             let p = lot.shape.corners[0].z(lot.height);
             let d1 = r1.points.project(p).distance(p);
             let d2 = r2.points.project(p).distance(p);
             if d1 < d2 {
                 if d1 < r1.width * 0.5 + 1.5 {
                     lot.parent = r1.id;
-                } else {
-                    spatial.remove(lot.id);
-                    return false;
+                    continue;
                 }
             } else {
                 if d2 < r2.width * 0.5 + 1.5 {
                     lot.parent = r2.id;
-                } else {
-                    spatial.remove(lot.id);
-                    return false;
+                    continue;
                 }
             }
-            true
-        });
+            self.spatial_map.remove(lot_id);
+            self.lots.remove(lot_id);
+        }
 
         Some(id)
     }
@@ -546,6 +890,9 @@ impl Map {
     pub fn lots(&self) -> &Lots {
         &self.lots
     }
+    pub fn power_lines(&self) -> &PowerLines {
+        &self.power_lines
+    }
     pub fn spatial_map(&self) -> &SpatialMap {
         &self.spatial_map
     }
@@ -577,6 +924,9 @@ impl Map {
 
     pub fn parking_to_drive(&self, spot: ParkingSpotID) -> Option<LaneID> {
         let spot = self.parking.get(spot)?;
+        if let Some(b) = spot.building {
+            return self.building_to_drive(b);
+        }
         let park_lane = self.lanes.get(spot.parent)?;
         let road = self.roads.get(park_lane.parent)?;
         road.outgoing_lanes_from(park_lane.src)
@@ -587,6 +937,12 @@ impl Map {
 
     pub fn parking_to_drive_pos(&self, spot: ParkingSpotID) -> Option<Vec3> {
         let spot = self.parking.get(spot)?;
+        if let Some(b) = spot.building {
+            let door = self.buildings.get(b)?.door_pos;
+            let lane = self.lanes.get(self.building_to_drive(b)?)?;
+            let (pos, _, dir) = lane.points.project_segment_dir(door);
+            return Some(pos - dir * 4.0);
+        }
         let park_lane = self.lanes.get(spot.parent)?;
         let road = self.roads.get(park_lane.parent)?;
         let lane = road
@@ -603,6 +959,13 @@ impl Map {
         Some(pos - dir * 4.0)
     }
 
+    /// The driving lane leading to the entrance of a parking lot or garage.
+    /// It is looked up every time as lanes get regenerated when roads are edited
+    fn building_to_drive(&self, b: BuildingID) -> Option<LaneID> {
+        let door = self.buildings.get(b)?.door_pos;
+        self.nearest_lane(door, LaneKind::Driving)
+    }
+
     pub fn check_invariants(&self) {
         for inter in self.intersections.values() {
             log::debug!("{:?}", inter.id);
@@ -692,6 +1055,12 @@ impl Map {
             }
         }
 
+        for line in self.power_lines.values() {
+            log::debug!("{:?}", line.id);
+            assert!(self.buildings.contains_key(line.src), "{:?}", line.src);
+            assert!(self.buildings.contains_key(line.dst), "{:?}", line.dst);
+        }
+
         for lot in self.lots.values() {
             log::debug!("{:?}", lot.id);
             assert!(lot.shape.axis().iter().all(|x| x.magnitude() > 0.0));
@@ -15,6 +15,14 @@ debug_inspect_impl!(BuildingID);
 pub enum BuildingKind {
     House,
     GoodsCompany(u32),
+    ParkingLot,
+    ParkingGarage,
+}
+
+impl BuildingKind {
+    pub fn is_parking(self) -> bool {
+        matches!(self, BuildingKind::ParkingLot | BuildingKind::ParkingGarage)
+    }
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
@@ -24,6 +32,9 @@ pub enum BuildingGen {
     CenteredDoor {
         vertical_factor: f32, // 1.0 means that the door is at the bottom, just on the street
     },
+    Parking {
+        levels: u32,
+    },
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -58,6 +69,7 @@ impl Building {
             BuildingGen::CenteredDoor { vertical_factor } => {
                 (Default::default(), Vec2::y(-vertical_factor * 0.5 * size))
             }
+            BuildingGen::Parking { levels } => crate::procgen::gen_exterior_parking(size, levels),
         };
 
         for (poly, _) in &mut mesh.faces {
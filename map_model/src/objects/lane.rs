@@ -9,6 +9,9 @@ new_key_type! {
     pub struct LaneID;
 }
 
+/// Speed vehicles are expected to drive at past an incident
+pub const INCIDENT_SPEED: f32 = 2.0;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Hash)]
 pub enum LaneKind {
     Driving,
@@ -29,13 +32,29 @@ impl LaneKind {
 
     pub fn width(self) -> f32 {
         match self {
-            LaneKind::Driving | LaneKind::Biking | LaneKind::Bus => 4.0,
+            LaneKind::Driving | LaneKind::Bus => 4.0,
+            LaneKind::Biking => 2.0,
             LaneKind::Parking => 2.5,
             LaneKind::Walking => 3.0,
         }
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClosureKind {
+    /// Nobody can drive through, e.g. for road works
+    Closed,
+    /// Traffic is slowed down, vehicles go around it if they can
+    Incident,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Closure {
+    pub kind: ClosureKind,
+    /// Game seconds at which the closure is lifted, None if it has to be lifted by hand
+    pub until: Option<u32>,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LaneDirection {
     Forward,
@@ -55,6 +74,9 @@ pub struct Lane {
 
     pub control: TrafficControl,
     pub speed_limit: f32,
+    /// Smoothed average speed of the vehicles driving on it, None until it's been observed
+    pub observed_speed: Option<f32>,
+    pub closure: Option<Closure>,
 
     /// Always from src to dst
     pub points: PolyLine3,
@@ -99,6 +121,8 @@ pub struct LanePatternBuilder {
     pub sidewalks: bool,
     pub parking: bool,
     pub one_way: bool,
+    pub bus_lanes: bool,
+    pub bike_lanes: bool,
 }
 
 impl Default for LanePatternBuilder {
@@ -115,6 +139,8 @@ impl LanePatternBuilder {
             sidewalks: true,
             parking: true,
             one_way: false,
+            bus_lanes: false,
+            bike_lanes: false,
         }
     }
 
@@ -143,6 +169,16 @@ impl LanePatternBuilder {
         self
     }
 
+    pub fn bus_lanes(&mut self, bus_lanes: bool) -> &mut Self {
+        self.bus_lanes = bus_lanes;
+        self
+    }
+
+    pub fn bike_lanes(&mut self, bike_lanes: bool) -> &mut Self {
+        self.bike_lanes = bike_lanes;
+        self
+    }
+
     pub fn width(self) -> f32 {
         let mut w = 0.0;
         let wayf = if self.one_way { 1.0 } else { 2.0 };
@@ -152,6 +188,12 @@ impl LanePatternBuilder {
         if self.parking {
             w += LaneKind::Parking.width() * wayf;
         }
+        if self.bus_lanes {
+            w += LaneKind::Bus.width() * wayf;
+        }
+        if self.bike_lanes {
+            w += LaneKind::Biking.width() * wayf;
+        }
         w += self.n_lanes as f32 * wayf * LaneKind::Driving.width();
         w + 0.5
     }
@@ -159,6 +201,8 @@ impl LanePatternBuilder {
     pub fn build(mut self) -> LanePattern {
         if self.n_lanes == 0 {
             self.parking = false;
+            self.bus_lanes = false;
+            self.bike_lanes = false;
             self.sidewalks = true;
         }
 
@@ -170,6 +214,20 @@ impl LanePatternBuilder {
 
         let mut forward: Vec<_> = (0..self.n_lanes).map(|_| LaneKind::Driving).collect();
 
+        if self.bus_lanes {
+            if !self.one_way {
+                backward.push(LaneKind::Bus);
+            }
+            forward.push(LaneKind::Bus);
+        }
+
+        if self.bike_lanes {
+            if !self.one_way {
+                backward.push(LaneKind::Biking);
+            }
+            forward.push(LaneKind::Biking);
+        }
+
         if self.parking {
             if !self.one_way {
                 backward.push(LaneKind::Parking);
@@ -216,6 +274,8 @@ impl Lane {
             dist_from_bottom,
             control: TrafficControl::Always,
             speed_limit,
+            observed_speed: None,
+            closure: None,
         })
     }
 
@@ -290,6 +350,33 @@ impl Lane {
         }
     }
 
+    /// Speed at which vehicles are expected to drive through, taking traffic into account
+    pub fn travel_speed(&self) -> f32 {
+        self.travel_speed_at(self.observed_speed)
+    }
+
+    /// The travel speed had the observed speed been `observed`
+    pub(crate) fn travel_speed_at(&self, observed: Option<f32>) -> f32 {
+        let speed = observed.unwrap_or(self.speed_limit);
+        match self.closure {
+            Some(Closure {
+                kind: ClosureKind::Incident,
+                ..
+            }) => speed.min(INCIDENT_SPEED),
+            _ => speed,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self.closure,
+            Some(Closure {
+                kind: ClosureKind::Closed,
+                ..
+            })
+        )
+    }
+
     pub fn orientation_from(&self, id: IntersectionID) -> Vec2 {
         if id == self.src {
             self.points.first_dir().unwrap_or(Vec3::X).xy()
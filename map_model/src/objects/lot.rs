@@ -1,4 +1,4 @@
-use crate::{Map, ProjectFilter, ProjectKind, RoadID};
+use crate::{Map, ProjectFilter, ProjectKind, RoadID, Terrain};
 use geom::Vec2;
 use geom::OBB;
 use geom::{Circle, Vec3};
@@ -10,10 +10,14 @@ new_key_type! {
     pub struct LotID;
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LotKind {
     Unassigned,
     Residential,
+    /// Stores grow here when consumers can't find what they want
+    Commercial,
+    /// Factories grow here when companies can't find what they need
+    Industrial,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -33,10 +37,7 @@ impl Lot {
         axis: Vec2,
         size: f32,
     ) -> Option<LotID> {
-        let height = map.terrain.height(at.xy())?;
-        if (height - at.z).abs() > 1.0 {
-            return None;
-        }
+        let height = Self::ground_height(&map.terrain, at)?;
 
         let shape = OBB::new(at.xy() + axis * size * 0.5, axis, size, size);
 
@@ -57,6 +58,45 @@ impl Lot {
         Some(id)
     }
 
+    /// Height of the ground at the front of the lot, if it is level with the road at `at`
+    fn ground_height(terrain: &Terrain, at: Vec3) -> Option<f32> {
+        let height = terrain.height(at.xy())?;
+        if (height - at.z).abs() > 1.0 {
+            return None;
+        }
+        Some(height)
+    }
+
+    /// Checks the height of the lots in the area again after the terrain changed,
+    /// the ones that aren't level with their road anymore are removed
+    pub fn revalidate_heights(map: &mut Map, area: Circle) {
+        let lots = map
+            .spatial_map
+            .query(area, ProjectFilter::LOT)
+            .filter_map(|k| match k {
+                ProjectKind::Lot(id) => Some(id),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for id in lots {
+            let lot = unwrap_cont!(map.lots.get(id));
+            let front = lot.shape.center() - lot.shape.axis()[1] * 0.5;
+            let height = map.roads.get(lot.parent).and_then(|r| {
+                let at = r.points().project(front.z(lot.height));
+                Self::ground_height(&map.terrain, front.z(at.z))
+            });
+
+            match height {
+                Some(height) => map.lots[id].height = height,
+                None => {
+                    map.lots.remove(id);
+                    map.spatial_map.remove(id);
+                }
+            }
+        }
+    }
+
     pub fn generate_along_road(map: &mut Map, road: RoadID) {
         if !map.roads.contains_key(road) {
             log::error!("trying to generate along invalid road");
@@ -1,9 +1,9 @@
-use crate::{Lane, LaneID, LaneKind, CROSSWALK_WIDTH};
+use crate::{Building, BuildingID, Journaled, Lane, LaneID, LaneKind, SlotsDiff, CROSSWALK_WIDTH};
 use flat_spatial::ShapeGrid;
-use geom::{Transform, Vec2, Vec3};
+use geom::{vec2, Transform, Vec2, Vec3};
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
-use slotmap::{new_key_type, SecondaryMap, SlotMap};
+use slotmap::{new_key_type, SecondaryMap};
 use std::convert::TryFrom;
 
 new_key_type! {
@@ -11,18 +11,45 @@ new_key_type! {
 }
 
 pub const PARKING_SPOT_LENGTH: f32 = 6.0;
+/// Width of an off-street spot, they are laid out side by side in rows
+pub const PARKING_SPOT_WIDTH: f32 = 3.0;
+/// A row of off-street spots and half of the aisle serving it
+const PARKING_ROW_DEPTH: f32 = 9.0;
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct ParkingSpot {
     pub parent: LaneID,
     pub trans: Transform,
+    /// The parking lot or garage the spot is in, None for on-street spots
+    pub building: Option<BuildingID>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ParkingSpots {
-    spots: SlotMap<ParkingSpotID, ParkingSpot>,
+    spots: Journaled<ParkingSpotID, ParkingSpot>,
     lane_spots: SecondaryMap<LaneID, Vec<ParkingSpotID>>,
+    building_spots: SecondaryMap<BuildingID, Vec<ParkingSpotID>>,
     pub(crate) reuse_spot: ShapeGrid<ParkingSpotID, Vec2>,
+    #[serde(skip)]
+    journal: Option<ParkingDiff>,
+}
+
+/// Previous state of the spots changed while recording
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct ParkingDiff {
+    spots: SlotsDiff<ParkingSpotID, ParkingSpot>,
+    lane_spots: SlotsDiff<LaneID, Vec<ParkingSpotID>>,
+    building_spots: SlotsDiff<BuildingID, Vec<ParkingSpotID>>,
+}
+
+impl ParkingDiff {
+    pub fn is_empty(&self) -> bool {
+        self.spots.is_empty() && self.lane_spots.is_empty() && self.building_spots.is_empty()
+    }
+
+    pub(crate) fn contains_spot(&self, spot: ParkingSpotID) -> bool {
+        self.spots.contains(spot)
+    }
 }
 
 impl Default for ParkingSpots {
@@ -30,7 +57,9 @@ impl Default for ParkingSpots {
         Self {
             spots: Default::default(),
             lane_spots: Default::default(),
+            building_spots: Default::default(),
             reuse_spot: ShapeGrid::new(10),
+            journal: None,
         }
     }
 }
@@ -44,7 +73,47 @@ impl ParkingSpots {
         self.spots.contains_key(spot)
     }
 
+    pub(crate) fn start_recording(&mut self) {
+        self.spots.start_recording();
+        if self.journal.is_none() {
+            self.journal = Some(ParkingDiff::default());
+        }
+    }
+
+    pub(crate) fn stop_recording(&mut self) -> ParkingDiff {
+        let mut diff = self.journal.take().unwrap_or_default();
+        diff.spots = self.spots.stop_recording();
+        diff
+    }
+
+    /// Puts back the spots of the diff, which then holds the spots it replaced
+    pub(crate) fn swap(&mut self, diff: &mut ParkingDiff) {
+        self.spots.swap(&mut diff.spots);
+        diff.lane_spots.swap(&mut self.lane_spots, |_| true);
+        diff.building_spots.swap(&mut self.building_spots, |_| true);
+        for _ in self.reuse_spot.clear() {}
+    }
+
+    /// Frees the spot ids of a diff leaving the history, see [`Journaled::release`]
+    pub(crate) fn release(&mut self, dropped: &ParkingDiff, kept: impl Fn(ParkingSpotID) -> bool) {
+        self.spots.release(&dropped.spots, kept);
+    }
+
+    fn record_lane(&mut self, lane: LaneID) {
+        if let Some(ref mut j) = self.journal {
+            j.lane_spots.record(lane, self.lane_spots.get(lane));
+        }
+    }
+
+    fn record_building(&mut self, building: BuildingID) {
+        if let Some(ref mut j) = self.journal {
+            j.building_spots
+                .record(building, self.building_spots.get(building));
+        }
+    }
+
     pub fn remove_spots(&mut self, lane: LaneID) {
+        self.record_lane(lane);
         if let Some(spots) = self.lane_spots.remove(lane) {
             for spot in spots {
                 self.spots.remove(spot);
@@ -62,6 +131,7 @@ impl ParkingSpots {
     }
 
     pub fn remove_to_reuse(&mut self, lane: LaneID) {
+        self.record_lane(lane);
         if let Some(spots) = self.lane_spots.remove(lane) {
             for spot_id in spots {
                 let spot = unwrap_cont!(self.spots.get(spot_id));
@@ -76,6 +146,7 @@ impl ParkingSpots {
         if self.lane_spots.contains_key(lane.id) {
             self.remove_to_reuse(lane.id);
         }
+        self.record_lane(lane.id);
 
         let gap = CROSSWALK_WIDTH + 1.0;
         let l = lane.points.length() - gap * 2.0;
@@ -102,6 +173,7 @@ impl ParkingSpots {
                         *p = ParkingSpot {
                             parent,
                             trans: Transform::new_dir(pos, dir),
+                            building: None,
                         };
                         return spot_id;
                     } else {
@@ -112,6 +184,7 @@ impl ParkingSpots {
                 spots.insert(ParkingSpot {
                     parent,
                     trans: Transform::new_dir(pos, dir),
+                    building: None,
                 })
             })
             .collect();
@@ -119,9 +192,71 @@ impl ParkingSpots {
         self.lane_spots.insert(lane.id, spots);
     }
 
+    /// Lays out the spots of a parking lot or garage in rows facing away from the street.
+    /// Every level of a garage has the same layout, so the spots are stacked on top of each other.
+    /// `parent` is the driving lane the building is accessed from
+    pub fn generate_building_spots(&mut self, building: &Building, parent: LaneID, levels: u32) {
+        self.remove_building_spots(building.id);
+
+        let [w, h] = building.obb.axis();
+        let (w, h) = (w.magnitude(), h.magnitude());
+        let axis = (building.obb.corners[1] - building.obb.corners[0]).normalize();
+        let dir = Vec2::Y.rotated_by(axis).z0();
+        let center = building.obb.center().z(building.height);
+
+        let n_cols = (w / PARKING_SPOT_WIDTH) as i32;
+        let n_rows = (h / PARKING_ROW_DEPTH) as i32;
+
+        let mut ids = Vec::with_capacity((n_cols * n_rows) as usize * levels as usize);
+        for _ in 0..levels {
+            for row in 0..n_rows {
+                for col in 0..n_cols {
+                    let local = vec2(
+                        (col as f32 + 0.5) * PARKING_SPOT_WIDTH - w * 0.5,
+                        (row as f32 + 0.5) * PARKING_ROW_DEPTH - h * 0.5,
+                    );
+                    let id = self.spots.insert(ParkingSpot {
+                        parent,
+                        trans: Transform::new_dir(center + local.rotated_by(axis).z0(), dir),
+                        building: Some(building.id),
+                    });
+                    ids.push(id);
+                }
+            }
+        }
+
+        self.record_building(building.id);
+        self.building_spots.insert(building.id, ids);
+    }
+
+    pub fn remove_building_spots(&mut self, building: BuildingID) {
+        self.record_building(building);
+        if let Some(spots) = self.building_spots.remove(building) {
+            for spot in spots {
+                self.spots.remove(spot);
+            }
+        }
+    }
+
+    pub fn building_spots(&self, building: BuildingID) -> &[ParkingSpotID] {
+        self.building_spots
+            .get(building)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
     pub fn clear(&mut self) {
+        if let Some(ref mut j) = self.journal {
+            for (lane, spots) in &self.lane_spots {
+                j.lane_spots.record(lane, Some(spots));
+            }
+            for (building, spots) in &self.building_spots {
+                j.building_spots.record(building, Some(spots));
+            }
+        }
         self.spots.clear();
         self.lane_spots.clear();
+        self.building_spots.clear();
         for _ in self.reuse_spot.clear() {}
     }
 
@@ -0,0 +1,75 @@
+use crate::{BuildingID, Buildings, PowerLines};
+use geom::Vec3;
+use imgui_inspect::debug_inspect_impl;
+use serde::{Deserialize, Serialize};
+use slotmap::new_key_type;
+use std::collections::{BTreeMap, BTreeSet};
+
+new_key_type! {
+    pub struct PowerLineID;
+}
+
+debug_inspect_impl!(PowerLineID);
+
+/// Maximum length of a power line, longer distances need intermediate buildings
+pub const MAX_POWER_LINE_LENGTH: f32 = 500.0;
+/// Height above the ground at which lines are attached to buildings
+const ATTACH_HEIGHT: f32 = 10.0;
+
+/// Carries electricity between two buildings
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PowerLine {
+    pub id: PowerLineID,
+    pub src: BuildingID,
+    pub dst: BuildingID,
+}
+
+impl PowerLine {
+    pub fn connects(&self, b: BuildingID) -> bool {
+        self.src == b || self.dst == b
+    }
+
+    pub fn length(&self, buildings: &Buildings) -> Option<f32> {
+        let a = buildings.get(self.src)?.obb.center();
+        let b = buildings.get(self.dst)?.obb.center();
+        Some(a.distance(b))
+    }
+
+    /// Positions of the two ends of the line, above the buildings
+    pub fn ends(&self, buildings: &Buildings) -> Option<(Vec3, Vec3)> {
+        let end = |b: BuildingID| {
+            let b = buildings.get(b)?;
+            Some(b.obb.center().z(b.height + ATTACH_HEIGHT))
+        };
+        Some((end(self.src)?, end(self.dst)?))
+    }
+
+    /// Groups buildings linked together by power lines, buildings without any line are not part of a grid
+    pub fn grids(lines: &PowerLines) -> Vec<Vec<BuildingID>> {
+        let mut adjacency: BTreeMap<BuildingID, Vec<BuildingID>> = BTreeMap::new();
+        for line in lines.values() {
+            adjacency.entry(line.src).or_default().push(line.dst);
+            adjacency.entry(line.dst).or_default().push(line.src);
+        }
+
+        let mut grids = vec![];
+        let mut seen = BTreeSet::new();
+        for &start in adjacency.keys() {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut grid = vec![];
+            let mut stack = vec![start];
+            while let Some(b) = stack.pop() {
+                grid.push(b);
+                for &next in &adjacency[&b] {
+                    if seen.insert(next) {
+                        stack.push(next);
+                    }
+                }
+            }
+            grids.push(grid);
+        }
+        grids
+    }
+}
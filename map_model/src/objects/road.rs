@@ -125,6 +125,21 @@ impl Road {
             .copied()
     }
 
+    /// Lanes right next to the given one going the same way
+    pub fn adjacent_lanes(&self, lane: LaneID) -> impl Iterator<Item = (LaneID, LaneKind)> + '_ {
+        let lanes = if self.lanes_forward.iter().any(|&(id, _)| id == lane) {
+            &self.lanes_forward
+        } else {
+            &self.lanes_backward
+        };
+        let i = lanes.iter().position(|&(id, _)| id == lane);
+
+        i.and_then(|i| i.checked_sub(1))
+            .into_iter()
+            .chain(i.map(|i| i + 1))
+            .filter_map(move |i| lanes.get(i).copied())
+    }
+
     pub fn sidewalks(&self, from: IntersectionID) -> LanePair {
         self.mk_pair(from, |lanes| {
             lanes
@@ -1,5 +1,6 @@
 use crate::{
-    LaneID, LaneKind, LanePatternBuilder, Map, Traversable, TraverseDirection, TraverseKind, TurnID,
+    Lane, LaneID, LaneKind, LanePatternBuilder, Map, Traversable, TraverseDirection, TraverseKind,
+    TurnID,
 };
 use geom::{PolyLine3, Vec3};
 use ordered_float::OrderedFloat;
@@ -16,27 +17,115 @@ pub trait Pathfinder {
 pub enum PathKind {
     Pedestrian,
     Vehicle,
+    /// Like Vehicle, but may also use bus lanes
+    Bus,
+    /// Prefers bike lanes but may also use driving lanes
+    Bike,
+}
+
+/// Speed of bikes, used to estimate travel costs
+const BIKE_SPEED: f32 = 6.0;
+/// Cost multiplier of driving lanes for bikes, so that bike lanes are preferred
+const BIKE_DRIVING_LANE_PENALTY: f32 = 1.5;
+/// Time lost making a U-turn, smaller turns cost proportionally less
+const TURN_PENALTY: f32 = 8.0;
+/// Time usually lost waiting at a light or a stop sign
+const CONTROL_PENALTY: f32 = 4.0;
+/// Time lost waiting for a gap to change lanes
+const LANE_CHANGE_PENALTY: f32 = 3.0;
+/// Lanes are never considered slower than this, even when traffic is stuck
+const MIN_TRAVEL_SPEED: f32 = 0.5;
+
+impl PathKind {
+    /// Whether vehicles following this kind of path can use lanes of the given kind
+    pub fn allows(self, lane: LaneKind) -> bool {
+        match self {
+            PathKind::Pedestrian => lane == LaneKind::Walking,
+            PathKind::Vehicle => lane == LaneKind::Driving,
+            PathKind::Bus => matches!(lane, LaneKind::Driving | LaneKind::Bus),
+            PathKind::Bike => matches!(lane, LaneKind::Driving | LaneKind::Biking),
+        }
+    }
+
+    /// Time needed to traverse the lane at the given speed, for vehicles
+    fn lane_cost(self, lane: &Lane, speed: f32) -> f32 {
+        let speed = speed.max(MIN_TRAVEL_SPEED);
+        match self {
+            PathKind::Bike => {
+                let penalty = if lane.kind == LaneKind::Biking {
+                    1.0
+                } else {
+                    BIKE_DRIVING_LANE_PENALTY
+                };
+                lane.points.length() * penalty / speed.min(BIKE_SPEED)
+            }
+            _ => lane.points.length() / speed,
+        }
+    }
+
+    /// Time lost going from one lane to the next through the intersection
+    pub(crate) fn turn_cost(from: &Lane, to: &Lane) -> f32 {
+        let dir_in = -from.orientation_from(from.dst);
+        let dir_out = to.orientation_from(to.src);
+        let control = if from.control.is_always() {
+            0.0
+        } else {
+            CONTROL_PENALTY
+        };
+        TURN_PENALTY * (1.0 - dir_in.dot(dir_out)) * 0.5 + control
+    }
+
+    /// Cost of going from one lane to the next, either by turning or by changing lanes.
+    /// The lane changed from was already paid for, so only the difference is added.
+    /// Closed lanes can't be entered.
+    pub(crate) fn step_cost(self, from: &Lane, to: &Lane) -> f32 {
+        self.step_cost_at(from, from.travel_speed(), to, to.travel_speed())
+    }
+
+    /// Cost of the step had the lanes been driven through at the given speeds
+    pub(crate) fn step_cost_at(
+        self,
+        from: &Lane,
+        from_speed: f32,
+        to: &Lane,
+        to_speed: f32,
+    ) -> f32 {
+        let to_cost = self.lane_cost(to, to_speed);
+        if to.is_closed() {
+            f32::INFINITY
+        } else if from.parent == to.parent && from.dst == to.dst {
+            LANE_CHANGE_PENALTY + (to_cost - self.lane_cost(from, from_speed)).max(0.0)
+        } else {
+            to_cost + Self::turn_cost(from, to)
+        }
+    }
 }
 
 impl Pathfinder for PathKind {
     fn path(&self, map: &Map, start: Traversable, end: LaneID) -> Option<Vec<Traversable>> {
         match self {
             PathKind::Pedestrian => PedestrianPath.path(map, start, end),
-            PathKind::Vehicle => CarPath.path(map, start, end),
+            PathKind::Vehicle | PathKind::Bus | PathKind::Bike => {
+                CarPath(*self).path(map, start, end)
+            }
         }
     }
 
     fn nearest_lane(&self, map: &Map, pos: Vec3) -> Option<LaneID> {
         match self {
             PathKind::Pedestrian => PedestrianPath.nearest_lane(map, pos),
-            PathKind::Vehicle => CarPath.nearest_lane(map, pos),
+            PathKind::Vehicle | PathKind::Bus | PathKind::Bike => {
+                CarPath(*self).nearest_lane(map, pos)
+            }
         }
     }
 
     fn local_route(&self, map: &Map, lane: LaneID, start: Vec3, end: Vec3) -> Option<PolyLine3> {
         match self {
             PathKind::Pedestrian => PedestrianPath.local_route(map, lane, start, end),
-            PathKind::Vehicle => CarPath.local_route(map, lane, start, end),
+            PathKind::Vehicle | PathKind::Bus | PathKind::Bike => {
+                CarPath(*self).local_route(map, lane, start, end)
+            }
         }
     }
 }
@@ -95,7 +184,7 @@ impl Pathfinder for PedestrianPath {
 
         let has_arrived = |p: &Traversable| match p.kind {
             TraverseKind::Lane(id) => id == end,
-            TraverseKind::Turn(_) => false,
+            _ => false,
         };
 
         pathfinding::directed::astar::astar(&start, successors, heuristic, has_arrived)
@@ -123,7 +212,51 @@ impl Pathfinder for PedestrianPath {
     }
 }
 
-struct CarPath;
+struct CarPath(PathKind);
+
+impl CarPath {
+    /// Links the lanes to go through after `start` with the turns between them.
+    /// A lane followed by the one next to it is replaced by a lane change.
+    fn lanes_to_path(
+        map: &Map,
+        start: Traversable,
+        v: impl IntoIterator<Item = LaneID>,
+    ) -> Option<Vec<Traversable>> {
+        let v = v.into_iter();
+        let mut path = Vec::with_capacity(v.size_hint().0 * 2 + 1);
+        path.push(start);
+
+        let mut last_id = start.destination_lane();
+
+        for lane in v {
+            let l = map.lanes.get(lane)?;
+            let last = map.lanes.get(last_id)?;
+            if l.parent == last.parent && l.dst == last.dst {
+                let change = TraverseKind::LaneChange {
+                    from: last_id,
+                    to: lane,
+                };
+                match path.last_mut() {
+                    Some(t) if t.kind == TraverseKind::Lane(last_id) => t.kind = change,
+                    _ => path.push(Traversable::new(change, TraverseDirection::Forward)),
+                }
+            } else {
+                let id = TurnID::new(l.src, last_id, lane, false);
+                path.push(Traversable::new(
+                    TraverseKind::Turn(id),
+                    TraverseDirection::Forward,
+                ));
+            }
+            path.push(Traversable::new(
+                TraverseKind::Lane(lane),
+                TraverseDirection::Forward,
+            ));
+
+            last_id = lane;
+        }
+        Some(path)
+    }
+}
 
 impl Pathfinder for CarPath {
     fn path(&self, map: &Map, start: Traversable, end: LaneID) -> Option<Vec<Traversable>> {
@@ -131,6 +264,12 @@ impl Pathfinder for CarPath {
         let lanes = &map.lanes;
 
         let start_lane = start.destination_lane();
+        let kind = self.0;
+
+        if map.routing.is_up_to_date(map.dirt_id.0) && map.routing.covers(kind, start_lane, end) {
+            let v = map.routing.path(kind, start_lane, end)?;
+            return Self::lanes_to_path(map, start, v);
+        }
 
         let end_pos = inters.get(lanes.get(end)?.dst)?.pos;
 
@@ -156,46 +295,38 @@ impl Pathfinder for CarPath {
                 l = lanes.get(p);
                 p
             };
+            let lane_changes = l
+                .and_then(|x| map.roads.get(x.parent))
+                .into_iter()
+                .flat_map(move |road| road.adjacent_lanes(p))
+                .map(|(id, _)| id);
+
             l.and_then(|x| inters.get(x.dst))
                 .into_iter()
-                .flat_map(move |inter| {
-                    inter.turns_from(p).map(move |(x, _)| {
-                        let cost = lanes
-                            .get(x.dst)
-                            .map(|p| p.points.length() / p.speed_limit)
-                            .unwrap_or(f32::INFINITY);
-                        (x.dst, OrderedFloat(cost))
-                    })
+                .flat_map(move |inter| inter.turns_from(p).map(|(x, _)| x.dst))
+                .chain(lane_changes)
+                .filter_map(move |dst| {
+                    let lane = lanes.get(dst)?;
+                    if !kind.allows(lane.kind) || lane.is_closed() {
+                        return None;
+                    }
+                    let cost = l.map_or(0.0, |from| kind.step_cost(from, lane));
+                    Some((dst, OrderedFloat(cost)))
                 })
         };
 
         let (v, _) =
             pathfinding::directed::astar::astar(&dummy, successors, heuristic, |p| *p == end)?;
 
-        let mut path = Vec::with_capacity(v.len() * 2);
-        path.push(start);
-
-        let mut last_id = start_lane;
-
-        for lane in v.into_iter().skip(1) {
-            let inter_end = &inters.get(lanes.get(lane)?.src)?;
-            let id = TurnID::new(inter_end.id, last_id, lane, false);
-            path.push(Traversable::new(
-                TraverseKind::Turn(id),
-                TraverseDirection::Forward,
-            ));
-            path.push(Traversable::new(
-                TraverseKind::Lane(lane),
-                TraverseDirection::Forward,
-            ));
-
-            last_id = lane;
-        }
-        Some(path)
+        Self::lanes_to_path(map, start, v.into_iter().skip(1))
     }
 
     fn nearest_lane(&self, map: &Map, pos: Vec3) -> Option<LaneID> {
-        map.nearest_lane(pos, LaneKind::Driving)
+        map.lanes
+            .iter()
+            .filter(|(_, x)| self.0.allows(x.kind))
+            .min_by_key(|(_, lane)| OrderedFloat(lane.points.project_dist2(pos)))
+            .map(|(id, _)| id)
     }
 
     fn local_route(&self, map: &Map, lane: LaneID, start: Vec3, end: Vec3) -> Option<PolyLine3> {
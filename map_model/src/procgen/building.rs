@@ -196,6 +196,35 @@ pub fn gen_exterior_farm(size: f32, seed: u64) -> (ColoredMesh, Vec2) {
     (mesh, door_pos)
 }
 
+/// Height of a single level of a parking garage
+pub const PARKING_LEVEL_HEIGHT: f32 = 3.0;
+
+/// A flat paved lot when `levels` is 1, otherwise a concrete garage with the entrance facing
+/// the street
+pub fn gen_exterior_parking(size: f32, levels: u32) -> (ColoredMesh, Vec2) {
+    let h = size * 0.5;
+    let corners = [vec2(-h, -h), vec2(h, -h), vec2(h, h), vec2(-h, h)];
+    let mut mesh = ColoredMesh::default();
+
+    let ground = corners.iter().map(|c| c.z(0.05)).collect();
+    mesh.faces.push((ground, LinearColor::gray(0.3)));
+
+    if levels > 1 {
+        let height = levels as f32 * PARKING_LEVEL_HEIGHT;
+        let wall_col = LinearColor::gray(0.6);
+        for i in 0..4 {
+            let a = corners[i];
+            let b = corners[(i + 1) % 4];
+            mesh.faces
+                .push((vec![a.z0(), b.z0(), b.z(height), a.z(height)], wall_col));
+        }
+        let roof = corners.iter().map(|c| c.z(height)).collect();
+        mesh.faces.push((roof, LinearColor::gray(0.45)));
+    }
+
+    (mesh, Vec2::y(-h))
+}
+
 // How to gen a house
 // Idea: Make everything out of rectangles
 // 1. Make exterior
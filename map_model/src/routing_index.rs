@@ -0,0 +1,710 @@
+//! Customizable contraction hierarchies over the lane graph, so that vehicle paths can be found
+//! without exploring the whole map.
+//!
+//! Building the index is done in two steps:
+//! - The contraction only depends on which lanes connect to which. It orders the lanes and adds
+//!   shortcuts so that any path can be found going "up" the order from both ends.
+//! - The customization computes the weight of every arc and shortcut from the lane costs.
+//!
+//! The contraction is only redone when the lane graph actually changes, whereas the customization
+//! is redone periodically to follow the observed lane speeds.
+
+use crate::{Intersections, Lane, LaneID, Lanes, PathKind, Roads};
+use geom::Vec2;
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use slotmap::{Key, SecondaryMap};
+use std::collections::BTreeMap;
+
+const NONE: u32 = u32::MAX;
+
+/// Lanes usable by a kind of path, and the turns and lane changes between them
+#[derive(Default, PartialEq)]
+struct LaneGraph {
+    lanes: Vec<LaneID>,
+    /// Middle of each lane, used to order them
+    pos: Vec<Vec2>,
+    arcs: Vec<(u32, u32)>,
+}
+
+impl LaneGraph {
+    fn new(kind: PathKind, lanes: &Lanes, inters: &Intersections, roads: &Roads) -> Self {
+        let mut graph = Self::default();
+        let mut node_of = SecondaryMap::new();
+        for (id, lane) in lanes {
+            if kind.allows(lane.kind) {
+                node_of.insert(id, graph.lanes.len() as u32);
+                graph.lanes.push(id);
+                graph
+                    .pos
+                    .push(((lane.points.first() + lane.points.last()) * 0.5).xy());
+            }
+        }
+
+        for (a, &id) in graph.lanes.iter().enumerate() {
+            let lane = unwrap_cont!(lanes.get(id));
+            let inter = unwrap_cont!(inters.get(lane.dst));
+            let road = unwrap_cont!(roads.get(lane.parent));
+            let dsts = inter
+                .turns_from(id)
+                .map(|(turn, _)| turn.dst)
+                .chain(road.adjacent_lanes(id).map(|(id, _)| id));
+            for dst in dsts {
+                if let Some(&b) = node_of.get(dst) {
+                    graph.arcs.push((a as u32, b));
+                }
+            }
+        }
+        graph
+    }
+}
+
+/// Contraction hierarchy of the lane graph of one kind of path.
+/// Nodes are designated by their rank in the contraction order.
+struct Cch {
+    graph: LaneGraph,
+    /// Rank of each node of the graph
+    node_rank: Vec<u32>,
+    rank_of: SecondaryMap<LaneID, u32>,
+    lane_at: Vec<LaneID>,
+
+    /// Arcs towards higher ranks, arcs of rank r are `head[first_out[r]..first_out[r + 1]]`
+    first_out: Vec<u32>,
+    head: Vec<u32>,
+    /// Cost of going up the arc
+    up_weight: Vec<f32>,
+    /// Cost of going down the arc
+    down_weight: Vec<f32>,
+    /// Node the shortcut goes through when going up, NONE if it is an actual turn
+    up_mid: Vec<u32>,
+    down_mid: Vec<u32>,
+}
+
+impl Cch {
+    /// Contracts the nodes in nested dissection order, linking the remaining neighbours of each
+    /// node so that the shortest paths are kept
+    fn new(graph: LaneGraph) -> Self {
+        let n = graph.lanes.len();
+        let mut adj: Vec<Vec<u32>> = vec![vec![]; n];
+        for &(a, b) in &graph.arcs {
+            if a != b {
+                adj[a as usize].push(b);
+                adj[b as usize].push(a);
+            }
+        }
+        for neighs in &mut adj {
+            neighs.sort_unstable();
+            neighs.dedup();
+        }
+
+        let mut order = Vec::with_capacity(n);
+        let mut nodes: Vec<u32> = (0..n as u32).collect();
+        dissect(
+            &mut nodes,
+            &graph.pos,
+            &adj,
+            &mut vec![false; n],
+            &mut order,
+        );
+
+        let mut node_rank = vec![NONE; n];
+        for (r, &v) in order.iter().enumerate() {
+            node_rank[v as usize] = r as u32;
+        }
+
+        // The upper neighbours of a node once the lower ones are contracted are its original
+        // upper neighbours, plus those of its children in the elimination tree
+        let mut ups: Vec<Vec<u32>> = vec![vec![]; n];
+        for (v, neighs) in adj.iter().enumerate() {
+            let r = node_rank[v];
+            let mut heads: Vec<u32> = neighs
+                .iter()
+                .map(|&u| node_rank[u as usize])
+                .filter(|&u| u > r)
+                .collect();
+            heads.sort_unstable();
+            ups[r as usize] = heads;
+        }
+        for r in 0..n {
+            let heads = std::mem::take(&mut ups[r]);
+            if let Some((&parent, rest)) = heads.split_first() {
+                ups[parent as usize] = merge_sorted(&ups[parent as usize], rest);
+            }
+            ups[r] = heads;
+        }
+
+        let mut lane_at = vec![LaneID::null(); n];
+        let mut rank_of = SecondaryMap::with_capacity(n);
+        for (v, &r) in node_rank.iter().enumerate() {
+            lane_at[r as usize] = graph.lanes[v];
+            rank_of.insert(graph.lanes[v], r);
+        }
+
+        let mut first_out = Vec::with_capacity(n + 1);
+        let mut head = vec![];
+        first_out.push(0);
+        for heads in ups {
+            head.extend(heads);
+            first_out.push(head.len() as u32);
+        }
+
+        let m = head.len();
+        Self {
+            graph,
+            node_rank,
+            rank_of,
+            lane_at,
+            first_out,
+            head,
+            up_weight: vec![f32::INFINITY; m],
+            down_weight: vec![f32::INFINITY; m],
+            up_mid: vec![NONE; m],
+            down_mid: vec![NONE; m],
+        }
+    }
+
+    fn arcs(&self, r: u32) -> std::ops::Range<usize> {
+        self.first_out[r as usize] as usize..self.first_out[r as usize + 1] as usize
+    }
+
+    /// Arc between lo and hi, with lo ranked lower than hi
+    fn arc(&self, lo: u32, hi: u32) -> Option<usize> {
+        let range = self.arcs(lo);
+        let start = range.start;
+        self.head[range].binary_search(&hi).ok().map(|i| start + i)
+    }
+
+    /// Computes the weights of the turns, then of the shortcuts from the lowest ranks up
+    fn customize(&mut self, kind: PathKind, lanes: &Lanes, speeds: &BTreeMap<LaneID, f32>) {
+        for w in self.up_weight.iter_mut().chain(self.down_weight.iter_mut()) {
+            *w = f32::INFINITY;
+        }
+        for m in self.up_mid.iter_mut().chain(self.down_mid.iter_mut()) {
+            *m = NONE;
+        }
+
+        for &(a, b) in &self.graph.arcs {
+            let from = unwrap_cont!(lanes.get(self.graph.lanes[a as usize]));
+            let to = unwrap_cont!(lanes.get(self.graph.lanes[b as usize]));
+            let speed = |lane: &Lane| lane.travel_speed_at(speeds.get(&lane.id).copied());
+            let cost = kind.step_cost_at(from, speed(from), to, speed(to));
+
+            let (ra, rb) = (self.node_rank[a as usize], self.node_rank[b as usize]);
+            if ra < rb {
+                let e = unwrap_cont!(self.arc(ra, rb));
+                self.up_weight[e] = self.up_weight[e].min(cost);
+            } else if rb < ra {
+                let e = unwrap_cont!(self.arc(rb, ra));
+                self.down_weight[e] = self.down_weight[e].min(cost);
+            }
+        }
+
+        // arc from v to each of its upper neighbours, to find triangles without searching
+        let mut arc_to = vec![NONE; self.lane_at.len()];
+        for v in 0..self.lane_at.len() as u32 {
+            let range = self.arcs(v);
+            for e in range.clone() {
+                arc_to[self.head[e] as usize] = e as u32;
+            }
+            for i in range.clone() {
+                let u = self.head[i];
+                for e in self.arcs(u) {
+                    let j = arc_to[self.head[e] as usize];
+                    if j == NONE {
+                        continue;
+                    }
+                    let j = j as usize;
+
+                    // u -> v -> w
+                    let cost = self.down_weight[i] + self.up_weight[j];
+                    if cost < self.up_weight[e] {
+                        self.up_weight[e] = cost;
+                        self.up_mid[e] = v;
+                    }
+                    // w -> v -> u
+                    let cost = self.down_weight[j] + self.up_weight[i];
+                    if cost < self.down_weight[e] {
+                        self.down_weight[e] = cost;
+                        self.down_mid[e] = v;
+                    }
+                }
+            }
+            for e in range {
+                arc_to[self.head[e] as usize] = NONE;
+            }
+        }
+    }
+
+    /// Searches upwards from both ends and meets at the node minimizing the total cost
+    fn path(&self, start: u32, end: u32) -> Option<Vec<LaneID>> {
+        let forward = self.upward_search(start, &self.up_weight);
+        let backward = self.upward_search(end, &self.down_weight);
+
+        // both searches end at the root, so they meet on the ancestors they share
+        let (meet, _) = forward
+            .visited
+            .iter()
+            .map(|&x| (x, forward.dist[x as usize] + backward.dist[x as usize]))
+            .filter(|(_, cost)| cost.is_finite())
+            .min_by_key(|&(x, cost)| (OrderedFloat(cost), x))?;
+
+        let mut ranks = vec![meet];
+        let mut cur = meet;
+        while forward.pred[cur as usize] != NONE {
+            cur = forward.pred[cur as usize];
+            ranks.push(cur);
+        }
+        ranks.reverse();
+        cur = meet;
+        while backward.pred[cur as usize] != NONE {
+            cur = backward.pred[cur as usize];
+            ranks.push(cur);
+        }
+
+        let mut nodes = Vec::with_capacity(ranks.len() * 2);
+        for w in ranks.windows(2) {
+            self.unpack(w[0], w[1], &mut nodes)?;
+        }
+        Some(
+            nodes
+                .into_iter()
+                .map(|r| self.lane_at[r as usize])
+                .collect(),
+        )
+    }
+
+    /// Every node reachable going up from a node is one of its ancestors in the elimination tree,
+    /// where the parent of a node is its lowest ranked upper neighbour.
+    /// Relaxing the ancestors in order is then enough, no priority queue is needed.
+    fn upward_search(&self, from: u32, weights: &[f32]) -> UpwardSearch {
+        let n = self.lane_at.len();
+        let mut search = UpwardSearch {
+            visited: vec![],
+            dist: vec![f32::INFINITY; n],
+            pred: vec![NONE; n],
+        };
+        search.dist[from as usize] = 0.0;
+
+        let mut x = from;
+        loop {
+            search.visited.push(x);
+            let d = search.dist[x as usize];
+            let arcs = self.arcs(x);
+            if d.is_finite() {
+                for e in arcs.clone() {
+                    let y = self.head[e] as usize;
+                    let nd = d + weights[e];
+                    if nd < search.dist[y] {
+                        search.dist[y] = nd;
+                        search.pred[y] = x;
+                    }
+                }
+            }
+            if arcs.is_empty() {
+                break;
+            }
+            x = self.head[arcs.start];
+        }
+        search
+    }
+
+    /// Replaces a shortcut from a to b by the nodes it goes through, a excluded
+    fn unpack(&self, a: u32, b: u32, out: &mut Vec<u32>) -> Option<()> {
+        let mut stack = vec![(a, b)];
+        while let Some((a, b)) = stack.pop() {
+            let mid = if a < b {
+                self.up_mid[self.arc(a, b)?]
+            } else {
+                self.down_mid[self.arc(b, a)?]
+            };
+            if mid == NONE {
+                out.push(b);
+            } else {
+                stack.push((mid, b));
+                stack.push((a, mid));
+            }
+        }
+        Some(())
+    }
+}
+
+struct UpwardSearch {
+    /// Ancestors of the start node, from the bottom up
+    visited: Vec<u32>,
+    dist: Vec<f32>,
+    pred: Vec<u32>,
+}
+
+/// Below this many lanes, a part of the map isn't split anymore
+const DISSECTION_LEAF: usize = 16;
+
+/// Orders the nodes by nested dissection: they are split in two halves along their longest axis,
+/// both halves are ordered first and the nodes linking them come last.
+fn dissect(
+    nodes: &mut [u32],
+    pos: &[Vec2],
+    adj: &[Vec<u32>],
+    in_right: &mut [bool],
+    order: &mut Vec<u32>,
+) {
+    if nodes.len() <= DISSECTION_LEAF {
+        order.extend_from_slice(nodes);
+        return;
+    }
+
+    let mut ll = Vec2::new(f32::INFINITY, f32::INFINITY);
+    let mut ur = Vec2::new(f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for &v in nodes.iter() {
+        ll = ll.min(pos[v as usize]);
+        ur = ur.max(pos[v as usize]);
+    }
+    let extent = ur - ll;
+    let coord = |v: u32| {
+        let p = pos[v as usize];
+        if extent.x > extent.y {
+            p.x
+        } else {
+            p.y
+        }
+    };
+    nodes.sort_by_key(|&v| (OrderedFloat(coord(v)), v));
+
+    let (left, right) = nodes.split_at_mut(nodes.len() / 2);
+    for &v in right.iter() {
+        in_right[v as usize] = true;
+    }
+    let (mut separator, mut left): (Vec<u32>, Vec<u32>) = left
+        .iter()
+        .partition(|&&v| adj[v as usize].iter().any(|&u| in_right[u as usize]));
+    for &v in right.iter() {
+        in_right[v as usize] = false;
+    }
+
+    dissect(&mut left, pos, adj, in_right, order);
+    dissect(right, pos, adj, in_right, order);
+    separator.sort_unstable();
+    order.append(&mut separator);
+}
+
+fn merge_sorted(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            merged.push(a[i]);
+            i += 1;
+        } else {
+            if a[i] == b[j] {
+                i += 1;
+            }
+            merged.push(b[j]);
+            j += 1;
+        }
+    }
+    merged.extend_from_slice(&a[i..]);
+    merged.extend_from_slice(&b[j..]);
+    merged
+}
+
+/// The index is only rebuilt once the map stopped changing for this long, in seconds,
+/// so that it isn't rebuilt for every road placed while editing
+pub const ROUTING_REBUILD_DELAY: u32 = 3;
+/// How often the weights are recomputed from the observed lane speeds, in seconds
+pub const ROUTING_CUSTOMIZE_PERIOD: u32 = 30;
+
+/// What the index was last built from. It is saved along with the map so that a loaded map,
+/// or a client joining a game, builds the exact same index and finds the same paths.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub(crate) struct RoutingState {
+    /// Map dirt id at which the index was last updated
+    dirt_id: u32,
+    /// Last map dirt id seen and since when, in seconds
+    seen: (u32, u32),
+    customized_at: u32,
+    /// Observed speeds of the lanes when the weights were last computed
+    speeds: BTreeMap<LaneID, f32>,
+}
+
+/// Precomputed routing for the kinds of paths vehicles use.
+///
+/// Changes that keep the lane graph as is, such as closures or lights, only need new weights
+/// and are applied right away. Other changes contract the graphs again from scratch:
+/// the nested dissection order is global, so a new road may change the rank of any lane.
+#[derive(Default)]
+pub struct RoutingIndex {
+    vehicle: Option<Cch>,
+    /// None when buses can only use the same lanes as other vehicles
+    bus: Option<Cch>,
+    bike: Option<Cch>,
+    pub(crate) state: RoutingState,
+}
+
+impl RoutingIndex {
+    /// Builds the index back from the state saved with the map
+    pub(crate) fn restore(
+        state: RoutingState,
+        lanes: &Lanes,
+        inters: &Intersections,
+        roads: &Roads,
+        dirt_id: u32,
+    ) -> Self {
+        let mut index = Self {
+            state,
+            ..Self::default()
+        };
+        if index.is_up_to_date(dirt_id) {
+            index.rebuild(Self::graphs(lanes, inters, roads));
+            index.customize(lanes);
+        }
+        index
+    }
+
+    pub(crate) fn is_up_to_date(&self, dirt_id: u32) -> bool {
+        self.state.dirt_id == dirt_id
+    }
+
+    fn cch(&self, kind: PathKind) -> Option<&Cch> {
+        match kind {
+            PathKind::Vehicle => self.vehicle.as_ref(),
+            PathKind::Bus => self.bus.as_ref().or(self.vehicle.as_ref()),
+            PathKind::Bike => self.bike.as_ref(),
+            PathKind::Pedestrian => None,
+        }
+    }
+
+    /// Rebuilds the index once the map stopped changing, and customizes it periodically
+    pub(crate) fn update(
+        &mut self,
+        lanes: &Lanes,
+        inters: &Intersections,
+        roads: &Roads,
+        dirt_id: u32,
+        now: u32,
+    ) {
+        if self.state.seen.0 != dirt_id {
+            self.state.seen = (dirt_id, now);
+
+            let graphs = Self::graphs(lanes, inters, roads);
+            if self.same_graphs(&graphs) {
+                self.state.dirt_id = dirt_id;
+                self.state.speeds = observed_speeds(lanes);
+                self.customize(lanes);
+                self.state.customized_at = now;
+                return;
+            }
+        }
+
+        if !self.is_up_to_date(dirt_id) {
+            if now < self.state.seen.1 + ROUTING_REBUILD_DELAY {
+                return;
+            }
+            self.rebuild(Self::graphs(lanes, inters, roads));
+            self.state.dirt_id = dirt_id;
+        } else if now < self.state.customized_at + ROUTING_CUSTOMIZE_PERIOD {
+            return;
+        }
+
+        self.state.speeds = observed_speeds(lanes);
+        self.customize(lanes);
+        self.state.customized_at = now;
+    }
+
+    /// Lane graphs of vehicles, buses and bikes. The bus one is None if it is the same as the
+    /// vehicle one.
+    fn graphs(lanes: &Lanes, inters: &Intersections, roads: &Roads) -> [Option<LaneGraph>; 3] {
+        let vehicle = LaneGraph::new(PathKind::Vehicle, lanes, inters, roads);
+        let bus =
+            Some(LaneGraph::new(PathKind::Bus, lanes, inters, roads)).filter(|g| *g != vehicle);
+        let bike = LaneGraph::new(PathKind::Bike, lanes, inters, roads);
+        [Some(vehicle), bus, Some(bike)]
+    }
+
+    fn same_graphs(&self, graphs: &[Option<LaneGraph>; 3]) -> bool {
+        [&self.vehicle, &self.bus, &self.bike]
+            .iter()
+            .zip(graphs.iter())
+            .all(|(cch, graph)| cch.as_ref().map(|cch| &cch.graph) == graph.as_ref())
+    }
+
+    /// Contracts again the lane graphs that changed
+    fn rebuild(&mut self, graphs: [Option<LaneGraph>; 3]) {
+        let [vehicle, bus, bike] = graphs;
+        for (cch, graph) in [
+            (&mut self.vehicle, vehicle),
+            (&mut self.bus, bus),
+            (&mut self.bike, bike),
+        ] {
+            match graph {
+                Some(graph) if matches!(cch, Some(cch) if cch.graph == graph) => {}
+                Some(graph) => *cch = Some(Cch::new(graph)),
+                None => *cch = None,
+            }
+        }
+    }
+
+    /// Updates the weights from the lane costs, at the saved lane speeds
+    fn customize(&mut self, lanes: &Lanes) {
+        for (kind, cch) in [
+            (PathKind::Vehicle, &mut self.vehicle),
+            (PathKind::Bus, &mut self.bus),
+            (PathKind::Bike, &mut self.bike),
+        ] {
+            if let Some(cch) = cch {
+                cch.customize(kind, lanes, &self.state.speeds);
+            }
+        }
+    }
+
+    /// Whether `path` can answer this query
+    pub(crate) fn covers(&self, kind: PathKind, start: LaneID, end: LaneID) -> bool {
+        let cch = unwrap_ret!(self.cch(kind), false);
+        start != end && cch.rank_of.contains_key(start) && cch.rank_of.contains_key(end)
+    }
+
+    /// Lanes to go through after `start` to get to `end`, `end` included
+    pub(crate) fn path(&self, kind: PathKind, start: LaneID, end: LaneID) -> Option<Vec<LaneID>> {
+        let cch = self.cch(kind)?;
+        cch.path(*cch.rank_of.get(start)?, *cch.rank_of.get(end)?)
+    }
+}
+
+fn observed_speeds(lanes: &Lanes) -> BTreeMap<LaneID, f32> {
+    lanes
+        .iter()
+        .filter_map(|(id, lane)| Some((id, lane.observed_speed?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cch, LaneGraph, RoutingIndex, ROUTING_CUSTOMIZE_PERIOD, ROUTING_REBUILD_DELAY};
+    use crate::procgen::load_testfield;
+    use crate::{LaneID, Map, PathKind};
+    use common::saveload::{Bincode, Encoder};
+    use geom::Vec2;
+    use ordered_float::OrderedFloat;
+    use std::cmp::Reverse;
+    use std::collections::{BTreeMap, BinaryHeap};
+
+    #[test]
+    fn test_cch_matches_dijkstra() {
+        let mut m = Map::empty();
+        load_testfield(&mut m, Vec2::ZERO, 5, 100.0);
+
+        let kind = PathKind::Vehicle;
+        let graph = LaneGraph::new(kind, &m.lanes, &m.intersections, &m.roads);
+        let lanes = graph.lanes.clone();
+        let arcs = graph.arcs.clone();
+        let n = lanes.len();
+        let cost = |a: u32, b: u32| {
+            let from = &m.lanes[lanes[a as usize]];
+            let to = &m.lanes[lanes[b as usize]];
+            kind.step_cost(from, to)
+        };
+
+        let mut out = vec![vec![]; n];
+        for &(a, b) in &arcs {
+            out[a as usize].push((b, cost(a, b)));
+        }
+        let dijkstra = |start: u32| {
+            let mut dist = vec![f32::INFINITY; n];
+            let mut heap = BinaryHeap::new();
+            dist[start as usize] = 0.0;
+            heap.push(Reverse((OrderedFloat(0.0), start)));
+            while let Some(Reverse((OrderedFloat(d), x))) = heap.pop() {
+                if d > dist[x as usize] {
+                    continue;
+                }
+                for &(y, c) in &out[x as usize] {
+                    if d + c < dist[y as usize] {
+                        dist[y as usize] = d + c;
+                        heap.push(Reverse((OrderedFloat(d + c), y)));
+                    }
+                }
+            }
+            dist
+        };
+
+        let node_of = |lane| lanes.iter().position(|&l| l == lane).unwrap() as u32;
+        let mut cch = Cch::new(graph);
+        cch.customize(kind, &m.lanes, &BTreeMap::new());
+
+        for start in (0..n as u32).step_by(7) {
+            let expected = dijkstra(start);
+            for end in (0..n as u32).step_by(5).filter(|&end| end != start) {
+                let path = cch.path(
+                    cch.rank_of[lanes[start as usize]],
+                    cch.rank_of[lanes[end as usize]],
+                );
+                let path = match path {
+                    Some(path) => path,
+                    None => {
+                        assert!(expected[end as usize].is_infinite());
+                        continue;
+                    }
+                };
+
+                assert_eq!(*path.last().unwrap(), lanes[end as usize]);
+                let mut total = 0.0;
+                let mut last = start;
+                for lane in path {
+                    let node = node_of(lane);
+                    assert!(arcs.contains(&(last, node)));
+                    total += cost(last, node);
+                    last = node;
+                }
+                assert!((total - expected[end as usize]).abs() < 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_survives_save_load() {
+        let mut m = Map::empty();
+        load_testfield(&mut m, Vec2::ZERO, 5, 100.0);
+        m.update_routing_index(0);
+        m.update_routing_index(ROUTING_REBUILD_DELAY);
+        assert!(m.routing.is_up_to_date(m.dirt_id.0));
+
+        // the weights are computed while some lanes are jammed, which clears up afterwards
+        let jammed: BTreeMap<LaneID, f32> = m.lanes.keys().step_by(3).map(|id| (id, 0.0)).collect();
+        for _ in 0..10 {
+            m.observe_lane_speeds(&jammed);
+        }
+        m.update_routing_index(ROUTING_REBUILD_DELAY + ROUTING_CUSTOMIZE_PERIOD);
+        for _ in 0..10 {
+            m.observe_lane_speeds(&BTreeMap::new());
+        }
+
+        let loaded: Map = Bincode::decode(&Bincode::encode(&m).unwrap()).unwrap();
+        assert!(loaded.routing.is_up_to_date(loaded.dirt_id.0));
+
+        let mut fresh = RoutingIndex::default();
+        fresh.update(&m.lanes, &m.intersections, &m.roads, m.dirt_id.0, 0);
+        fresh.update(&m.lanes, &m.intersections, &m.roads, m.dirt_id.0, 10);
+
+        let kind = PathKind::Vehicle;
+        let lanes: Vec<LaneID> = m
+            .lanes
+            .iter()
+            .filter(|(_, l)| kind.allows(l.kind))
+            .map(|(id, _)| id)
+            .collect();
+        let mut n_paths = 0;
+        let mut n_fresh_differs = 0;
+        for &start in lanes.iter().step_by(7) {
+            for &end in lanes.iter().step_by(5) {
+                if !m.routing.covers(kind, start, end) {
+                    continue;
+                }
+                let path = m.routing.path(kind, start, end);
+                assert_eq!(path, loaded.routing.path(kind, start, end));
+                n_paths += 1;
+                n_fresh_differs += (path != fresh.path(kind, start, end)) as usize;
+            }
+        }
+        assert!(n_paths > 100);
+        // customizing at the current speeds instead would have found other paths
+        assert!(n_fresh_differs > 0);
+    }
+}
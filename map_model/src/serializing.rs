@@ -1,4 +1,8 @@
-use crate::{Buildings, Intersections, Lanes, Lots, Map, ParkingSpots, Roads, SpatialMap, Terrain};
+use crate::routing_index::RoutingState;
+use crate::{
+    Buildings, Intersections, Lanes, Lots, Map, ParkingSpots, PowerLines, Roads, RoutingIndex,
+    SpatialMap, Terrain,
+};
 use serde::{Deserialize, Serialize};
 use std::num::Wrapping;
 
@@ -10,8 +14,10 @@ pub(crate) struct SerializedMap {
     pub lanes: Lanes,
     pub parking: ParkingSpots,
     pub lots: Lots,
+    pub power_lines: PowerLines,
     pub terrain: Terrain,
     pub dirt_id: u32,
+    pub routing: RoutingState,
 }
 
 impl From<&Map> for SerializedMap {
@@ -23,15 +29,24 @@ impl From<&Map> for SerializedMap {
             lanes: m.lanes.clone(),
             parking: m.parking.clone(),
             lots: m.lots.clone(),
+            power_lines: m.power_lines.clone(),
             terrain: m.terrain.clone(),
             dirt_id: m.dirt_id.0,
+            routing: m.routing.state.clone(),
         }
     }
 }
 
 impl From<SerializedMap> for Map {
     fn from(sel: SerializedMap) -> Self {
-        let spatial_map = mk_spatial_map(&sel);
+        let spatial_map = mk_spatial_map(&sel.buildings, &sel.roads, &sel.intersections, &sel.lots);
+        let routing = RoutingIndex::restore(
+            sel.routing,
+            &sel.lanes,
+            &sel.intersections,
+            &sel.roads,
+            sel.dirt_id,
+        );
         Map {
             roads: sel.roads,
             lanes: sel.lanes,
@@ -39,25 +54,32 @@ impl From<SerializedMap> for Map {
             buildings: sel.buildings,
             spatial_map,
             lots: sel.lots,
+            power_lines: sel.power_lines,
             parking: sel.parking,
             terrain: sel.terrain,
+            routing,
             dirt_id: Wrapping(sel.dirt_id),
         }
     }
 }
 
-fn mk_spatial_map(m: &SerializedMap) -> SpatialMap {
+pub(crate) fn mk_spatial_map(
+    buildings: &Buildings,
+    roads: &Roads,
+    intersections: &Intersections,
+    lots: &Lots,
+) -> SpatialMap {
     let mut sm = SpatialMap::default();
-    for h in m.buildings.values() {
+    for h in buildings.values() {
         sm.insert(h.id, h.obb);
     }
-    for r in m.roads.values() {
+    for r in roads.values() {
         sm.insert(r.id, r.boldline());
     }
-    for i in m.intersections.values() {
-        sm.insert(i.id, i.bcircle(&m.roads));
+    for i in intersections.values() {
+        sm.insert(i.id, i.bcircle(roads));
     }
-    for l in m.lots.values() {
+    for l in lots.values() {
         sm.insert(l.id, l.shape);
     }
     sm
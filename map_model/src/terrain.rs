@@ -1,14 +1,14 @@
 use crate::procgen::heightmap::tree_density;
 use geom::{vec2, Vec2, AABB};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::num::Wrapping;
 
 pub const CHUNK_SIZE: u32 = 1024;
 pub const CHUNK_RESOLUTION: usize = 32;
 pub const CELL_SIZE: f32 = CHUNK_SIZE as f32 / CHUNK_RESOLUTION as f32;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Chunk {
     pub trees: Vec<Tree>,
     pub heights: [[f32; CHUNK_RESOLUTION]; CHUNK_RESOLUTION],
@@ -33,10 +33,27 @@ pub struct Tree {
     pub dir: Vec2,
 }
 
+/// A change of the ground heights within a circle, strongest at its center
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Terraform {
+    /// Raises the ground by this many meters
+    Raise(f32),
+    /// Lowers the ground by this many meters
+    Lower(f32),
+    /// Brings the ground to this height
+    Flatten(f32),
+    /// Brings the ground to the average height of its neighbours
+    Smooth,
+}
+
+/// Previous state of the chunks changed while recording, None if they didn't exist
+pub type ChunksDiff = BTreeMap<(i32, i32), Option<Chunk>>;
+
 #[derive(Clone)]
 pub struct Terrain {
     pub chunks: HashMap<(i32, i32), Chunk>,
     pub dirt_id: Wrapping<u32>,
+    journal: Option<ChunksDiff>,
 }
 
 defer_serialize!(Terrain, SerializedTerrain);
@@ -52,12 +69,54 @@ impl Terrain {
         Self {
             chunks: Default::default(),
             dirt_id: Wrapping(1),
+            journal: None,
+        }
+    }
+
+    pub(crate) fn start_recording(&mut self) {
+        if self.journal.is_none() {
+            self.journal = Some(ChunksDiff::new());
+        }
+    }
+
+    pub(crate) fn stop_recording(&mut self) -> ChunksDiff {
+        self.journal.take().unwrap_or_default()
+    }
+
+    /// Puts back the chunks of the diff, which then holds the chunks it replaced
+    pub(crate) fn swap(&mut self, diff: &mut ChunksDiff) {
+        for (cell, chunk) in diff.iter_mut() {
+            let cur = match chunk.take() {
+                Some(mut c) => {
+                    let cur = self.chunks.remove(cell);
+                    if let Some(ref cur) = cur {
+                        c.dirt_id = cur.dirt_id + Wrapping(1);
+                    }
+                    self.chunks.insert(*cell, c);
+                    cur
+                }
+                None => self.chunks.remove(cell),
+            };
+            *chunk = cur;
+        }
+        self.dirt_id += Wrapping(1);
+    }
+
+    fn record(&mut self, cell: (i32, i32)) {
+        let chunks = &self.chunks;
+        if let Some(ref mut j) = self.journal {
+            j.entry(cell).or_insert_with(|| chunks.get(&cell).cloned());
         }
     }
 
     pub fn remove_near_filter(&mut self, bbox: AABB, should_remove: impl Fn(Vec2) -> bool) {
         let mut v = false;
         for cell in self.chunks_iter(bbox) {
+            let chunk = unwrap_cont!(self.chunks.get(&cell));
+            if !chunk.trees.iter().any(|t| should_remove(t.pos)) {
+                continue;
+            }
+            self.record(cell);
             let chunk = unwrap_cont!(self.chunks.get_mut(&cell));
             let mut vcell = false;
             chunk.trees.retain(|t| {
@@ -71,6 +130,71 @@ impl Terrain {
         self.dirt_id += Wrapping(v as u32)
     }
 
+    /// Edits the heights of the cells within the radius, returns whether any changed
+    pub fn terraform(&mut self, center: Vec2, radius: f32, kind: Terraform) -> bool {
+        let minx = ((center.x - radius) / CELL_SIZE).floor() as i32;
+        let maxx = ((center.x + radius) / CELL_SIZE).ceil() as i32;
+        let miny = ((center.y - radius) / CELL_SIZE).floor() as i32;
+        let maxy = ((center.y + radius) / CELL_SIZE).ceil() as i32;
+
+        // computed before editing anything so that smoothing doesn't depend on the order
+        let mut edits = vec![];
+        for y in miny..=maxy {
+            for x in minx..=maxx {
+                let d = (vec2(x as f32, y as f32) * CELL_SIZE).distance(center);
+                if d > radius {
+                    continue;
+                }
+                let h = unwrap_cont!(self.cell_height(x, y));
+                let target = match kind {
+                    Terraform::Raise(amount) => h + amount,
+                    Terraform::Lower(amount) => h - amount,
+                    Terraform::Flatten(height) => height,
+                    Terraform::Smooth => {
+                        let neighs = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)];
+                        let (sum, n) = neighs
+                            .iter()
+                            .filter_map(|&(x, y)| self.cell_height(x, y))
+                            .fold((h, 1.0), |(sum, n), h| (sum + h, n + 1.0));
+                        sum / n
+                    }
+                };
+                let strength = 1.0 - (d / radius).powi(2);
+                edits.push((x, y, h + (target - h) * strength));
+            }
+        }
+
+        let mut changed = false;
+        for (x, y, h) in edits {
+            let (cell, cx, cy) = Self::cell_coords(x, y);
+            if unwrap_cont!(self.chunks.get(&cell)).heights[cy][cx] == h {
+                continue;
+            }
+            self.record(cell);
+            let chunk = unwrap_cont!(self.chunks.get_mut(&cell));
+            chunk.heights[cy][cx] = h;
+            chunk.dirt_id += Wrapping(1);
+            changed = true;
+        }
+        self.dirt_id += Wrapping(changed as u32);
+        changed
+    }
+
+    /// Chunk and position in the chunk of the cell at the given world cell coordinates
+    fn cell_coords(x: i32, y: i32) -> ((i32, i32), usize, usize) {
+        let res = CHUNK_RESOLUTION as i32;
+        (
+            (x.div_euclid(res), y.div_euclid(res)),
+            x.rem_euclid(res) as usize,
+            y.rem_euclid(res) as usize,
+        )
+    }
+
+    fn cell_height(&self, x: i32, y: i32) -> Option<f32> {
+        let (cell, cx, cy) = Self::cell_coords(x, y);
+        self.chunks.get(&cell).map(|chunk| chunk.heights[cy][cx])
+    }
+
     pub fn cell(p: Vec2) -> (i32, i32) {
         (
             p.x as i32 / CHUNK_SIZE as i32 - if p.x < 0.0 { 1 } else { 0 },
@@ -114,6 +238,7 @@ impl Terrain {
         if self.chunks.contains_key(&(x, y)) {
             return;
         }
+        self.record((x, y));
 
         let chunk = self.chunks.entry((x, y)).or_default();
 
@@ -256,6 +381,8 @@ impl From<&Terrain> for SerializedTerrain {
                 },
             ))
         }
+        // chunks is a HashMap, sort to get a deterministic output (used for hashing)
+        t.v.sort_unstable_by_key(|(cell, _)| *cell);
 
         t
     }
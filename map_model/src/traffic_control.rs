@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TrafficBehavior {
     RED,
     ORANGE,
@@ -39,6 +39,8 @@ impl TrafficLightSchedule {
 pub enum TrafficControl {
     Always,
     Light(TrafficLightSchedule),
+    /// Light switched by a controller reacting to the vehicles waiting, holds the current state
+    Actuated(TrafficBehavior),
     StopSign,
 }
 
@@ -52,7 +54,7 @@ impl TrafficControl {
     }
 
     pub fn is_light(&self) -> bool {
-        matches!(self, TrafficControl::Light(_))
+        matches!(self, TrafficControl::Light(_) | TrafficControl::Actuated(_))
     }
 
     pub fn get_behavior(&self, seconds: u32) -> TrafficBehavior {
@@ -68,6 +70,7 @@ impl TrafficControl {
                     TrafficBehavior::RED
                 }
             }
+            TrafficControl::Actuated(behavior) => *behavior,
             TrafficControl::StopSign => TrafficBehavior::STOP,
         }
     }
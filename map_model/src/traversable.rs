@@ -14,6 +14,11 @@ pub enum TraverseDirection {
 pub enum TraverseKind {
     Lane(LaneID),
     Turn(TurnID),
+    /// Driving along `from` until merging into `to`, the lane next to it going the same way
+    LaneChange {
+        from: LaneID,
+        to: LaneID,
+    },
 }
 
 impl TraverseKind {
@@ -46,6 +51,7 @@ impl Traversable {
         match self.kind {
             TraverseKind::Lane(id) => Some(&m.lanes.get(id)?.points),
             TraverseKind::Turn(id) => Some(&m.intersections.get(id.parent)?.find_turn(id)?.points),
+            TraverseKind::LaneChange { from, .. } => Some(&m.lanes.get(from)?.points),
         }
     }
 
@@ -55,7 +61,7 @@ impl Traversable {
                 let l = unwrap_or!(lanes.get(id), return true);
                 !l.control.get_behavior(time).is_red()
             }
-            TraverseKind::Turn(_) => true,
+            TraverseKind::Turn(_) | TraverseKind::LaneChange { .. } => true,
         }
     }
 
@@ -66,6 +72,7 @@ impl Traversable {
                 TraverseDirection::Backward => lanes.get(p)?.src,
             },
             TraverseKind::Turn(id) => id.parent,
+            TraverseKind::LaneChange { to, .. } => lanes.get(to)?.dst,
         })
     }
 
@@ -76,6 +83,7 @@ impl Traversable {
                 TraverseDirection::Forward => t.dst,
                 TraverseDirection::Backward => t.src,
             },
+            TraverseKind::LaneChange { to, .. } => to,
         }
     }
 }
@@ -123,5 +131,9 @@ macro_rules! enum_inspect_impl {
     };
 }
 
-enum_inspect_impl!(TraverseKind; TraverseKind::Lane(_), TraverseKind::Turn(_));
+enum_inspect_impl!(TraverseKind;
+    TraverseKind::Lane(_),
+    TraverseKind::Turn(_),
+    TraverseKind::LaneChange { .. }
+);
 enum_inspect_impl!(TraverseDirection; TraverseDirection::Forward, TraverseDirection::Backward);
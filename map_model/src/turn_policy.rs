@@ -21,10 +21,9 @@ impl Default for TurnPolicy {
     }
 }
 
-fn filter_vehicles(x: &[(LaneID, LaneKind)]) -> Vec<LaneID> {
+fn filter_vehicles(x: &[(LaneID, LaneKind)]) -> Vec<(LaneID, LaneKind)> {
     x.iter()
         .filter(|(_, kind)| kind.vehicles())
-        .map(|(id, _)| id)
         .copied()
         .collect::<Vec<_>>()
 }
@@ -32,13 +31,13 @@ fn filter_vehicles(x: &[(LaneID, LaneKind)]) -> Vec<LaneID> {
 impl TurnPolicy {
     fn zip(
         inter_id: IntersectionID,
-        incoming: &[LaneID],
-        outgoing: &[LaneID],
+        incoming: &[(LaneID, LaneKind)],
+        outgoing: &[(LaneID, LaneKind)],
     ) -> Vec<(TurnID, TurnKind)> {
         incoming
             .iter()
             .zip(outgoing)
-            .map(|(lane_src, lane_dst)| {
+            .map(|((lane_src, _), (lane_dst, _))| {
                 (
                     TurnID::new(inter_id, *lane_src, *lane_dst, false),
                     TurnKind::Driving,
@@ -49,13 +48,13 @@ impl TurnPolicy {
 
     fn all(
         inter_id: IntersectionID,
-        incoming: &[LaneID],
-        outgoing: &[LaneID],
+        incoming: &[(LaneID, LaneKind)],
+        outgoing: &[(LaneID, LaneKind)],
     ) -> Vec<(TurnID, TurnKind)> {
         incoming
             .iter()
-            .flat_map(|lane_src| {
-                outgoing.iter().map(move |lane_dst| {
+            .flat_map(|(lane_src, _)| {
+                outgoing.iter().map(move |(lane_dst, _)| {
                     (
                         TurnID::new(inter_id, *lane_src, *lane_dst, false),
                         TurnKind::Driving,
@@ -65,12 +64,17 @@ impl TurnPolicy {
             .collect()
     }
 
+    /// Connects lanes one to one when both sides have the same lanes, every pair otherwise
     fn zip_on_same_length(
         inter_id: IntersectionID,
-        incoming: &[LaneID],
-        outgoing: &[LaneID],
+        incoming: &[(LaneID, LaneKind)],
+        outgoing: &[(LaneID, LaneKind)],
     ) -> Vec<(TurnID, TurnKind)> {
-        if incoming.len() == outgoing.len() {
+        if incoming
+            .iter()
+            .map(|x| x.1)
+            .eq(outgoing.iter().map(|x| x.1))
+        {
             Self::zip(inter_id, incoming, outgoing)
         } else {
             Self::all(inter_id, incoming, outgoing)
@@ -15,6 +15,7 @@ use crate::audio::GameAudio;
 use crate::context::Context;
 use crate::gui::inputmap::InputMap;
 use crate::gui::windows::debug::DebugObjs;
+use crate::gui::windows::map::ReplayState;
 use crate::gui::windows::network::NetworkConnectionInfo;
 use crate::gui::windows::settings::{Settings, ShadowQuality};
 use crate::gui::{FollowEntity, Gui, UiTextures};
@@ -26,6 +27,7 @@ use crate::uiworld::{ReceivedCommands, UiWorld};
 use common::saveload::Encoder;
 use common::timestep::Timestep;
 use egregoria::engine_interaction::WorldCommands;
+use egregoria::utils::replay::ReplayPlayer;
 use egregoria::utils::scheduler::SeqSchedule;
 use networking::{Frame, PollResult, ServerPollResult};
 use std::convert::{TryFrom, TryInto};
@@ -102,6 +104,7 @@ impl State {
         *self.uiw.write::<ReceivedCommands>() = ReceivedCommands::default();
 
         let mut net_state = self.uiw.write::<NetworkState>();
+        let mut replay = self.uiw.write::<ReplayState>();
 
         let mut inputs_to_apply = None;
         match *net_state {
@@ -110,11 +113,38 @@ impl State {
                 let sched = &mut self.game_schedule;
                 let mut timings = self.uiw.write::<Timings>();
 
+                if replay.load_requested {
+                    replay.load_requested = false;
+                    if let Some((replay_goria, player)) = ReplayPlayer::load("replay") {
+                        *goria = replay_goria;
+                        replay.player = Some(player);
+                        crate::gui::reset_world_refs(&self.uiw);
+                    }
+                }
+
+                // the replay drives the world, the player's commands are dropped
+                let commands = if replay.player.is_some() {
+                    WorldCommands::default()
+                } else {
+                    commands
+                };
+
                 let has_commands = !commands.is_empty();
                 let mut commands_once = Some(commands.clone());
                 step.prepare_frame(settings.time_warp);
                 while step.tick() || (has_commands && commands_once.is_some()) {
-                    let t = goria.tick(sched, &commands_once.take().unwrap_or_default());
+                    let mut commands = commands_once.take().unwrap_or_default();
+                    if let Some(ref mut player) = replay.player {
+                        commands = player.next(goria.get_tick());
+                        if player.is_finished() {
+                            log::info!("replay finished");
+                            replay.player = None;
+                        }
+                    }
+                    if let Some(ref mut recorder) = replay.recorder {
+                        recorder.record(goria.get_tick(), &commands);
+                    }
+                    let t = goria.tick(sched, &commands);
                     timings.world_update.add_value(t.as_secs_f32());
                 }
 
@@ -178,11 +208,31 @@ impl State {
                     .iter()
                     .map(|x| x.inp.clone())
                     .collect();
+                if let Some(ref mut recorder) = replay.recorder {
+                    recorder.record(self.goria.get_tick(), &commands);
+                }
                 let t = self.goria.tick(&mut self.game_schedule, &commands);
                 self.uiw
                     .write::<Timings>()
                     .world_update
                     .add_value(t.as_secs_f32());
+
+                let goria = &self.goria;
+                let frame = Frame(goria.get_tick());
+                match *net_state {
+                    NetworkState::Server(ref mut server) => {
+                        server.check_hashes(frame, || goria.hashes())
+                    }
+                    NetworkState::Client(ref mut client) => {
+                        if let Some(desync) = client.check_hashes(frame, || goria.hashes()) {
+                            log::warn!(
+                                "world diverged from the server {}, see the network window",
+                                desync
+                            );
+                        }
+                    }
+                    NetworkState::Singleplayer(_) => {}
+                }
                 merged.merge(
                     &frame_commands
                         .inputs
@@ -196,6 +246,7 @@ impl State {
         }
 
         drop(net_state);
+        drop(replay);
 
         let real_delta = ctx.delta;
         self.uiw.write::<Timings>().all.add_value(real_delta as f32);
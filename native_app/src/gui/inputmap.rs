@@ -31,6 +31,8 @@ impl InputMap {
             (Close,         ic(vec![Key(KeyCode::Escape)])),
             (Select,        ic(vec![Mouse(MouseButton::Left)])),
             (HideInterface, ic(vec![Key(KeyCode::H)])),
+            (Undo,          ic(vec![KeyModifier(KeyCode::LControl, KeyCode::Z)])),
+            (Redo,          ic(vec![KeyModifier(KeyCode::LControl, KeyCode::Y)])),
         ] {
             m.insert(k, v);
         }
@@ -137,6 +139,8 @@ pub enum InputAction {
     Close,
     Select,
     HideInterface,
+    Undo,
+    Redo,
 }
 
 impl Display for InputAction {
@@ -156,6 +160,8 @@ impl Display for InputAction {
                 InputAction::Close => "Close",
                 InputAction::Select => "Select",
                 InputAction::HideInterface => "Hide interface",
+                InputAction::Undo => "Undo",
+                InputAction::Redo => "Redo",
             }
         )
     }
@@ -4,9 +4,11 @@ use egregoria::economy::{Market, Workers};
 use egregoria::map_dynamic::{Itinerary, Router};
 use egregoria::pedestrians::{Location, Pedestrian};
 use egregoria::physics::{Collider, CollisionWorld, Kinematics, PhysicsObject};
-use egregoria::souls::desire::{BuyFood, Home, Work};
+use egregoria::souls::desire::{BuyFood, Home, Shop, Work};
 use egregoria::souls::goods_company::GoodsCompany;
 use egregoria::souls::human::HumanDecision;
+use egregoria::souls::population::Happiness;
+use egregoria::transit::Bus;
 use egregoria::vehicles::{Vehicle, VehicleID, VehicleState};
 use egregoria::{Egregoria, SoulID};
 use geom::{Transform, Vec2};
@@ -56,6 +58,7 @@ impl InspectRenderer {
         ui.text(im_str!("{:?}", self.entity));
         self.inspect_transform(goria, uiworld, ui);
         self.inspect_component::<Vehicle>(goria, ui);
+        self.inspect_component::<Bus>(goria, ui);
         self.inspect_component::<Pedestrian>(goria, ui);
         self.inspect_component::<Location>(goria, ui);
         self.inspect_component::<Kinematics>(goria, ui);
@@ -66,6 +69,8 @@ impl InspectRenderer {
         self.inspect_component::<Work>(goria, ui);
         self.inspect_component::<Home>(goria, ui);
         self.inspect_component::<BuyFood>(goria, ui);
+        self.inspect_component::<Shop>(goria, ui);
+        self.inspect_component::<Happiness>(goria, ui);
         self.inspect_component::<GoodsCompany>(goria, ui);
 
         if let Some(v) = goria.comp::<Vehicle>(self.entity) {
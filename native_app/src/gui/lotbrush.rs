@@ -31,6 +31,8 @@ pub fn lotbrush(goria: &Egregoria, uiworld: &mut UiWorld) {
     let mut col = match kind {
         LotKind::Unassigned => common::config().lot_unassigned_col,
         LotKind::Residential => common::config().lot_residential_col,
+        LotKind::Commercial => common::config().lot_commercial_col,
+        LotKind::Industrial => common::config().lot_industrial_col,
     };
 
     col.a = 0.2;
@@ -44,7 +46,13 @@ pub fn lotbrush(goria: &Egregoria, uiworld: &mut UiWorld) {
             .query_around(mpos.xy(), res.radius, ProjectFilter::LOT)
         {
             if let ProjectKind::Lot(id) = v {
-                commands.map_build_house(id);
+                match kind {
+                    LotKind::Residential => commands.map_build_house(id),
+                    _ if map.lots().get(id).map(|lot| lot.kind) != Some(kind) => {
+                        commands.map_set_lot_kind(id, kind)
+                    }
+                    _ => {}
+                }
             }
         }
     }
@@ -4,10 +4,11 @@ use imgui::TextureId;
 use legion::Entity;
 use serde::{Deserialize, Serialize};
 
+use crate::gui::inputmap::{InputAction, InputMap};
 use crate::input::{KeyCode, KeyboardInfo};
 use crate::uiworld::UiWorld;
 use egregoria::Egregoria;
-use roadbuild::RoadBuildResource;
+use roadbuild::{BuildState, RoadBuildResource};
 use wgpu_engine::GfxContext;
 
 mod bulldozer;
@@ -15,11 +16,14 @@ mod follow;
 mod inspect;
 mod inspected_aura;
 mod lotbrush;
+mod powerlines;
 mod roadbuild;
 mod roadeditor;
 mod selectable;
 mod specialbuilding;
+mod terraform;
 mod topgui;
+mod transit;
 
 pub mod inputmap;
 pub mod windows;
@@ -33,11 +37,15 @@ pub fn run_ui_systems(goria: &Egregoria, uiworld: &mut UiWorld) {
     bulldozer::bulldozer(goria, uiworld);
     inspected_aura::inspected_aura(goria, uiworld);
     lotbrush::lotbrush(goria, uiworld);
+    powerlines::powerlines(goria, uiworld);
     roadbuild::roadbuild(goria, uiworld);
     roadeditor::roadeditor(goria, uiworld);
     selectable::selectable(goria, uiworld);
     specialbuilding::specialbuilding(goria, uiworld);
+    terraform::terraform(goria, uiworld);
+    transit::transit(goria, uiworld);
     hand_reset(uiworld);
+    undo_redo(uiworld);
 }
 
 register_resource_noserialize!(InspectedEntity);
@@ -64,6 +72,30 @@ pub fn hand_reset(uiworld: &mut UiWorld) {
     }
 }
 
+/// Forgets the selection and what the tools were doing, as they point into the previous world
+/// when it gets replaced (like when a replay starts)
+pub fn reset_world_refs(uiworld: &UiWorld) {
+    *uiworld.write::<Tool>() = Tool::Hand;
+    *uiworld.write::<InspectedEntity>() = InspectedEntity::default();
+    *uiworld.write::<FollowEntity>() = FollowEntity::default();
+    uiworld.write::<RoadBuildResource>().build_state = BuildState::Hover;
+    *uiworld.write::<roadeditor::RoadEditorResource>() = Default::default();
+    uiworld.write::<powerlines::PowerLinesResource>().start = None;
+    uiworld.write::<transit::TransitToolResource>().line.clear();
+}
+
+#[profiling::function]
+pub fn undo_redo(uiworld: &mut UiWorld) {
+    let inputs = uiworld.read::<InputMap>();
+    let mut commands = uiworld.commands();
+    if inputs.just_act.contains(&InputAction::Undo) {
+        commands.undo();
+    }
+    if inputs.just_act.contains(&InputAction::Redo) {
+        commands.redo();
+    }
+}
+
 register_resource_noserialize!(Tool);
 #[derive(Copy, Clone, Serialize, Deserialize)]
 pub enum Tool {
@@ -74,6 +106,9 @@ pub enum Tool {
     Bulldozer,
     LotBrush,
     SpecialBuilding,
+    Transit,
+    PowerLines,
+    Terraform,
 }
 
 #[derive(Copy, Clone, Hash, Eq, PartialEq)]
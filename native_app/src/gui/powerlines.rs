@@ -0,0 +1,117 @@
+use super::Tool;
+use crate::input::{MouseButton, MouseInfo};
+use crate::rendering::immediate::ImmediateDraw;
+use crate::uiworld::UiWorld;
+use egregoria::Egregoria;
+use geom::Segment;
+use map_model::{BuildingID, ProjectKind, MAX_POWER_LINE_LENGTH};
+use ordered_float::OrderedFloat;
+
+/// Distance under which a power line is picked by the mouse
+const LINE_PICK_DIST: f32 = 5.0;
+
+register_resource_noserialize!(PowerLinesResource);
+#[derive(Default)]
+pub struct PowerLinesResource {
+    pub mode: PowerLinesMode,
+    /// Building the line being placed starts from
+    pub start: Option<BuildingID>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum PowerLinesMode {
+    Connect,
+    Remove,
+}
+
+impl Default for PowerLinesMode {
+    fn default() -> Self {
+        PowerLinesMode::Connect
+    }
+}
+
+#[profiling::function]
+pub fn powerlines(goria: &Egregoria, uiworld: &mut UiWorld) {
+    let tool = *uiworld.read::<Tool>();
+    let mut state = uiworld.write::<PowerLinesResource>();
+
+    if !matches!(tool, Tool::PowerLines) {
+        state.start = None;
+        return;
+    }
+
+    let map = goria.map();
+    state.start = state.start.filter(|&b| map.buildings().contains_key(b));
+
+    let mouseinfo = uiworld.read::<MouseInfo>();
+    let mut draw = uiworld.write::<ImmediateDraw>();
+    let commands = &mut *uiworld.commands();
+
+    let mpos = unwrap_ret!(mouseinfo.unprojected);
+    let clicked = mouseinfo.just_pressed.contains(&MouseButton::Left);
+
+    match state.mode {
+        PowerLinesMode::Connect => {
+            let hovered = match unwrap_ret!(map.project(mpos, 0.0)).kind {
+                ProjectKind::Building(id) => Some(id),
+                _ => None,
+            };
+
+            let start = state
+                .start
+                .and_then(|b| Some((b, map.buildings().get(b)?.obb.center())));
+
+            if let Some((_, spos)) = start {
+                let end = hovered
+                    .and_then(|b| map.buildings().get(b))
+                    .map(|b| b.obb.center())
+                    .unwrap_or_else(|| mpos.xy());
+                let col = if spos.distance(end) <= MAX_POWER_LINE_LENGTH {
+                    common::config().gui_success
+                } else {
+                    common::config().gui_danger
+                };
+                draw.line(spos.z(mpos.z + 0.5), end.z(mpos.z + 0.5), 1.0)
+                    .color(col);
+            }
+
+            let hovered = unwrap_ret!(hovered);
+            let b = unwrap_ret!(map.buildings().get(hovered));
+            draw.circle(b.obb.center().z(mpos.z + 0.5), 3.0)
+                .color(common::config().gui_primary);
+
+            if !clicked {
+                return;
+            }
+            match start {
+                Some((start, _)) if start != hovered => {
+                    commands.map_add_power_line(start, hovered);
+                    // keep going from there to place lines one after the other
+                    state.start = Some(hovered);
+                }
+                Some(_) => state.start = None,
+                None => state.start = Some(hovered),
+            }
+        }
+        PowerLinesMode::Remove => {
+            let hovered = map
+                .power_lines()
+                .values()
+                .filter_map(|l| {
+                    let (a, b) = l.ends(map.buildings())?;
+                    let d = Segment::new(a.xy(), b.xy())
+                        .project(mpos.xy())
+                        .distance(mpos.xy());
+                    Some((l, a, b, d))
+                })
+                .filter(|&(_, _, _, d)| d < LINE_PICK_DIST)
+                .min_by_key(|&(_, _, _, d)| OrderedFloat(d));
+
+            let (line, a, b, _) = unwrap_ret!(hovered);
+            draw.line(a, b, 1.5).color(common::config().gui_danger);
+            if clicked {
+                commands.map_remove_power_line(line.id);
+            }
+        }
+    }
+}
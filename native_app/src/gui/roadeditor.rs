@@ -3,9 +3,12 @@ use crate::input::{MouseButton, MouseInfo};
 use crate::rendering::immediate::ImmediateDraw;
 use crate::uiworld::UiWorld;
 use egregoria::Egregoria;
-use geom::Color;
+use geom::{Color, Vec2};
+use imgui::{im_str, Ui};
 use map_model::ProjectKind;
-use map_model::{IntersectionID, LightPolicy, TurnPolicy};
+use map_model::{
+    CustomLights, Intersection, IntersectionID, LightPhase, LightPolicy, RoadID, Roads, TurnPolicy,
+};
 
 #[derive(Clone)]
 pub struct IntersectionComponent {
@@ -19,6 +22,12 @@ register_resource_noserialize!(RoadEditorResource);
 pub struct RoadEditorResource {
     pub inspect: Option<IntersectionComponent>,
     pub dirty: bool,
+    /// Chain of intersections being selected for a green wave
+    pub corridor: Option<Vec<IntersectionID>>,
+    /// Road whose closure is being edited
+    pub road: Option<RoadID>,
+    /// How long the next closure lasts, in minutes, 0 until reopened by hand
+    pub closure_minutes: i32,
 }
 
 #[profiling::function]
@@ -32,9 +41,30 @@ pub fn roadeditor(goria: &Egregoria, uiworld: &mut UiWorld) {
 
     if !matches!(*tool, Tool::RoadEditor) {
         state.inspect = None;
+        state.corridor = None;
+        state.road = None;
         return;
     }
 
+    if let Some(road) = state.road.and_then(|id| map.roads().get(id)) {
+        imm_draw
+            .polyline(road.points().as_slice().to_vec(), road.width)
+            .color(common::config().gui_primary.a(0.3));
+    }
+
+    if let Some(ref corridor) = state.corridor {
+        let points: Vec<_> = corridor
+            .iter()
+            .filter_map(|&id| map.intersections().get(id))
+            .map(|inter| inter.pos.up(0.1))
+            .collect();
+        if points.len() >= 2 {
+            imm_draw
+                .polyline(points, 3.0)
+                .color(common::config().gui_success);
+        }
+    }
+
     if let Some(id) = state.inspect.as_ref().map(|x| x.id) {
         if let Some(inter) = map.intersections().get(id) {
             let lanes = map.lanes();
@@ -73,16 +103,31 @@ pub fn roadeditor(goria: &Egregoria, uiworld: &mut UiWorld) {
     }
 
     if mouseinfo.pressed.contains(&MouseButton::Left) {
-        if let ProjectKind::Inter(id) = cur_proj.kind {
+        if let (ProjectKind::Inter(id), Some(corridor)) = (cur_proj.kind, &mut state.corridor) {
+            // only extend the chain with a neighbour of its last intersection
+            let connected = corridor
+                .last()
+                .map_or(true, |&last| map.find_road(last, id).is_some());
+            if connected && !corridor.contains(&id) {
+                corridor.push(id);
+                proj_col = common::config().gui_success;
+            } else {
+                proj_col = common::config().gui_danger;
+            }
+        } else if let ProjectKind::Inter(id) = cur_proj.kind {
             proj_col = common::config().gui_success;
             proj_pos = cur_proj.pos;
             let inter = &map.intersections()[id];
             state.inspect = Some(IntersectionComponent {
                 id,
                 turn_policy: inter.turn_policy,
-                light_policy: inter.light_policy,
+                light_policy: inter.light_policy.clone(),
             });
+            state.road = None;
             state.dirty = false;
+        } else if let ProjectKind::Road(id) = cur_proj.kind {
+            state.road = Some(id);
+            state.inspect = None;
         }
     }
 
@@ -93,9 +138,89 @@ pub fn roadeditor(goria: &Egregoria, uiworld: &mut UiWorld) {
             commands.map_update_intersection_policy(
                 interc.id,
                 interc.turn_policy,
-                interc.light_policy,
+                interc.light_policy.clone(),
             );
         }
         state.dirty = false;
     }
 }
+
+/// Edits the phases of a custom light schedule, returns whether anything changed
+pub fn custom_lights_ui(
+    ui: &Ui<'_>,
+    custom: &mut CustomLights,
+    inter: &Intersection,
+    roads: &Roads,
+) -> bool {
+    let mut changed = false;
+    let tok = ui.push_item_width(80.0);
+
+    ui.text(format!("cycle: {}s", custom.period()));
+    let mut remove = None;
+    for (i, phase) in custom.phases.iter_mut().enumerate() {
+        ui.text(format!("Phase {}", i + 1));
+        ui.same_line(0.0);
+        if ui.small_button(&*im_str!("remove##phase{}", i)) {
+            remove = Some(i);
+        }
+
+        let mut green = phase.green as i32;
+        if ui
+            .input_int(&*im_str!("green (s)##{}", i), &mut green)
+            .build()
+        {
+            phase.green = green.max(1) as u32;
+            changed = true;
+        }
+
+        for &road in &inter.roads {
+            let r = unwrap_cont!(roads.get(road));
+            let mut on = phase.roads.contains(&road);
+            let label = im_str!("{}##{}{:?}", compass(r.dir_from(inter.id)), i, road);
+            if ui.checkbox(&*label, &mut on) {
+                if on {
+                    phase.roads.push(road);
+                } else {
+                    phase.roads.retain(|&x| x != road);
+                }
+                changed = true;
+            }
+            ui.same_line(0.0);
+        }
+        ui.new_line();
+    }
+
+    if let Some(i) = remove {
+        custom.phases.remove(i);
+        changed = true;
+    }
+
+    if ui.small_button(im_str!("add phase")) {
+        custom.phases.push(LightPhase {
+            roads: vec![],
+            green: 10,
+        });
+        changed = true;
+    }
+
+    let mut orange = custom.orange as i32;
+    if ui.input_int(im_str!("orange (s)"), &mut orange).build() {
+        custom.orange = orange.max(0) as u32;
+        changed = true;
+    }
+    let mut offset = custom.offset as i32;
+    if ui.input_int(im_str!("offset (s)"), &mut offset).build() {
+        custom.offset = offset.max(0) as u32;
+        changed = true;
+    }
+
+    tok.pop(ui);
+    changed
+}
+
+/// Direction the road goes to when leaving the intersection, to tell them apart
+fn compass(dir: Vec2) -> &'static str {
+    const NAMES: [&str; 8] = ["E", "NE", "N", "NW", "W", "SW", "S", "SE"];
+    let octant = (dir.y.atan2(dir.x) / std::f32::consts::FRAC_PI_4).round() as i32;
+    NAMES[octant.rem_euclid(8) as usize]
+}
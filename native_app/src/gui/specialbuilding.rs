@@ -70,6 +70,8 @@ pub fn specialbuilding(goria: &Egregoria, uiworld: &mut UiWorld) {
         } else if p.ends_with(".glb") {
             draw.mesh(p, obb.center().z(mpos.z), obb.axis()[0].normalize().z0())
                 .color(col);
+        } else {
+            draw.obb(obb, mpos.z + 0.1).color(col);
         }
     };
 
@@ -0,0 +1,68 @@
+use super::Tool;
+use crate::input::{MouseButton, MouseInfo};
+use crate::rendering::immediate::ImmediateDraw;
+use crate::uiworld::UiWorld;
+use egregoria::Egregoria;
+use map_model::Terraform;
+
+register_resource_noserialize!(TerraformResource);
+pub struct TerraformResource {
+    pub mode: TerraformMode,
+    pub radius: f32,
+    /// How much raising or lowering moves the ground at the center of the brush, in meters
+    pub amount: f32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TerraformMode {
+    Raise,
+    Lower,
+    /// Flattens to the height of the ground under the center of the brush
+    Flatten,
+    Smooth,
+}
+
+#[profiling::function]
+pub fn terraform(goria: &Egregoria, uiworld: &mut UiWorld) {
+    let res = uiworld.read::<TerraformResource>();
+    let tool = *uiworld.read::<Tool>();
+    let mouseinfo = uiworld.read::<MouseInfo>();
+    let mut draw = uiworld.write::<ImmediateDraw>();
+    let commands = &mut *uiworld.commands();
+
+    if !matches!(tool, Tool::Terraform) {
+        return;
+    }
+
+    let mpos = unwrap_ret!(mouseinfo.unprojected);
+    let map = goria.map();
+    let kind = match res.mode {
+        TerraformMode::Raise => Terraform::Raise(res.amount),
+        TerraformMode::Lower => Terraform::Lower(res.amount),
+        TerraformMode::Flatten => Terraform::Flatten(unwrap_ret!(map.terrain.height(mpos.xy()))),
+        TerraformMode::Smooth => Terraform::Smooth,
+    };
+    let can_terraform = map.can_terraform(mpos.xy(), res.radius, kind);
+
+    let mut col = if can_terraform {
+        common::config().gui_primary
+    } else {
+        common::config().gui_danger
+    };
+    col.a = 0.2;
+    draw.circle(mpos.up(0.8), res.radius).color(col);
+
+    if can_terraform && mouseinfo.just_pressed.contains(&MouseButton::Left) {
+        commands.map_terraform(mpos.xy(), res.radius, kind);
+    }
+}
+
+impl Default for TerraformResource {
+    fn default() -> Self {
+        Self {
+            mode: TerraformMode::Raise,
+            radius: 100.0,
+            amount: 5.0,
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::gui::bulldozer::BulldozerState;
 use crate::gui::lotbrush::LotBrushResource;
-use crate::gui::roadeditor::RoadEditorResource;
+use crate::gui::roadeditor::{custom_lights_ui, RoadEditorResource};
 use crate::gui::specialbuilding::SpecialBuildingResource;
 use crate::gui::windows::settings::Settings;
 use crate::gui::windows::ImguiWindows;
@@ -8,7 +8,9 @@ use crate::gui::{InspectedEntity, RoadBuildResource, Tool, UiTex, UiTextures};
 use crate::input::{KeyCode, KeyboardInfo};
 use crate::uiworld::UiWorld;
 use common::saveload::Encoder;
-use egregoria::economy::Government;
+use egregoria::economy::{Government, ZoneDemand};
+use egregoria::engine_interaction::RejectedCommands;
+use egregoria::map_dynamic::{MapHistory, PARKING_FACILITIES};
 use egregoria::souls::goods_company::GoodsCompanyRegistry;
 use egregoria::utils::time::GameTime;
 use egregoria::Egregoria;
@@ -16,7 +18,7 @@ use imgui::{im_str, StyleColor, StyleVar, Ui, Window};
 use imgui_inspect::{
     InspectArgsDefault, InspectArgsStruct, InspectRenderDefault, InspectRenderStruct,
 };
-use map_model::{LanePatternBuilder, LightPolicy, LotKind, TurnPolicy};
+use map_model::{ClosureKind, CustomLights, LanePatternBuilder, LightPolicy, LotKind, TurnPolicy};
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 
@@ -68,6 +70,8 @@ impl Gui {
 
         self.time_controls(ui, uiworld, goria);
 
+        Self::rejected_commands(ui, goria);
+
         self.auto_save(uiworld, goria);
 
         tok.pop(ui);
@@ -147,8 +151,11 @@ impl Gui {
             let state = &mut *uiworld.write::<RoadEditorResource>();
             if let Some(ref mut v) = state.inspect {
                 let dirty = &mut state.dirty;
+                let map = goria.map();
+                let inter = map.intersections().get(v.id);
                 Window::new(im_str!("Road Properties"))
                     .size([150.0, 200.0], imgui::Condition::Appearing)
+                    .always_auto_resize(true)
                     .position(
                         [w - 150.0 - toolbox_w, h * 0.5 - 30.0],
                         imgui::Condition::Appearing,
@@ -160,16 +167,27 @@ impl Gui {
                     .resizable(false)
                     .build(ui, || {
                         ui.text("Light policy");
-                        *dirty |= <LightPolicy as InspectRenderDefault<LightPolicy>>::render_mut(
-                            &mut [&mut v.light_policy],
-                            "",
-                            ui,
-                            &InspectArgsDefault {
-                                header: Some(false),
-                                indent_children: Some(false),
-                                ..Default::default()
-                            },
-                        );
+                        let changed =
+                            <LightPolicy as InspectRenderDefault<LightPolicy>>::render_mut(
+                                &mut [&mut v.light_policy],
+                                "",
+                                ui,
+                                &InspectArgsDefault {
+                                    header: Some(false),
+                                    indent_children: Some(false),
+                                    ..Default::default()
+                                },
+                            );
+                        *dirty |= changed;
+                        if let (LightPolicy::Custom(custom), Some(inter)) =
+                            (&mut v.light_policy, inter)
+                        {
+                            if changed {
+                                // start from the phases the default lights would use
+                                *custom = CustomLights::from_intersection(inter);
+                            }
+                            *dirty |= custom_lights_ui(ui, custom, inter, map.roads());
+                        }
                         ui.new_line();
                         ui.text("Turn policy");
                         *dirty |= <TurnPolicy as InspectRenderDefault<TurnPolicy>>::render_mut(
@@ -184,6 +202,95 @@ impl Gui {
                         );
                     });
             }
+
+            Window::new(im_str!("Green wave"))
+                .position(
+                    [w - 150.0 - toolbox_w, h * 0.5 - 130.0],
+                    imgui::Condition::Appearing,
+                )
+                .always_auto_resize(true)
+                .collapsible(false)
+                .resizable(false)
+                .build(ui, || {
+                    let corridor = match state.corridor {
+                        Some(ref mut corridor) => corridor,
+                        None => {
+                            if ui.small_button(im_str!("Select corridor")) {
+                                state.corridor = Some(vec![]);
+                            }
+                            return;
+                        }
+                    };
+                    ui.text("Click connected intersections in order");
+                    ui.text(format!("{} selected", corridor.len()));
+                    if corridor.len() >= 2 && ui.small_button(im_str!("Apply")) {
+                        uiworld
+                            .commands()
+                            .map_set_green_wave(std::mem::take(corridor));
+                        state.corridor = None;
+                        return;
+                    }
+                    if ui.small_button(im_str!("Cancel")) {
+                        state.corridor = None;
+                    }
+                });
+
+            if let Some(road) = state.road {
+                let map = goria.map();
+                let now = goria.read::<GameTime>().seconds;
+                let closure = map.roads().get(road).and_then(|r| {
+                    let (lane, _) = r.lanes_iter().find(|(_, kind)| kind.vehicles())?;
+                    map.lanes().get(lane)?.closure
+                });
+                Window::new(im_str!("Road closure"))
+                    .position(
+                        [w - 150.0 - toolbox_w, h * 0.5 + 200.0],
+                        imgui::Condition::Appearing,
+                    )
+                    .always_auto_resize(true)
+                    .collapsible(false)
+                    .resizable(false)
+                    .build(ui, || {
+                        if let Some(closure) = closure {
+                            ui.text(match closure.kind {
+                                ClosureKind::Closed => "Closed",
+                                ClosureKind::Incident => "Incident",
+                            });
+                            if let Some(until) = closure.until {
+                                ui.text(format!("for {}s", until.saturating_sub(now)));
+                            }
+                            if ui.small_button(im_str!("Reopen")) {
+                                uiworld.commands().map_reopen_road(road);
+                            }
+                            return;
+                        }
+
+                        ui.text("Open");
+                        let tok = ui.push_item_width(80.0);
+                        ui.input_int(
+                            im_str!("minutes (0: until reopened)"),
+                            &mut state.closure_minutes,
+                        )
+                        .build();
+                        tok.pop(ui);
+                        state.closure_minutes = state.closure_minutes.max(0);
+                        let duration = Some(state.closure_minutes as u32 * 60).filter(|&d| d > 0);
+
+                        if ui.small_button(im_str!("Close")) {
+                            uiworld
+                                .commands()
+                                .map_close_road(road, ClosureKind::Closed, duration);
+                        }
+                        ui.same_line(0.0);
+                        if ui.small_button(im_str!("Incident")) {
+                            uiworld.commands().map_close_road(
+                                road,
+                                ClosureKind::Incident,
+                                duration,
+                            );
+                        }
+                    });
+            }
         }
         spacing_left.pop(ui);
 
@@ -193,7 +300,7 @@ impl Gui {
         ) {
             let rbw = 220.0;
             Window::new(im_str!("Road Properties"))
-                .size([rbw, 380.0], imgui::Condition::Appearing)
+                .size([rbw, 445.0], imgui::Condition::Appearing)
                 .position(
                     [w - rbw - toolbox_w, h * 0.5 - 30.0],
                     imgui::Condition::Appearing,
@@ -226,10 +333,21 @@ impl Gui {
                         *pat = *LanePatternBuilder::new().one_way(true);
                     }
 
+                    if ui.button(im_str!("Street with bike lanes"), [rbw, 30.0]) {
+                        *pat = *LanePatternBuilder::new().bike_lanes(true);
+                    }
+
                     if ui.button(im_str!("Avenue"), [rbw, 30.0]) {
                         *pat = *LanePatternBuilder::new().n_lanes(2).speed_limit(13.0);
                     }
 
+                    if ui.button(im_str!("Avenue with bus lanes"), [rbw, 30.0]) {
+                        *pat = *LanePatternBuilder::new()
+                            .n_lanes(2)
+                            .bus_lanes(true)
+                            .speed_limit(13.0);
+                    }
+
                     if ui.button(im_str!("Avenue one-way"), [rbw, 30.0]) {
                         *pat = *LanePatternBuilder::new()
                             .n_lanes(2)
@@ -285,6 +403,8 @@ impl Gui {
                         if pat.n_lanes == 0 {
                             pat.sidewalks = true;
                             pat.parking = false;
+                            pat.bus_lanes = false;
+                            pat.bike_lanes = false;
                         }
 
                         if pat.n_lanes > 10 {
@@ -294,13 +414,18 @@ impl Gui {
                 });
         }
 
-        let brushes = [(im_str!("Residential"), LotKind::Residential)];
+        let brushes = [
+            (im_str!("Residential"), LotKind::Residential),
+            (im_str!("Commercial"), LotKind::Commercial),
+            (im_str!("Industrial"), LotKind::Industrial),
+            (im_str!("Unzone"), LotKind::Unassigned),
+        ];
 
         if matches!(*uiworld.read::<Tool>(), Tool::LotBrush) {
             let lbw = 130.0;
             Window::new(im_str!("Lot Brush"))
                 .size(
-                    [lbw, 50.0 + brushes.len() as f32 * 35.0],
+                    [lbw, 120.0 + brushes.len() as f32 * 35.0],
                     imgui::Condition::Appearing,
                 )
                 .position(
@@ -335,6 +460,16 @@ impl Gui {
                         .range(10.0..=300.0)
                         .display_format(im_str!("%.0f"))
                         .build(ui, &mut cur_brush.radius);
+
+                    let demand = goria.read::<ZoneDemand>();
+                    ui.text(format!("Jobs: {}", demand.jobs));
+                    for (name, goods) in &[("Com.", demand.commercial), ("Ind.", demand.industrial)]
+                    {
+                        match goods.most_wanted {
+                            Some(kind) => ui.text(format!("{}: {} ({})", name, goods.total, kind)),
+                            None => ui.text(format!("{}: {}", name, goods.total)),
+                        }
+                    }
                 });
         }
 
@@ -415,6 +550,24 @@ impl Gui {
                         tok.pop(ui);
                     }
 
+                    for facility in &PARKING_FACILITIES {
+                        let tok =
+                            ui.push_style_var(StyleVar::Alpha(if facility.bkind == cur_kind {
+                                1.0
+                            } else {
+                                0.5
+                            }));
+                        const SCROLLBAR_W: f32 = 10.0;
+                        if ui.button(
+                            &im_str!("{}", facility.name),
+                            [building_select_w - SCROLLBAR_W, 35.0],
+                        ) {
+                            cur_build.opt =
+                                Some((facility.bkind, facility.bgen, facility.size, String::new()));
+                        }
+                        tok.pop(ui);
+                    }
+
                     let bdescrpt_w = 180.0;
 
                     if let Some(descr) = picked_descr {
@@ -439,6 +592,9 @@ impl Gui {
                             .resizable(false)
                             .build(ui, || {
                                 ui.text(im_str!("workers: {}", descr.n_workers));
+                                if descr.power_consumption > 0 {
+                                    ui.text(im_str!("power: {} kW", descr.power_consumption));
+                                }
                                 ui.new_line();
                                 if !descr.recipe.consumption.is_empty() {
                                     ui.text("consumption:");
@@ -454,6 +610,13 @@ impl Gui {
                                     }
                                     ui.new_line();
                                 }
+                                if !descr.stock.is_empty() {
+                                    ui.text("sells:");
+                                    for (kind, n) in &descr.stock {
+                                        ui.text(im_str!("- {} x{}", kind, n));
+                                    }
+                                    ui.new_line();
+                                }
                                 ui.text(im_str!("time: {}s", descr.recipe.complexity));
                                 ui.text(im_str!(
                                     "storage multiplier: {}",
@@ -467,6 +630,34 @@ impl Gui {
         tok.pop(ui);
     }
 
+    /// Tells the player about the commands that were recently rejected (e.g not enough money)
+    pub fn rejected_commands(ui: &Ui<'_>, goria: &Egregoria) {
+        const SHOW_FOR_TICKS: u32 = 100;
+
+        let rejected = goria.read::<RejectedCommands>();
+        let tick = goria.get_tick();
+        let mut recent = rejected
+            .rejected
+            .iter()
+            .filter(|x| x.tick + SHOW_FOR_TICKS >= tick)
+            .peekable();
+        if recent.peek().is_none() {
+            return;
+        }
+
+        let [w, h] = ui.io().display_size;
+        Window::new(im_str!("Rejected"))
+            .position([w * 0.5, h - 60.0], imgui::Condition::Always)
+            .position_pivot([0.5, 1.0])
+            .no_decoration()
+            .always_auto_resize(true)
+            .build(ui, || {
+                for r in recent {
+                    ui.text_colored(common::config().gui_danger.into(), &r.reason);
+                }
+            });
+    }
+
     pub fn inspector(ui: &Ui<'_>, uiworld: &mut UiWorld, goria: &Egregoria) {
         let mut inspected = *uiworld.read::<InspectedEntity>();
         let e = unwrap_or!(inspected.e, return);
@@ -580,6 +771,21 @@ impl Gui {
                 uiworld.save_to_disk();
             }
 
+            let history = goria.read::<MapHistory>();
+            let tok =
+                ui.push_style_var(StyleVar::Alpha(if history.can_undo() { 1.0 } else { 0.5 }));
+            if ui.button(im_str!("Undo"), [50.0, h]) && history.can_undo() {
+                uiworld.commands().undo();
+            }
+            tok.pop(ui);
+            let tok =
+                ui.push_style_var(StyleVar::Alpha(if history.can_redo() { 1.0 } else { 0.5 }));
+            if ui.button(im_str!("Redo"), [50.0, h]) && history.can_redo() {
+                uiworld.commands().redo();
+            }
+            tok.pop(ui);
+            drop(history);
+
             ui.text(format!("Money: {}", goria.read::<Government>().money));
         });
         t.pop(ui);
@@ -0,0 +1,122 @@
+use super::Tool;
+use crate::input::{MouseButton, MouseInfo};
+use crate::rendering::immediate::ImmediateDraw;
+use crate::uiworld::UiWorld;
+use egregoria::transit::{BusStopID, Transit};
+use egregoria::Egregoria;
+use ordered_float::OrderedFloat;
+
+/// Distance under which a stop is picked by the mouse
+const STOP_PICK_DIST: f32 = 8.0;
+
+register_resource_noserialize!(TransitToolResource);
+#[derive(Default)]
+pub struct TransitToolResource {
+    pub mode: TransitMode,
+    /// Stops of the line being built, in order
+    pub line: Vec<BusStopID>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TransitMode {
+    PlaceStops,
+    BuildLine,
+    RemoveStops,
+}
+
+impl Default for TransitMode {
+    fn default() -> Self {
+        TransitMode::PlaceStops
+    }
+}
+
+#[profiling::function]
+pub fn transit(goria: &Egregoria, uiworld: &mut UiWorld) {
+    let tool = *uiworld.read::<Tool>();
+    let mut state = uiworld.write::<TransitToolResource>();
+    let transit = goria.read::<Transit>();
+
+    state.line.retain(|&s| transit.stops().contains_key(s));
+
+    if !matches!(tool, Tool::Transit) {
+        state.line.clear();
+        return;
+    }
+
+    let mouseinfo = uiworld.read::<MouseInfo>();
+    let mut draw = uiworld.write::<ImmediateDraw>();
+    let map = goria.map();
+    let commands = &mut *uiworld.commands();
+
+    for line in transit.lines().values() {
+        let n = line.stops.len();
+        for (i, &a) in line.stops.iter().enumerate() {
+            let b = line.stops[(i + 1) % n];
+            let (a, b) = unwrap_cont!(transit.stops().get(a).zip(transit.stops().get(b)));
+            draw.line(a.pos.up(0.3), b.pos.up(0.3), 1.0)
+                .color(common::config().gui_primary);
+        }
+    }
+
+    for w in state.line.windows(2) {
+        let (a, b) = unwrap_cont!(transit.stops().get(w[0]).zip(transit.stops().get(w[1])));
+        draw.line(a.pos.up(0.35), b.pos.up(0.35), 1.5)
+            .color(common::config().gui_success);
+    }
+
+    for stop in transit.stops().values() {
+        draw.circle(stop.pos.up(0.4), 2.0)
+            .color(common::config().gui_primary);
+        draw.circle(stop.wait_pos.up(0.4), 1.0)
+            .color(common::config().gui_primary);
+    }
+
+    let mpos = unwrap_ret!(mouseinfo.unprojected);
+    let clicked = mouseinfo.just_pressed.contains(&MouseButton::Left);
+
+    let hovered = transit
+        .stops()
+        .iter()
+        .map(|(id, s)| (id, s, s.pos.distance(mpos).min(s.wait_pos.distance(mpos))))
+        .filter(|&(_, _, d)| d < STOP_PICK_DIST)
+        .min_by_key(|&(_, _, d)| OrderedFloat(d))
+        .map(|(id, s, _)| (id, s));
+
+    match state.mode {
+        TransitMode::PlaceStops => match Transit::place_stop(&map, mpos) {
+            Some(stop) => {
+                draw.circle(stop.pos.up(0.5), 2.0)
+                    .color(common::config().gui_success);
+                if clicked {
+                    commands.add_bus_stop(mpos);
+                }
+            }
+            None => {
+                draw.circle(mpos.up(0.5), 2.0)
+                    .color(common::config().gui_disabled);
+            }
+        },
+        TransitMode::BuildLine => {
+            let (id, stop) = unwrap_ret!(hovered);
+            draw.circle(stop.pos.up(0.5), 3.0)
+                .color(common::config().gui_success);
+            if !clicked {
+                return;
+            }
+            if state.line.len() >= 2 && state.line.first() == Some(&id) {
+                // Clicking the first stop again closes the loop
+                commands.add_bus_line(std::mem::take(&mut state.line));
+            } else if state.line.last() != Some(&id) {
+                state.line.push(id);
+            }
+        }
+        TransitMode::RemoveStops => {
+            let (id, stop) = unwrap_ret!(hovered);
+            draw.circle(stop.pos.up(0.5), 3.0)
+                .color(common::config().gui_danger);
+            if clicked {
+                commands.remove_bus_stop(id);
+            }
+        }
+    }
+}
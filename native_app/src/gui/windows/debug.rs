@@ -4,7 +4,7 @@ use crate::game_loop::Timings;
 use crate::gui::InspectedEntity;
 use crate::input::MouseInfo;
 use crate::uiworld::UiWorld;
-use egregoria::map_dynamic::{Itinerary, ParkingManagement};
+use egregoria::map_dynamic::{Itinerary, ParkingManagement, ParkingOccupancy};
 use egregoria::physics::CollisionWorld;
 use egregoria::utils::time::{GameTime, SECONDS_PER_DAY};
 use egregoria::Egregoria;
@@ -40,6 +40,7 @@ impl Default for DebugObjs {
             (false, "Debug lots", debug_lots),
             (false, "Debug road points", debug_road_points),
             (false, "Debug parking", debug_parking),
+            (false, "Debug parking occupancy", debug_parking_occupancy),
             (false, "Show grid", show_grid),
         ])
     }
@@ -344,6 +345,29 @@ pub fn debug_parking(tess: &mut Tesselator, goria: &Egregoria, _: &UiWorld) -> O
     Some(())
 }
 
+pub fn debug_parking_occupancy(
+    tess: &mut Tesselator,
+    goria: &Egregoria,
+    _: &UiWorld,
+) -> Option<()> {
+    let map: &Map = &goria.map();
+    let occupancy = goria.read::<ParkingOccupancy>();
+
+    for (area, occ) in occupancy.areas() {
+        let r = ParkingOccupancy::area_bounds(area);
+        let ratio = occ.ratio();
+        tess.set_color(LinearColor::new(ratio, 1.0 - ratio, 0.0, 0.2));
+        tess.draw_rect_cos_sin(
+            r.center().z(map.terrain.height(r.center()).unwrap_or(0.0)),
+            r.w(),
+            r.h(),
+            Vec2::X,
+        );
+    }
+
+    Some(())
+}
+
 pub fn debug_pathfinder(tess: &mut Tesselator, goria: &Egregoria, uiworld: &UiWorld) -> Option<()> {
     let map: &Map = &goria.map();
     let selected = uiworld.read::<InspectedEntity>().e?;
@@ -1,10 +1,15 @@
 use crate::uiworld::UiWorld;
-use egregoria::economy::{CommodityKind, Market};
+use egregoria::economy::{CommodityKind, Ledger, Market, Money, Taxes};
+use egregoria::souls::population::{Happiness, Population, LEAVE_THRESHOLD};
 use egregoria::Egregoria;
 use imgui::{im_str, Condition, Ui};
+use legion::IntoQuery;
 
-pub fn economy(window: imgui::Window<'_>, ui: &Ui<'_>, _: &mut UiWorld, goria: &Egregoria) {
+pub fn economy(window: imgui::Window<'_>, ui: &Ui<'_>, uiworld: &mut UiWorld, goria: &Egregoria) {
     let market = goria.read::<Market>();
+    let taxes = goria.read::<Taxes>();
+    let ledger = goria.read::<Ledger>();
+    let population = goria.read::<Population>();
     let [w, h] = ui.io().display_size;
 
     window
@@ -14,7 +19,7 @@ pub fn economy(window: imgui::Window<'_>, ui: &Ui<'_>, _: &mut UiWorld, goria: &
         .build(ui, || {
             let inner = market.inner();
 
-            ui.columns(5, im_str!("Economy"), false);
+            ui.columns(6, im_str!("Economy"), false);
 
             ui.text("Commodity");
             ui.next_column();
@@ -26,6 +31,8 @@ pub fn economy(window: imgui::Window<'_>, ui: &Ui<'_>, _: &mut UiWorld, goria: &
             ui.next_column();
             ui.text("Capital");
             ui.next_column();
+            ui.text("Price");
+            ui.next_column();
 
             for kind in CommodityKind::values() {
                 let market = unwrap_or!(inner.get(kind), {
@@ -37,8 +44,8 @@ pub fn economy(window: imgui::Window<'_>, ui: &Ui<'_>, _: &mut UiWorld, goria: &
                 let sell = market.sell_orders();
                 let capital = market.capital_map();
                 let tot_capital = capital.values().sum::<i32>();
-                let offer = sell.values().map(|x| x.1).sum::<i32>();
-                let demand = buy.values().map(|x| x.1).sum::<i32>();
+                let offer = sell.values().map(|x| x.qty).sum::<i32>();
+                let demand = buy.values().map(|x| x.qty).sum::<i32>();
 
                 if tot_capital == 0 && offer == 0 && demand == 0 {
                     continue;
@@ -68,6 +75,90 @@ pub fn economy(window: imgui::Window<'_>, ui: &Ui<'_>, _: &mut UiWorld, goria: &
 
                 ui.text(format!("{}", tot_capital));
                 ui.next_column();
+
+                let history = market
+                    .price_history()
+                    .iter()
+                    .map(|p| p.0 as f32)
+                    .collect::<Vec<_>>();
+                let label = im_str!("##price_{}", kind);
+                let overlay = im_str!("{}", market.price());
+                imgui::PlotLines::new(ui, &label, &history)
+                    .overlay_text(&overlay)
+                    .scale_min(0.0)
+                    .graph_size([0.0, 30.0])
+                    .build();
+                ui.next_column();
+            }
+
+            ui.columns(1, im_str!("Taxes"), false);
+            ui.separator();
+
+            let mut taxes = *taxes;
+            let mut trade_tax = taxes.trade_tax_per_unit.0 as i32;
+            let mut resident_tax = taxes.resident_tax_per_day.0 as i32;
+            let mut changed = imgui::Drag::new(im_str!("trade tax per unit (cents)"))
+                .range(0..=10000)
+                .build(ui, &mut trade_tax);
+            changed |= imgui::Drag::new(im_str!("resident tax per day (cents)"))
+                .range(0..=100000)
+                .build(ui, &mut resident_tax);
+            if changed {
+                taxes.trade_tax_per_unit = Money::cents(trade_tax as i64);
+                taxes.resident_tax_per_day = Money::cents(resident_tax as i64);
+                uiworld.commands().set_taxes(taxes);
+            }
+
+            ui.separator();
+
+            let happiness: Vec<f32> = <&Happiness>::query()
+                .iter(goria.world())
+                .map(|h| h.value)
+                .collect();
+            let average = happiness.iter().sum::<f32>() / happiness.len().max(1) as f32;
+            let unhappy = happiness.iter().filter(|&&h| h < LEAVE_THRESHOLD).count();
+            ui.text(format!(
+                "Adults: {}  Average happiness: {:.0}%  About to leave: {}",
+                happiness.len(),
+                average * 100.0,
+                unhappy
+            ));
+            ui.text(format!(
+                "Moved in: {}  Moved out: {}",
+                population.immigrants, population.emigrants
+            ));
+
+            ui.separator();
+            ui.columns(7, im_str!("Ledger"), false);
+
+            for header in &[
+                "Day",
+                "Trade taxes",
+                "Resident taxes",
+                "Parking fees",
+                "Expenses",
+                "Refunds",
+                "Balance",
+            ] {
+                ui.text(header);
+                ui.next_column();
+            }
+
+            for (day, l) in ledger.days().rev() {
+                ui.text(format!("{}", day));
+                ui.next_column();
+                ui.text(format!("{}", l.trade_taxes));
+                ui.next_column();
+                ui.text(format!("{}", l.resident_taxes));
+                ui.next_column();
+                ui.text(format!("{}", l.parking_fees));
+                ui.next_column();
+                ui.text(format!("{}", l.expenses));
+                ui.next_column();
+                ui.text(format!("{}", l.refunds));
+                ui.next_column();
+                ui.text(format!("{}", l.balance()));
+                ui.next_column();
             }
         });
 }
@@ -0,0 +1,77 @@
+use crate::gui::powerlines::{PowerLinesMode, PowerLinesResource};
+use crate::gui::Tool;
+use crate::uiworld::UiWorld;
+use egregoria::map_dynamic::ElectricityFlow;
+use egregoria::Egregoria;
+use imgui::{im_str, Condition, Ui};
+
+pub fn electricity(
+    window: imgui::Window<'_>,
+    ui: &Ui<'_>,
+    uiworld: &mut UiWorld,
+    goria: &Egregoria,
+) {
+    let flow = goria.read::<ElectricityFlow>();
+
+    window
+        .size([250.0, 300.0], Condition::Appearing)
+        .build(ui, || {
+            let mut tool = uiworld.write::<Tool>();
+            let editing = matches!(*tool, Tool::PowerLines);
+            if ui.small_button(if editing {
+                im_str!("Stop editing")
+            } else {
+                im_str!("Edit power lines")
+            }) {
+                *tool = if editing {
+                    Tool::Hand
+                } else {
+                    Tool::PowerLines
+                };
+            }
+            drop(tool);
+
+            if editing {
+                let mut state = uiworld.write::<PowerLinesResource>();
+                ui.radio_button(
+                    im_str!("Connect buildings"),
+                    &mut state.mode,
+                    PowerLinesMode::Connect,
+                );
+                ui.radio_button(
+                    im_str!("Remove lines"),
+                    &mut state.mode,
+                    PowerLinesMode::Remove,
+                );
+                if matches!(state.mode, PowerLinesMode::Connect) {
+                    ui.text("click the same building twice to stop");
+                }
+            }
+
+            ui.separator();
+            ui.text(format!("{} power lines", goria.map().power_lines().len()));
+
+            for (i, grid) in flow.grids().iter().enumerate() {
+                let sat = grid.satisfaction();
+                let col = if sat < 1.0 {
+                    common::config().gui_danger
+                } else {
+                    common::config().gui_success
+                };
+                ui.text(format!(
+                    "Grid {}: {} buildings",
+                    i + 1,
+                    grid.buildings.len()
+                ));
+                ui.text_colored(
+                    col.into(),
+                    format!(
+                        "  {} / {} kW ({:.0}%)",
+                        grid.production,
+                        grid.consumption,
+                        sat * 100.0
+                    ),
+                );
+            }
+        });
+}
@@ -1,6 +1,7 @@
 use crate::network::NetworkState;
 use crate::uiworld::UiWorld;
 use egregoria::pedestrians::Pedestrian;
+use egregoria::utils::replay::{ReplayPlayer, ReplayRecorder};
 use egregoria::vehicles::Vehicle;
 use egregoria::Egregoria;
 use geom::Camera;
@@ -15,6 +16,15 @@ struct TestFieldProperties {
     spacing: f32,
 }
 
+register_resource_noserialize!(ReplayState);
+#[derive(Default)]
+pub struct ReplayState {
+    pub recorder: Option<ReplayRecorder>,
+    pub player: Option<ReplayPlayer>,
+    /// Set by the UI, the game loop then swaps the world with the replay's start
+    pub load_requested: bool,
+}
+
 pub fn map(window: imgui::Window<'_>, ui: &Ui<'_>, uiworld: &mut UiWorld, goria: &Egregoria) {
     window.build(ui, || {
         if ui.small_button(im_str!("load Paris map")) {
@@ -48,6 +58,37 @@ pub fn map(window: imgui::Window<'_>, ui: &Ui<'_>, uiworld: &mut UiWorld, goria:
             uiworld.commands().reset_save();
         }
 
+        ui.separator();
+        let mut replay = uiworld.write::<ReplayState>();
+        match replay.recorder {
+            Some(ref r) => {
+                ui.text(im_str!("recording... {} commands", r.n_recorded()));
+                if ui.small_button(im_str!("stop and save replay")) {
+                    r.save("replay");
+                    replay.recorder = None;
+                }
+            }
+            None => {
+                if ui.small_button(im_str!("start recording")) {
+                    replay.recorder = ReplayRecorder::new(goria);
+                }
+            }
+        }
+
+        if replay.player.is_some() {
+            ui.text("playing replay, your edits are ignored...");
+            if ui.small_button(im_str!("stop replay")) {
+                replay.player = None;
+            }
+        } else if matches!(
+            *uiworld.read::<NetworkState>(),
+            NetworkState::Singleplayer { .. }
+        ) && ui.small_button(im_str!("play replay"))
+        {
+            replay.load_requested = true;
+        }
+        drop(replay);
+
         ui.text(im_str!(
             "{} pedestrians",
             <&Pedestrian>::query().iter(goria.world()).count()
@@ -7,9 +7,13 @@ use egregoria::Egregoria;
 mod config;
 pub mod debug;
 mod economy;
-mod map;
+mod electricity;
+pub mod map;
 pub mod network;
+mod parking;
 pub mod settings;
+mod terrain;
+mod transit;
 
 pub trait ImguiWindow: Send + Sync {
     fn render_window(
@@ -57,10 +61,18 @@ impl Default for ImguiWindows {
         };
         s.insert(imgui::im_str!("Map"), map::map, true);
         s.insert(imgui::im_str!("Economy"), economy::economy, false);
+        s.insert(imgui::im_str!("Transit"), transit::transit, false);
+        s.insert(
+            imgui::im_str!("Electricity"),
+            electricity::electricity,
+            false,
+        );
         s.insert(imgui::im_str!("Config"), config::config, false);
         s.insert(imgui::im_str!("Debug"), debug::debug, false);
         s.insert(imgui::im_str!("Settings"), settings::settings, false);
         s.insert(imgui::im_str!("Network"), network::network, false);
+        s.insert(imgui::im_str!("Parking"), parking::parking, false);
+        s.insert(imgui::im_str!("Terrain"), terrain::terrain, false);
         s
     }
 }
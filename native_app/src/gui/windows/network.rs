@@ -15,6 +15,7 @@ pub struct NetworkConnectionInfo {
     pub error: String,
     show_hashes: bool,
     hashes: BTreeMap<String, u64>,
+    resync_on_desync: bool,
 }
 
 pub fn network(window: imgui::Window<'_>, ui: &Ui<'_>, uiworld: &mut UiWorld, goria: &Egregoria) {
@@ -45,6 +46,10 @@ pub fn network(window: imgui::Window<'_>, ui: &Ui<'_>, uiworld: &mut UiWorld, go
 
                 ui.separator();
                 ui.input_text(im_str!("IP"), &mut info.ip).build();
+                ui.checkbox(
+                    im_str!("resync automatically on desync"),
+                    &mut info.resync_on_desync,
+                );
                 if ui.small_button(im_str!("Connect")) {
                     if let Some(c) = start_client(&mut info) {
                         *state = NetworkState::Client(c);
@@ -127,6 +132,7 @@ fn start_client(info: &mut NetworkConnectionInfo) -> Option<Client> {
         port: if port != 80 { Some(port) } else { None },
         frame_buffer_advance: 8,
         version: goria_version::VERSION.to_string(),
+        resync_on_desync: info.resync_on_desync,
     }) {
         Ok(x) => x,
         Err(e) => {
@@ -146,6 +152,7 @@ impl Default for NetworkConnectionInfo {
             error: String::new(),
             show_hashes: false,
             hashes: Default::default(),
+            resync_on_desync: true,
         }
     }
 }
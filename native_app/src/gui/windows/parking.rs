@@ -0,0 +1,72 @@
+use crate::uiworld::UiWorld;
+use egregoria::economy::Money;
+use egregoria::map_dynamic::{Occupancy, ParkingManagement, ParkingOccupancy, OCCUPANCY_AREA_SIZE};
+use egregoria::Egregoria;
+use imgui::{im_str, Condition, Ui};
+
+pub fn parking(window: imgui::Window<'_>, ui: &Ui<'_>, uiworld: &mut UiWorld, goria: &Egregoria) {
+    let pm = goria.read::<ParkingManagement>();
+    let occupancy = goria.read::<ParkingOccupancy>();
+
+    window
+        .size([400.0, 400.0], Condition::Appearing)
+        .build(ui, || {
+            let mut prices = pm.prices;
+            let mut street = prices.street.0 as i32;
+            let mut lot = prices.lot.0 as i32;
+            let mut garage = prices.garage.0 as i32;
+            let mut changed = imgui::Drag::new(im_str!("street parking (cents)"))
+                .range(0..=10000)
+                .build(ui, &mut street);
+            changed |= imgui::Drag::new(im_str!("parking lots (cents)"))
+                .range(0..=10000)
+                .build(ui, &mut lot);
+            changed |= imgui::Drag::new(im_str!("parking garages (cents)"))
+                .range(0..=10000)
+                .build(ui, &mut garage);
+            if changed {
+                prices.street = Money::cents(street as i64);
+                prices.lot = Money::cents(lot as i64);
+                prices.garage = Money::cents(garage as i64);
+                uiworld.commands().set_parking_prices(prices);
+            }
+
+            ui.separator();
+            let total = occupancy.total();
+            ui.text(format!("Occupancy: {:.0}%", total.ratio() * 100.0));
+
+            ui.separator();
+            ui.text(format!(
+                "Per area of {}m x {}m",
+                OCCUPANCY_AREA_SIZE, OCCUPANCY_AREA_SIZE
+            ));
+            ui.columns(4, im_str!("Parking occupancy"), false);
+            for header in &["Area", "Street", "Off-street", "Occupancy"] {
+                ui.text(header);
+                ui.next_column();
+            }
+
+            for ((x, y), occ) in occupancy.areas() {
+                ui.text(format!("{}, {}", x, y));
+                ui.next_column();
+                ui.text(format!("{}/{}", occ.street_used, occ.street_total));
+                ui.next_column();
+                ui.text(format!("{}/{}", occ.offstreet_used, occ.offstreet_total));
+                ui.next_column();
+                ui.text_colored(occupancy_color(occ), format!("{:.0}%", occ.ratio() * 100.0));
+                ui.next_column();
+            }
+            ui.columns(1, im_str!("Parking occupancy end"), false);
+        });
+}
+
+fn occupancy_color(occ: &Occupancy) -> [f32; 4] {
+    let r = occ.ratio();
+    if r > 0.9 {
+        common::config().gui_danger.into()
+    } else if r > 0.7 {
+        [0.8, 0.6, 0.2, 1.0]
+    } else {
+        common::config().gui_success.into()
+    }
+}
@@ -0,0 +1,47 @@
+use crate::gui::terraform::{TerraformMode, TerraformResource};
+use crate::gui::Tool;
+use crate::uiworld::UiWorld;
+use egregoria::Egregoria;
+use imgui::{im_str, Condition, Ui};
+
+pub fn terrain(window: imgui::Window<'_>, ui: &Ui<'_>, uiworld: &mut UiWorld, _: &Egregoria) {
+    window
+        .size([250.0, 200.0], Condition::Appearing)
+        .build(ui, || {
+            let mut tool = uiworld.write::<Tool>();
+            let editing = matches!(*tool, Tool::Terraform);
+            if ui.small_button(if editing {
+                im_str!("Stop editing")
+            } else {
+                im_str!("Sculpt terrain")
+            }) {
+                *tool = if editing { Tool::Hand } else { Tool::Terraform };
+            }
+            drop(tool);
+
+            if !editing {
+                return;
+            }
+
+            let mut state = uiworld.write::<TerraformResource>();
+            ui.radio_button(im_str!("Raise"), &mut state.mode, TerraformMode::Raise);
+            ui.radio_button(im_str!("Lower"), &mut state.mode, TerraformMode::Lower);
+            ui.radio_button(im_str!("Flatten"), &mut state.mode, TerraformMode::Flatten);
+            ui.radio_button(im_str!("Smooth"), &mut state.mode, TerraformMode::Smooth);
+
+            imgui::Drag::new(im_str!("radius"))
+                .range(40.0..=500.0)
+                .display_format(im_str!("%.0f"))
+                .build(ui, &mut state.radius);
+            if matches!(state.mode, TerraformMode::Raise | TerraformMode::Lower) {
+                imgui::Drag::new(im_str!("amount"))
+                    .range(1.0..=50.0)
+                    .display_format(im_str!("%.0f m"))
+                    .build(ui, &mut state.amount);
+            }
+            if matches!(state.mode, TerraformMode::Flatten) {
+                ui.text("flattens to the height under the cursor");
+            }
+            ui.text("can't sculpt near roads and buildings");
+        });
+}
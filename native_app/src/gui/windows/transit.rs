@@ -0,0 +1,68 @@
+use crate::gui::transit::{TransitMode, TransitToolResource};
+use crate::gui::Tool;
+use crate::uiworld::UiWorld;
+use egregoria::transit::Transit;
+use egregoria::Egregoria;
+use imgui::{im_str, Condition, Ui};
+
+pub fn transit(window: imgui::Window<'_>, ui: &Ui<'_>, uiworld: &mut UiWorld, goria: &Egregoria) {
+    let transit = goria.read::<Transit>();
+
+    window
+        .size([250.0, 300.0], Condition::Appearing)
+        .build(ui, || {
+            let mut tool = uiworld.write::<Tool>();
+            let editing = matches!(*tool, Tool::Transit);
+            if ui.small_button(if editing {
+                im_str!("Stop editing")
+            } else {
+                im_str!("Edit stops and lines")
+            }) {
+                *tool = if editing { Tool::Hand } else { Tool::Transit };
+            }
+            drop(tool);
+
+            if editing {
+                let mut state = uiworld.write::<TransitToolResource>();
+                ui.radio_button(
+                    im_str!("Place stops"),
+                    &mut state.mode,
+                    TransitMode::PlaceStops,
+                );
+                ui.radio_button(
+                    im_str!("Build line"),
+                    &mut state.mode,
+                    TransitMode::BuildLine,
+                );
+                ui.radio_button(
+                    im_str!("Remove stops"),
+                    &mut state.mode,
+                    TransitMode::RemoveStops,
+                );
+
+                if matches!(state.mode, TransitMode::BuildLine) {
+                    ui.text(format!("{} stops selected", state.line.len()));
+                    ui.text("click the first stop again to finish");
+                    if ui.small_button(im_str!("Cancel line")) {
+                        state.line.clear();
+                    }
+                }
+            }
+
+            ui.separator();
+            ui.text(format!("{} stops", transit.stops().len()));
+
+            for (i, (id, line)) in transit.lines().iter().enumerate() {
+                ui.text(format!(
+                    "Line {}: {} stops, {} buses",
+                    i + 1,
+                    line.stops.len(),
+                    line.buses.len()
+                ));
+                ui.same_line(0.0);
+                if ui.small_button(&*im_str!("remove##line{}", i)) {
+                    uiworld.commands().remove_bus_line(id);
+                }
+            }
+        });
+}
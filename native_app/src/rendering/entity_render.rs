@@ -1,6 +1,6 @@
 use egregoria::map_dynamic::Itinerary;
 use egregoria::pedestrians::{Location, Pedestrian};
-use egregoria::vehicles::{Vehicle, VehicleKind};
+use egregoria::vehicles::{Vehicle, VehicleKind, VehicleState};
 use egregoria::Egregoria;
 use geom::{LinearColor, Transform, Vec3, V3};
 use legion::query::IntoQuery;
@@ -46,8 +46,18 @@ impl InstancedRender {
 
             match v.kind {
                 VehicleKind::Car => self.cars.instances.push(instance),
-                VehicleKind::Truck => self.trucks.instances.push(instance),
-                _ => {}
+                // no bus model yet, trucks are close enough
+                VehicleKind::Truck | VehicleKind::Bus => self.trucks.instances.push(instance),
+                // no bike model yet, show the rider instead
+                VehicleKind::Bike => {
+                    if !matches!(v.state, VehicleState::Locked) {
+                        self.pedestrians.instances.push(MeshInstance {
+                            pos: trans.position.up(0.5),
+                            tint: LinearColor::WHITE,
+                            ..instance
+                        });
+                    }
+                }
             }
         }
 
@@ -49,18 +49,18 @@ impl MapMeshHandler {
         let mut buildmeshes = FastMap::default();
 
         for descr in goria.read::<GoodsCompanyRegistry>().descriptions.values() {
-            let asset = descr.asset_location;
+            let asset = &descr.asset_location;
             if !asset.ends_with(".png") {
                 continue;
             }
             buildsprites.insert(
                 descr.bkind,
-                SpriteBatchBuilder::new(gfx.texture(asset, asset)),
+                SpriteBatchBuilder::new(gfx.texture(asset, "goods company")),
             );
         }
 
         for descr in goria.read::<GoodsCompanyRegistry>().descriptions.values() {
-            let asset = descr.asset_location;
+            let asset = &descr.asset_location;
             if !asset.ends_with(".glb") {
                 continue;
             }
@@ -418,10 +418,19 @@ impl MapBuilders {
             let col = match lot.kind {
                 LotKind::Unassigned => common::config().lot_unassigned_col,
                 LotKind::Residential => common::config().lot_residential_col,
+                LotKind::Commercial => common::config().lot_commercial_col,
+                LotKind::Industrial => common::config().lot_industrial_col,
             };
             tess.set_color(col);
             tess.draw_filled_polygon(&lot.shape.corners, lot.height + 0.3);
         }
+
+        // Power lines
+        tess.set_color(line_col);
+        for line in map.power_lines().values() {
+            let (a, b) = unwrap_cont!(line.ends(map.buildings()));
+            tess.draw_stroke(a, b, 0.3);
+        }
     }
 }
 
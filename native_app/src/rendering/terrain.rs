@@ -1,7 +1,7 @@
 use crate::uiworld::UiWorld;
 use common::FastMap;
 use geom::{vec2, vec3, Camera, LinearColor, Polygon, Vec2};
-use map_model::{Map, CELL_SIZE, CHUNK_RESOLUTION, CHUNK_SIZE};
+use map_model::{Chunk, Map, CELL_SIZE, CHUNK_RESOLUTION, CHUNK_SIZE};
 use std::mem::MaybeUninit;
 use std::ops::Sub;
 use std::sync::Arc;
@@ -14,7 +14,8 @@ const LOD: usize = 4;
 
 struct TerrainChunk {
     lods: [Mesh; LOD],
-    dirt_id: u32,
+    /// Dirt ids of the chunk and of its right, up and upright neighbours, whose edges it uses
+    dirt_ids: [u32; 4],
 }
 
 pub struct TerrainRender {
@@ -58,10 +59,21 @@ impl TerrainRender {
             "trying to update nonexistent chunk"
         );
 
+        let right_chunk = map.terrain.chunks.get(&(cell.0 + 1, cell.1));
+        let up_chunk = map.terrain.chunks.get(&(cell.0, cell.1 + 1));
+        let upright_chunk = map.terrain.chunks.get(&(cell.0 + 1, cell.1 + 1));
+
+        let dirt = |c: Option<&Chunk>| c.map(|c| c.dirt_id.0).unwrap_or_default();
+        let dirt_ids = [
+            chunk.dirt_id.0,
+            dirt(right_chunk),
+            dirt(up_chunk),
+            dirt(upright_chunk),
+        ];
         if self
             .chunks
             .get(&cell)
-            .map(|x| x.dirt_id == chunk.dirt_id.0)
+            .map(|x| x.dirt_ids == dirt_ids)
             .unwrap_or_default()
         {
             return;
@@ -69,10 +81,6 @@ impl TerrainRender {
 
         let mut v = vec![];
 
-        let right_chunk = map.terrain.chunks.get(&(cell.0 + 1, cell.1));
-        let up_chunk = map.terrain.chunks.get(&(cell.0, cell.1 + 1));
-        let upright_chunk = map.terrain.chunks.get(&(cell.0 + 1, cell.1 + 1));
-
         for lod in 0..LOD {
             let scale = 1 << lod;
             let resolution = CHUNK_RESOLUTION / (1 << lod);
@@ -146,7 +154,7 @@ impl TerrainRender {
 
         let chunk = TerrainChunk {
             lods: collect_arrlod(v),
-            dirt_id: chunk.dirt_id.0,
+            dirt_ids,
         };
         self.chunks.insert(cell, chunk);
     }
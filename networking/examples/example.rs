@@ -59,6 +59,7 @@ pub fn main() {
         port: None,
         frame_buffer_advance: 10,
         version: "v1".to_string(),
+        resync_on_desync: false,
     })
     .unwrap();
 
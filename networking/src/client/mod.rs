@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::io;
 use std::net::{IpAddr, SocketAddr};
 
@@ -15,11 +16,15 @@ use crate::packets::{
 use crate::worldsend::WorldReceive;
 use crate::{
     decode, decode_merged, encode, AuthentID, Frame, PhantomSendSync, PlayerInput, DEFAULT_PORT,
+    HASH_CHECK_PERIOD,
 };
 use common::timestep::Timestep;
 
 mod client_playout;
 
+/// Number of checkpoints the local hashes are kept for while waiting for the server ones
+const MAX_LATE_CHECKPOINTS: usize = 10;
+
 #[derive(Debug)]
 pub struct FrameInputs<I> {
     pub inputs: Vec<ServerInput<I>>,
@@ -32,6 +37,25 @@ pub struct ServerInput<I> {
     pub inp: I,
 }
 
+/// The local world hashes did not match the ones sent by the server
+#[derive(Debug, Clone)]
+pub struct Desync {
+    pub frame: Frame,
+    /// Names of the hashes that differ with the checkpoint they first differed at,
+    /// the ones that diverged first come first
+    pub diverged: Vec<(String, Frame)>,
+}
+
+impl std::fmt::Display for Desync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at {:?} on:", self.frame)?;
+        for (name, frame) in &self.diverged {
+            write!(f, " {} (since {:?})", name, frame)?;
+        }
+        Ok(())
+    }
+}
+
 pub enum PollResult<W, I> {
     Wait(I),
     Input(Vec<FrameInputs<I>>),
@@ -75,6 +99,19 @@ pub struct Client<WORLD: DeserializeOwned, INPUT: Serialize + DeserializeOwned +
     pub step: Timestep,
     lag_compensate: u32,
 
+    resync_on_desync: bool,
+    /// Hashes sent by the server for frames the world didn't reach yet
+    expected_hashes: BTreeMap<Frame, BTreeMap<String, u64>>,
+    /// Local hashes at the checkpoints the server hashes didn't arrive for yet
+    local_hashes: BTreeMap<Frame, BTreeMap<String, u64>>,
+    /// Last frame the hashes were checked at
+    checked_frame: Frame,
+    /// Found when late server hashes arrived, returned by the next `check_hashes`
+    pending_desync: Option<Desync>,
+    last_desync: Option<Desync>,
+    /// Checkpoint at which each hash first differed since the world was received
+    first_diverged: BTreeMap<String, Frame>,
+
     _phantom: PhantomSendSync<(INPUT, WORLD)>,
 }
 
@@ -84,6 +121,8 @@ pub struct ConnectConf {
     pub port: Option<u16>,
     pub frame_buffer_advance: u32,
     pub version: String,
+    /// Ask the server to send the world again when a desync is detected
+    pub resync_on_desync: bool,
 }
 
 impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I> {
@@ -105,9 +144,113 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
             step: Timestep::default(),
             _phantom: Default::default(),
             version: conf.version,
+            resync_on_desync: conf.resync_on_desync,
+            expected_hashes: Default::default(),
+            local_hashes: Default::default(),
+            checked_frame: Frame(0),
+            pending_desync: None,
+            last_desync: None,
+            first_diverged: Default::default(),
         })
     }
 
+    /// Must be called after the world reached `frame`.
+    /// Compares the local hashes with the ones sent by the server, only computing them
+    /// on checkpoints. When the server hashes didn't arrive yet, the local ones are kept
+    /// and compared once they do, the desync is then returned by the next call.
+    pub fn check_hashes(
+        &mut self,
+        frame: Frame,
+        hashes: impl FnOnce() -> BTreeMap<String, u64>,
+    ) -> Option<Desync> {
+        if let Some(desync) = self.pending_desync.take() {
+            return Some(desync);
+        }
+        if !matches!(self.state, ClientState::Playing { .. }) {
+            return None;
+        }
+        self.checked_frame = frame;
+        if frame.0 % HASH_CHECK_PERIOD != 0 {
+            return None;
+        }
+
+        // drop the checkpoints we went past without checking
+        self.expected_hashes = self.expected_hashes.split_off(&frame);
+        let local = hashes();
+        match self.expected_hashes.remove(&frame) {
+            Some(expected) => self.compare_hashes(frame, &expected, &local),
+            None => {
+                if self.local_hashes.len() == MAX_LATE_CHECKPOINTS {
+                    let oldest = *self.local_hashes.keys().next().unwrap();
+                    self.local_hashes.remove(&oldest);
+                }
+                self.local_hashes.insert(frame, local);
+                None
+            }
+        }
+    }
+
+    fn compare_hashes(
+        &mut self,
+        frame: Frame,
+        expected: &BTreeMap<String, u64>,
+        local: &BTreeMap<String, u64>,
+    ) -> Option<Desync> {
+        let differs = expected
+            .iter()
+            .filter(|&(name, hash)| local.get(name) != Some(hash))
+            .map(|(name, _)| name)
+            .chain(local.keys().filter(|name| !expected.contains_key(*name)));
+
+        let mut new = false;
+        for name in differs {
+            if !self.first_diverged.contains_key(name) {
+                self.first_diverged.insert(name.clone(), frame);
+                new = true;
+            }
+        }
+        // already reported
+        if !new {
+            return None;
+        }
+
+        let mut diverged: Vec<(String, Frame)> = self
+            .first_diverged
+            .iter()
+            .map(|(name, &frame)| (name.clone(), frame))
+            .collect();
+        diverged.sort_by_key(|&(_, frame)| frame);
+        let desync = Desync { frame, diverged };
+
+        log::error!("{}: desync detected {}", self.name, desync);
+
+        self.network.send(
+            self.tcp,
+            &*encode(&ClientReliablePacket::Desync {
+                frame,
+                diverged: desync.diverged.clone(),
+            }),
+        );
+
+        if let ClientState::Playing { id, .. } = self.state {
+            if self.resync_on_desync {
+                log::info!("{}: asking the server for the world again", self.name);
+                self.network
+                    .send(self.tcp, &*encode(&ClientReliablePacket::Resync));
+                self.state = ClientState::Downloading {
+                    id,
+                    wr: WorldReceive::default(),
+                };
+                self.first_diverged.clear();
+            }
+        }
+        self.expected_hashes.clear();
+        self.local_hashes.clear();
+
+        self.last_desync = Some(desync.clone());
+        Some(desync)
+    }
+
     #[allow(clippy::collapsible_if)]
     pub fn poll(&mut self, input: I) -> PollResult<W, I> {
         while let Some(x) = self.events.try_receive() {
@@ -180,6 +323,7 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
                 {
                     self.network
                         .send(self.tcp, &*encode(&ClientReliablePacket::BeginCatchUp));
+                    self.first_diverged.clear();
                     return PollResult::GameWorld(input, world);
                 } else {
                     unreachable!()
@@ -269,6 +413,20 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
                     log::error!("received world but was not downloading.. weird");
                 }
             }
+            ServerReliablePacket::Hashes { frame, hashes } => {
+                if let ClientState::Playing { .. } = self.state {
+                    if let Some(local) = self.local_hashes.remove(&frame) {
+                        let desync = self.compare_hashes(frame, &hashes, &local);
+                        if desync.is_some() {
+                            self.pending_desync = desync;
+                        }
+                    } else if frame > self.checked_frame {
+                        self.expected_hashes.insert(frame, hashes);
+                    } else {
+                        log::warn!("{}: hashes for {:?} came too late", self.name, frame);
+                    }
+                }
+            }
             ServerReliablePacket::Challenge(challenge) => {
                 log::info!("{}: received challenge", self.name);
                 self.network.send(
@@ -391,7 +549,7 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
     }
 
     pub fn describe(&self) -> String {
-        match self.state {
+        let mut s = match self.state {
             ClientState::Connecting => "Connecting...".to_string(),
             ClientState::Downloading { .. } => "Downloading map...".to_string(),
             ClientState::CatchingUp { .. } => "Catching up...".to_string(),
@@ -401,6 +559,10 @@ impl<W: DeserializeOwned, I: Serialize + DeserializeOwned + Default> Client<W, I
                 format!("Playing! Buffer advance: {}", buf.advance())
             }
             ClientState::Disconnected { ref reason } => reason.clone(),
+        };
+        if let Some(ref desync) = self.last_desync {
+            s += &*format!("\nDesynced {}", desync);
         }
+        s
     }
 }
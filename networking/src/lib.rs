@@ -41,11 +41,13 @@ mod server;
 mod worldsend;
 
 use crate::client::FrameInputs;
-pub use client::{Client, ConnectConf, PollResult, ServerInput};
+pub use client::{Client, ConnectConf, Desync, PollResult, ServerInput};
 pub use server::{Server, ServerConfiguration, ServerPollResult, VirtualClientConf};
 
 pub(crate) const MAX_WORLDSEND_PACKET_SIZE: usize = 262144; //32 ko at least 1.3Mo per s at 50FPS
 pub(crate) const DEFAULT_PORT: u16 = 23019;
+/// Number of frames between two hash checkpoints sent by the server
+pub(crate) const HASH_CHECK_PERIOD: u32 = 100;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
 #[repr(transparent)]
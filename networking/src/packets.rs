@@ -1,6 +1,7 @@
 use crate::authent::AuthentID;
 use crate::{Frame, MergedInputs, PlayerInput};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::time::Duration;
 
 #[derive(Serialize, Deserialize)]
@@ -21,6 +22,10 @@ pub(crate) enum ServerReliablePacket {
         inputs: Vec<MergedInputs>,
     },
     WorldSend(WorldDataFragment),
+    Hashes {
+        frame: Frame,
+        hashes: BTreeMap<String, u64>,
+    },
 }
 
 #[derive(Serialize, Deserialize)]
@@ -31,10 +36,19 @@ pub(crate) enum ClientUnreliablePacket {
 
 #[derive(Serialize, Deserialize)]
 pub(crate) enum ClientReliablePacket {
-    Connect { name: String, version: String },
+    Connect {
+        name: String,
+        version: String,
+    },
     BeginCatchUp,
     CatchUpAck,
     WorldAck,
+    Desync {
+        frame: Frame,
+        diverged: Vec<(String, Frame)>,
+    },
+    /// Asks for the world to be sent again
+    Resync,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
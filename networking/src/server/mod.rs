@@ -14,9 +14,13 @@ use crate::packets::{
 };
 use crate::server::server_playout::ServerPlayoutBuffer;
 use crate::worldsend::WorldSend;
-use crate::{decode, decode_merged, encode, Frame, PhantomSendSync, PlayerInput, DEFAULT_PORT};
+use crate::{
+    decode, decode_merged, encode, Frame, PhantomSendSync, PlayerInput, DEFAULT_PORT,
+    HASH_CHECK_PERIOD,
+};
 use common::timestep::Timestep;
 use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
 
 mod server_playout;
@@ -142,6 +146,22 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
         ServerPollResult::Wait(local_inputs)
     }
 
+    /// Must be called after the world reached `frame`.
+    /// Periodically sends the world hashes to the playing clients so they can detect desyncs.
+    pub fn check_hashes(&mut self, frame: Frame, hashes: impl FnOnce() -> BTreeMap<String, u64>) {
+        if frame.0 % HASH_CHECK_PERIOD != 0 || self.authent.iter_playing().next().is_none() {
+            return;
+        }
+
+        let packet = encode(&ServerReliablePacket::Hashes {
+            frame,
+            hashes: hashes(),
+        });
+        for c in self.authent.iter_playing() {
+            self.network.send(c.reliable, &*packet);
+        }
+    }
+
     fn send_merged_inputs(&mut self) {
         let n_playing = self.authent.iter_playing().count() + self.v_client.is_some() as usize;
 
@@ -248,14 +268,7 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
 
                 match auth_r {
                     AuthentResponse::Accepted { .. } => {
-                        let c = self.authent.get_client(e)?;
-                        let (w, w_frame) = world();
-                        assert_eq!(self.buffer.consumed_frame, w_frame);
-                        self.worldsend.begin_send(c, encode(&w), w_frame);
-                        self.catchup
-                            .begin_remembering(self.buffer.consumed_frame, c);
-
-                        self.authent.get_client_mut(e)?.state = ClientGameState::Downloading;
+                        self.send_world(e, world)?;
                     }
                     AuthentResponse::Refused { reason } => {
                         log::error!("refused authent because: {}", reason);
@@ -279,10 +292,46 @@ impl<WORLD: 'static + Serialize, INPUT: Serialize + DeserializeOwned> Server<WOR
                 log::info!("client {} world rcv acked", c.name);
                 self.worldsend.ack(c);
             }
+            ClientReliablePacket::Desync { frame, diverged } => {
+                let c = self.authent.get_client(e)?;
+                let diverged: Vec<String> = diverged
+                    .iter()
+                    .map(|(name, since)| format!("{} (since {:?})", name, since))
+                    .collect();
+                log::error!(
+                    "client {} desynced at {:?} on: {}",
+                    c.name,
+                    frame,
+                    diverged.join(", ")
+                );
+            }
+            ClientReliablePacket::Resync => {
+                let c = self.authent.get_client(e)?;
+                if c.state != ClientGameState::Playing {
+                    log::warn!("client {} asked for a resync while {:?}", c.name, c.state);
+                    return None;
+                }
+                log::info!("resending world to {}", c.name);
+                self.send_world(e, world)?;
+            }
         }
         Some(())
     }
 
+    fn send_world(&mut self, e: Endpoint, world: &impl Fn() -> (WORLD, Frame)) -> Option<()> {
+        let c = self.authent.get_client(e)?;
+        let (w, w_frame) = world();
+        assert_eq!(self.buffer.consumed_frame, w_frame);
+        self.worldsend.begin_send(c, encode(&w), w_frame);
+        self.catchup
+            .begin_remembering(self.buffer.consumed_frame, c);
+
+        let c = self.authent.get_client_mut(e)?;
+        c.state = ClientGameState::Downloading;
+        c.ack = w_frame;
+        Some(())
+    }
+
     fn tcp_connected(&mut self, e: Endpoint) {
         self.authent.tcp_connected(e, &mut self.network)
     }